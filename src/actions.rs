@@ -0,0 +1,893 @@
+use bevy::{
+    input::{
+        gamepad::{GamepadEvent, GamepadEventType},
+        mouse::{MouseScrollUnit, MouseWheel},
+        InputSystem,
+    },
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_config::{changed_config, ConfigChanged},
+    tick::{FixedTickStage, FixedTickSystems},
+    GameAssets,
+};
+
+/// Analog inputs below this are ignored, to not pick up drifting gamepad sticks
+const AXIS_DEAD_ZONE: f32 = 0.2;
+/// Analog inputs above this count as pressed
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+/// How many pixels of scrolling (as reported by touchpads) count as one scrolled line
+const PIXELS_PER_SCROLL_LINE: f32 = 50.;
+
+/// The gamepad axes checked when capturing a new binding
+const GAMEPAD_AXES: [GamepadAxisType; 8] = [
+    GamepadAxisType::LeftStickX,
+    GamepadAxisType::LeftStickY,
+    GamepadAxisType::LeftZ,
+    GamepadAxisType::RightStickX,
+    GamepadAxisType::RightStickY,
+    GamepadAxisType::RightZ,
+    GamepadAxisType::DPadX,
+    GamepadAxisType::DPadY,
+];
+
+#[derive(Debug, Default)]
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputBindings>()
+            .init_resource::<ActionState>()
+            .init_resource::<TickActions>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_action_state
+                    .label(ActionSystems::Update)
+                    .after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                collect_tick_presses.after(ActionSystems::Update),
+            )
+            .add_system_to_stage(
+                FixedTickStage,
+                latch_tick_actions.label(FixedTickSystems::Prepare),
+            )
+            .add_system(load_bindings_from_config);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum ActionSystems {
+    Update,
+}
+
+/// Everything the player can do, systems should check these instead of raw inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    CameraPanLeft,
+    CameraPanRight,
+    CameraPanUp,
+    CameraPanDown,
+    CameraDrag,
+    ZoomIn,
+    ZoomOut,
+    ToggleFreeCamera,
+    ToggleDebugOverlay,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Interact,
+    MenuConfirm,
+    MenuBack,
+}
+
+impl Action {
+    pub const ALL: [Action; 15] = [
+        Action::CameraPanLeft,
+        Action::CameraPanRight,
+        Action::CameraPanUp,
+        Action::CameraPanDown,
+        Action::CameraDrag,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ToggleFreeCamera,
+        Action::ToggleDebugOverlay,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Interact,
+        Action::MenuConfirm,
+        Action::MenuBack,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+/// A physical input that can trigger an [`Action`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Scrolling the mouse wheel, the value is the number of lines scrolled this frame
+    MouseWheel(AxisDirection),
+    GamepadButton(GamepadButtonType),
+    GamepadAxis(GamepadAxisType, AxisDirection),
+}
+
+impl Binding {
+    /// Parses bindings as written in the `[input]` section of `game.config`
+    ///
+    /// The format is `<Device>:<Input>`, for example `Key:Space`, `Mouse:Right`, `Wheel:Up`,
+    /// `GamepadButton:South` or `GamepadAxis:LeftStickX-`.
+    pub fn parse(binding: &str) -> Result<Binding, String> {
+        let (device, input) = binding
+            .split_once(':')
+            .ok_or_else(|| format!("Binding '{}' is missing a ':'", binding))?;
+
+        let parsed = match device {
+            "Key" => parse_key_code(input).map(Binding::Key),
+            "Mouse" => parse_mouse_button(input).map(Binding::Mouse),
+            "Wheel" => match input {
+                "Up" => Some(Binding::MouseWheel(AxisDirection::Positive)),
+                "Down" => Some(Binding::MouseWheel(AxisDirection::Negative)),
+                _ => None,
+            },
+            "GamepadButton" => parse_gamepad_button(input).map(Binding::GamepadButton),
+            "GamepadAxis" => {
+                let (axis, direction) = if let Some(axis) = input.strip_suffix('+') {
+                    (axis, AxisDirection::Positive)
+                } else if let Some(axis) = input.strip_suffix('-') {
+                    (axis, AxisDirection::Negative)
+                } else {
+                    return Err(format!(
+                        "Gamepad axis '{}' needs a direction ('+' or '-')",
+                        binding
+                    ));
+                };
+
+                parse_gamepad_axis(axis).map(|axis| Binding::GamepadAxis(axis, direction))
+            }
+            _ => return Err(format!("Unknown input device '{}'", device)),
+        };
+
+        parsed.ok_or_else(|| format!("Unknown input '{}' for device '{}'", input, device))
+    }
+}
+
+/// Writes the binding the way [`Binding::parse`] reads it
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = |direction: AxisDirection| match direction {
+            AxisDirection::Positive => '+',
+            AxisDirection::Negative => '-',
+        };
+
+        match *self {
+            Binding::Key(key) => write!(f, "Key:{:?}", key),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse:{}", button),
+            Binding::Mouse(button) => write!(f, "Mouse:{:?}", button),
+            Binding::MouseWheel(AxisDirection::Positive) => write!(f, "Wheel:Up"),
+            Binding::MouseWheel(AxisDirection::Negative) => write!(f, "Wheel:Down"),
+            Binding::GamepadButton(button) => write!(f, "GamepadButton:{:?}", button),
+            Binding::GamepadAxis(axis, direction) => {
+                write!(f, "GamepadAxis:{:?}{}", axis, sign(direction))
+            }
+        }
+    }
+}
+
+/// Which [`Binding`]s trigger which [`Action`]s, can be changed at runtime
+///
+/// The player's changes are kept apart from the bindings of `game.config`, so reloading the
+/// config does not undo them.
+#[derive(Debug)]
+pub struct InputBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+    /// Rebound at runtime, these replace the configured bindings of their action
+    overrides: HashMap<Action, Vec<Binding>>,
+    capture: Option<Action>,
+    /// Whether something else, like the console, takes all input
+    suspended: bool,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use self::AxisDirection::*;
+
+        let mut bindings = InputBindings {
+            bindings: HashMap::default(),
+            overrides: HashMap::default(),
+            capture: None,
+            suspended: false,
+        };
+
+        bindings.set(Action::CameraPanLeft, vec![Binding::Key(KeyCode::Left)]);
+        bindings.set(Action::CameraPanRight, vec![Binding::Key(KeyCode::Right)]);
+        bindings.set(Action::CameraPanUp, vec![Binding::Key(KeyCode::Up)]);
+        bindings.set(Action::CameraPanDown, vec![Binding::Key(KeyCode::Down)]);
+        bindings.set(Action::CameraDrag, vec![Binding::Mouse(MouseButton::Right)]);
+        bindings.set(Action::ZoomIn, vec![Binding::MouseWheel(Positive)]);
+        bindings.set(Action::ZoomOut, vec![Binding::MouseWheel(Negative)]);
+        bindings.set(Action::ToggleFreeCamera, vec![Binding::Key(KeyCode::F2)]);
+        bindings.set(Action::ToggleDebugOverlay, vec![Binding::Key(KeyCode::F3)]);
+        bindings.set(
+            Action::MoveLeft,
+            vec![
+                Binding::Key(KeyCode::A),
+                Binding::GamepadButton(GamepadButtonType::DPadLeft),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickX, Negative),
+            ],
+        );
+        bindings.set(
+            Action::MoveRight,
+            vec![
+                Binding::Key(KeyCode::D),
+                Binding::GamepadButton(GamepadButtonType::DPadRight),
+                Binding::GamepadAxis(GamepadAxisType::LeftStickX, Positive),
+            ],
+        );
+        bindings.set(
+            Action::Jump,
+            vec![
+                Binding::Key(KeyCode::Space),
+                Binding::GamepadButton(GamepadButtonType::South),
+            ],
+        );
+        bindings.set(
+            Action::Interact,
+            vec![
+                Binding::Key(KeyCode::E),
+                Binding::GamepadButton(GamepadButtonType::West),
+            ],
+        );
+        bindings.set(
+            Action::MenuConfirm,
+            vec![
+                Binding::Key(KeyCode::Return),
+                Binding::GamepadButton(GamepadButtonType::South),
+            ],
+        );
+        bindings.set(
+            Action::MenuBack,
+            vec![
+                Binding::Key(KeyCode::Escape),
+                Binding::GamepadButton(GamepadButtonType::East),
+            ],
+        );
+
+        bindings
+    }
+}
+
+impl InputBindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.overrides
+            .get(&action)
+            .or_else(|| self.bindings.get(&action))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Replaces the configured bindings of `action`, a runtime [`rebind`](Self::rebind) stays
+    /// in effect
+    pub fn set(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    /// Replaces all bindings of `action` until it is [`reset`](Self::reset)
+    pub fn rebind(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.overrides.insert(action, bindings);
+    }
+
+    /// Goes back to the configured bindings of `action`
+    pub fn reset(&mut self, action: Action) {
+        self.overrides.remove(&action);
+    }
+
+    /// Adds a binding to `action`, keeping the existing ones
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let mut bindings = self.get(action).to_vec();
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+            self.rebind(action, bindings);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        let mut bindings = self.get(action).to_vec();
+        bindings.retain(|bound| *bound != binding);
+        self.rebind(action, bindings);
+    }
+
+    /// The next input that gets pressed [`rebind`](Self::rebind)s `action`
+    ///
+    /// Besides buttons, scrolling the mouse wheel and pushing a gamepad axis past the press
+    /// threshold are captured as well. Nothing is captured while the bindings are suspended, so
+    /// binding from the console waits for it to close.
+    pub fn capture_next(&mut self, action: Action) {
+        self.capture = Some(action);
+    }
+
+    /// The action currently waiting for [`InputBindings::capture_next`]
+    pub fn capturing(&self) -> Option<Action> {
+        self.capture
+    }
+
+    /// Releases all actions and ignores the inputs until resumed, inputs still held by then are
+    /// ignored until they are released
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+}
+
+/// The state of a single [`Action`], written to replays
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ActionData {
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+    value: f32,
+}
+
+/// The state of all [`Action`]s this frame
+#[derive(Debug, Default)]
+pub struct ActionState {
+    actions: HashMap<Action, ActionData>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.actions.get(&action).map_or(false, |data| data.pressed)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.actions
+            .get(&action)
+            .map_or(false, |data| data.just_pressed)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.actions
+            .get(&action)
+            .map_or(false, |data| data.just_released)
+    }
+
+    /// How strongly the action is triggered, `1.` for pressed buttons
+    ///
+    /// For analog inputs this is the deflection of the axis and for the mouse wheel the amount
+    /// scrolled this frame.
+    pub fn value(&self, action: Action) -> f32 {
+        self.actions.get(&action).map_or(0., |data| data.value)
+    }
+
+    /// Combines two opposing actions into a value between `-1.` and `1.`
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        (self.value(positive).min(1.) - self.value(negative).min(1.)).clamp(-1., 1.)
+    }
+}
+
+/// The [`ActionState`] as seen by the systems in the [`FixedTickStage`]
+///
+/// A frame can run several ticks or none at all, so `just_pressed` and `just_released` are
+/// relative to the previous tick. Presses between two ticks are kept for the next one, even when
+/// the button was released again in the meantime.
+#[derive(Debug, Default)]
+pub struct TickActions {
+    state: ActionState,
+    pending_presses: HashSet<Action>,
+}
+
+impl std::ops::Deref for TickActions {
+    type Target = ActionState;
+
+    fn deref(&self) -> &ActionState {
+        &self.state
+    }
+}
+
+impl TickActions {
+    /// The actions of this tick that are not idle, in the order of [`Action::ALL`]
+    pub fn active(&self) -> Vec<(Action, ActionData)> {
+        Action::ALL
+            .iter()
+            .filter_map(|action| {
+                let data = self.state.actions.get(action).copied()?;
+                (data != ActionData::default()).then(|| (*action, data))
+            })
+            .collect()
+    }
+
+    /// Replaces the actions of this tick, e.g. with recorded ones, all others are idle
+    pub fn set_active(&mut self, actions: &[(Action, ActionData)]) {
+        self.state.actions.clear();
+        self.state.actions.extend(actions.iter().copied());
+    }
+}
+
+fn collect_tick_presses(action_state: Res<ActionState>, mut tick_actions: ResMut<TickActions>) {
+    for action in Action::ALL {
+        if action_state.just_pressed(action) {
+            tick_actions.pending_presses.insert(action);
+        }
+    }
+}
+
+fn latch_tick_actions(action_state: Res<ActionState>, mut tick_actions: ResMut<TickActions>) {
+    let tick_actions = &mut *tick_actions;
+
+    for action in Action::ALL {
+        let frame = action_state
+            .actions
+            .get(&action)
+            .copied()
+            .unwrap_or_default();
+        let pending_press = tick_actions.pending_presses.remove(&action);
+
+        let data = tick_actions.state.actions.entry(action).or_default();
+        *data = ActionData {
+            pressed: frame.pressed,
+            just_pressed: pending_press || (frame.pressed && !data.pressed),
+            just_released: !frame.pressed && data.pressed,
+            value: frame.value,
+        };
+    }
+}
+
+struct RawInput<'a> {
+    keyboard: &'a Input<KeyCode>,
+    mouse: &'a Input<MouseButton>,
+    wheel: f32,
+    gamepads: &'a HashSet<Gamepad>,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    gamepad_axes: &'a Axis<GamepadAxis>,
+    /// The axes of the previous frame, to tell when an axis was just pushed
+    previous_gamepad_axes: &'a HashMap<GamepadAxis, f32>,
+}
+
+impl RawInput<'_> {
+    fn value(&self, binding: Binding) -> f32 {
+        let pressed = |pressed: bool| if pressed { 1. } else { 0. };
+
+        match binding {
+            Binding::Key(key) => pressed(self.keyboard.pressed(key)),
+            Binding::Mouse(button) => pressed(self.mouse.pressed(button)),
+            Binding::MouseWheel(direction) => directed(self.wheel, direction),
+            Binding::GamepadButton(button) => pressed(
+                self.gamepad_buttons
+                    .get_pressed()
+                    .any(|pressed| pressed.1 == button),
+            ),
+            Binding::GamepadAxis(axis, direction) => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis(*gamepad, axis)))
+                .map(|value| directed(value, direction))
+                .filter(|value| *value > AXIS_DEAD_ZONE)
+                .fold(0., f32::max),
+        }
+    }
+
+    /// The first input that started being pressed this frame, used to capture new bindings
+    fn first_just_pressed(&self) -> Option<Binding> {
+        if let Some(key) = self.keyboard.get_just_pressed().next() {
+            return Some(Binding::Key(*key));
+        }
+        if let Some(button) = self.mouse.get_just_pressed().next() {
+            return Some(Binding::Mouse(*button));
+        }
+        if let Some(button) = self.gamepad_buttons.get_just_pressed().next() {
+            return Some(Binding::GamepadButton(button.1));
+        }
+        if self.wheel > 0. {
+            return Some(Binding::MouseWheel(AxisDirection::Positive));
+        }
+        if self.wheel < 0. {
+            return Some(Binding::MouseWheel(AxisDirection::Negative));
+        }
+
+        for gamepad in self.gamepads.iter() {
+            for axis_type in GAMEPAD_AXES {
+                let axis = GamepadAxis(*gamepad, axis_type);
+                let value = self.gamepad_axes.get(axis).unwrap_or_default();
+                let previous = self
+                    .previous_gamepad_axes
+                    .get(&axis)
+                    .copied()
+                    .unwrap_or_default();
+
+                for direction in [AxisDirection::Positive, AxisDirection::Negative] {
+                    if directed(value, direction) >= AXIS_PRESS_THRESHOLD
+                        && directed(previous, direction) < AXIS_PRESS_THRESHOLD
+                    {
+                        return Some(Binding::GamepadAxis(axis_type, direction));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn directed(value: f32, direction: AxisDirection) -> f32 {
+    match direction {
+        AxisDirection::Positive => value.max(0.),
+        AxisDirection::Negative => (-value).max(0.),
+    }
+}
+
+fn update_action_state(
+    keyboard: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut bindings: ResMut<InputBindings>,
+    mut action_state: ResMut<ActionState>,
+    mut gamepads: Local<HashSet<Gamepad>>,
+    mut previous_gamepad_axes: Local<HashMap<GamepadAxis, f32>>,
+    mut held_while_ignored: Local<HashSet<Action>>,
+) {
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                gamepads.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                gamepads.remove(gamepad);
+            }
+            _ => {}
+        }
+    }
+
+    let raw_input = RawInput {
+        keyboard: &keyboard,
+        mouse: &mouse,
+        wheel: mouse_wheel_events
+            .iter()
+            .map(|wheel| match wheel.unit {
+                MouseScrollUnit::Line => wheel.y,
+                MouseScrollUnit::Pixel => wheel.y / PIXELS_PER_SCROLL_LINE,
+            })
+            .sum(),
+        gamepads: &gamepads,
+        gamepad_buttons: &gamepad_buttons,
+        gamepad_axes: &gamepad_axes,
+        previous_gamepad_axes: &previous_gamepad_axes,
+    };
+
+    // While capturing, the pressed input should not also trigger its old action
+    let ignored = bindings.capture.is_some() || bindings.suspended;
+
+    if let (Some(action), false) = (bindings.capture, bindings.suspended) {
+        if let Some(binding) = raw_input.first_just_pressed() {
+            info!("Bound {:?} to {}", action, binding);
+            bindings.rebind(action, vec![binding]);
+            bindings.capture = None;
+        }
+    }
+
+    let axes = gamepads
+        .iter()
+        .flat_map(|gamepad| {
+            GAMEPAD_AXES
+                .iter()
+                .map(move |axis_type| GamepadAxis(*gamepad, *axis_type))
+        })
+        .map(|axis| (axis, gamepad_axes.get(axis).unwrap_or_default()))
+        .collect();
+
+    for action in Action::ALL {
+        let value: f32 = bindings
+            .get(action)
+            .iter()
+            .map(|binding| raw_input.value(*binding))
+            .sum();
+
+        // Inputs pressed while ignored, like the Escape closing the console, only count again
+        // once they were released
+        if ignored && value >= AXIS_PRESS_THRESHOLD {
+            held_while_ignored.insert(action);
+        } else if value < AXIS_PRESS_THRESHOLD {
+            held_while_ignored.remove(&action);
+        }
+
+        let value = if ignored || held_while_ignored.contains(&action) {
+            0.
+        } else {
+            value
+        };
+        let pressed = value >= AXIS_PRESS_THRESHOLD;
+
+        let data = action_state.actions.entry(action).or_default();
+        *data = ActionData {
+            pressed,
+            just_pressed: pressed && !data.pressed,
+            just_released: !pressed && data.pressed,
+            value,
+        };
+    }
+
+    *previous_gamepad_axes = axes;
+}
+
+/// Replaces the bindings with the ones from `game.config` whenever it is (re)loaded
+///
+/// Actions the player rebound at runtime keep their bindings.
+fn load_bindings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    let input = &config.input;
+
+    let configured = [
+        (Action::CameraPanLeft, &input.camera_pan_left),
+        (Action::CameraPanRight, &input.camera_pan_right),
+        (Action::CameraPanUp, &input.camera_pan_up),
+        (Action::CameraPanDown, &input.camera_pan_down),
+        (Action::CameraDrag, &input.camera_drag),
+        (Action::ZoomIn, &input.zoom_in),
+        (Action::ZoomOut, &input.zoom_out),
+        (Action::ToggleFreeCamera, &input.toggle_free_camera),
+        (Action::ToggleDebugOverlay, &input.toggle_debug_overlay),
+        (Action::MoveLeft, &input.move_left),
+        (Action::MoveRight, &input.move_right),
+        (Action::Jump, &input.jump),
+        (Action::Interact, &input.interact),
+        (Action::MenuConfirm, &input.menu_confirm),
+        (Action::MenuBack, &input.menu_back),
+    ];
+
+    for (action, configured_bindings) in configured {
+        let parsed = configured_bindings
+            .iter()
+            .filter_map(|binding| match Binding::parse(binding) {
+                Ok(binding) => Some(binding),
+                Err(err) => {
+                    error!("Invalid binding for {:?} in game.config: {}", action, err);
+                    None
+                }
+            })
+            .collect();
+
+        bindings.set(action, parsed);
+    }
+
+    info!("Loaded input bindings from game.config");
+}
+
+fn parse_mouse_button(input: &str) -> Option<MouseButton> {
+    Some(match input {
+        "Left" => MouseButton::Left,
+        "Right" => MouseButton::Right,
+        "Middle" => MouseButton::Middle,
+        other => MouseButton::Other(other.parse().ok()?),
+    })
+}
+
+fn parse_gamepad_button(input: &str) -> Option<GamepadButtonType> {
+    use bevy::input::gamepad::GamepadButtonType::*;
+
+    Some(match input {
+        "South" => South,
+        "East" => East,
+        "North" => North,
+        "West" => West,
+        "C" => C,
+        "Z" => Z,
+        "LeftTrigger" => LeftTrigger,
+        "LeftTrigger2" => LeftTrigger2,
+        "RightTrigger" => RightTrigger,
+        "RightTrigger2" => RightTrigger2,
+        "Select" => Select,
+        "Start" => Start,
+        "Mode" => Mode,
+        "LeftThumb" => LeftThumb,
+        "RightThumb" => RightThumb,
+        "DPadUp" => DPadUp,
+        "DPadDown" => DPadDown,
+        "DPadLeft" => DPadLeft,
+        "DPadRight" => DPadRight,
+        _ => return None,
+    })
+}
+
+fn parse_gamepad_axis(input: &str) -> Option<GamepadAxisType> {
+    use bevy::input::gamepad::GamepadAxisType::*;
+
+    Some(match input {
+        "LeftStickX" => LeftStickX,
+        "LeftStickY" => LeftStickY,
+        "LeftZ" => LeftZ,
+        "RightStickX" => RightStickX,
+        "RightStickY" => RightStickY,
+        "RightZ" => RightZ,
+        "DPadX" => DPadX,
+        "DPadY" => DPadY,
+        _ => return None,
+    })
+}
+
+fn parse_key_code(input: &str) -> Option<KeyCode> {
+    use bevy::input::keyboard::KeyCode::*;
+
+    Some(match input {
+        "Key1" => Key1,
+        "Key2" => Key2,
+        "Key3" => Key3,
+        "Key4" => Key4,
+        "Key5" => Key5,
+        "Key6" => Key6,
+        "Key7" => Key7,
+        "Key8" => Key8,
+        "Key9" => Key9,
+        "Key0" => Key0,
+        "A" => A,
+        "B" => B,
+        "C" => C,
+        "D" => D,
+        "E" => E,
+        "F" => F,
+        "G" => G,
+        "H" => H,
+        "I" => I,
+        "J" => J,
+        "K" => K,
+        "L" => L,
+        "M" => M,
+        "N" => N,
+        "O" => O,
+        "P" => P,
+        "Q" => Q,
+        "R" => R,
+        "S" => S,
+        "T" => T,
+        "U" => U,
+        "V" => V,
+        "W" => W,
+        "X" => X,
+        "Y" => Y,
+        "Z" => Z,
+        "Escape" => Escape,
+        "F1" => F1,
+        "F2" => F2,
+        "F3" => F3,
+        "F4" => F4,
+        "F5" => F5,
+        "F6" => F6,
+        "F7" => F7,
+        "F8" => F8,
+        "F9" => F9,
+        "F10" => F10,
+        "F11" => F11,
+        "F12" => F12,
+        "Insert" => Insert,
+        "Home" => Home,
+        "Delete" => Delete,
+        "End" => End,
+        "PageDown" => PageDown,
+        "PageUp" => PageUp,
+        "Left" => Left,
+        "Up" => Up,
+        "Right" => Right,
+        "Down" => Down,
+        "Back" => Back,
+        "Return" => Return,
+        "Space" => Space,
+        "Tab" => Tab,
+        "Grave" => Grave,
+        "Minus" => Minus,
+        "Equals" => Equals,
+        "Comma" => Comma,
+        "Period" => Period,
+        "Slash" => Slash,
+        "Semicolon" => Semicolon,
+        "LBracket" => LBracket,
+        "RBracket" => RBracket,
+        "LShift" => LShift,
+        "RShift" => RShift,
+        "LControl" => LControl,
+        "RControl" => RControl,
+        "LAlt" => LAlt,
+        "RAlt" => RAlt,
+        "Numpad0" => Numpad0,
+        "Numpad1" => Numpad1,
+        "Numpad2" => Numpad2,
+        "Numpad3" => Numpad3,
+        "Numpad4" => Numpad4,
+        "Numpad5" => Numpad5,
+        "Numpad6" => Numpad6,
+        "Numpad7" => Numpad7,
+        "Numpad8" => Numpad8,
+        "Numpad9" => Numpad9,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::{Action, AxisDirection, Binding, InputBindings};
+
+    #[test]
+    fn bindings_round_trip_through_their_text() {
+        let bindings = [
+            Binding::Key(KeyCode::Space),
+            Binding::Key(KeyCode::Numpad7),
+            Binding::Mouse(MouseButton::Right),
+            Binding::Mouse(MouseButton::Other(4)),
+            Binding::MouseWheel(AxisDirection::Positive),
+            Binding::MouseWheel(AxisDirection::Negative),
+            Binding::GamepadButton(GamepadButtonType::South),
+            Binding::GamepadAxis(GamepadAxisType::LeftStickX, AxisDirection::Negative),
+            Binding::GamepadAxis(GamepadAxisType::RightZ, AxisDirection::Positive),
+        ];
+
+        for binding in bindings {
+            assert_eq!(Binding::parse(&binding.to_string()), Ok(binding));
+        }
+    }
+
+    #[test]
+    fn shipped_bindings_parse() {
+        let config: toml::Value = toml::from_str(include_str!("../assets/game.config")).unwrap();
+
+        for (action, bindings) in config["input"].as_table().unwrap() {
+            for binding in bindings.as_array().unwrap() {
+                let binding = binding.as_str().unwrap();
+                let parsed = Binding::parse(binding)
+                    .unwrap_or_else(|err| panic!("{} of {}: {}", binding, action, err));
+                assert_eq!(parsed.to_string(), binding);
+            }
+        }
+    }
+
+    #[test]
+    fn rebinds_outlive_the_config() {
+        let mut bindings = InputBindings::default();
+        let w = Binding::Key(KeyCode::W);
+        let k = Binding::Key(KeyCode::K);
+
+        bindings.rebind(Action::Jump, vec![w]);
+        bindings.set(Action::Jump, vec![k]);
+        assert_eq!(bindings.get(Action::Jump), [w]);
+
+        bindings.reset(Action::Jump);
+        assert_eq!(bindings.get(Action::Jump), [k]);
+
+        bindings.bind(Action::Jump, w);
+        bindings.set(Action::Jump, vec![Binding::Key(KeyCode::Space)]);
+        assert_eq!(bindings.get(Action::Jump), [k, w]);
+    }
+
+    #[test]
+    fn invalid_bindings_are_rejected() {
+        assert!(Binding::parse("Space").is_err());
+        assert!(Binding::parse("Keyboard:Space").is_err());
+        assert!(Binding::parse("Key:Spacebar").is_err());
+        assert!(Binding::parse("Wheel:Left").is_err());
+        assert!(Binding::parse("GamepadAxis:LeftStickX").is_err());
+        assert!(Binding::parse("GamepadAxis:LeftStick+").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::Cursor;
+
+use bevy::{audio::AudioSource, prelude::*, utils::HashMap};
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
+
+/// Identifies a sound started with [`AudioBackend::play`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlaybackId(u64);
+
+/// Plays the sounds, the rest of the audio code only decides what to play and how loud
+///
+/// The [`AudioPlugin`](super::AudioPlugin) picks [`RodioAudioBackend`] unless a backend was
+/// inserted before it, as the [`HeadlessPlugins`](crate::cli::HeadlessPlugins) do.
+pub trait AudioBackend: Send + Sync + 'static {
+    /// Starts playing `source`, `volume` goes from `0.` (silent) to `1.`
+    fn play(&mut self, source: &AudioSource, looped: bool, volume: f32) -> Option<PlaybackId>;
+    fn set_volume(&mut self, playback: PlaybackId, volume: f32);
+    fn stop(&mut self, playback: PlaybackId);
+    /// Whether the sound is still playing, looped sounds play until they are stopped
+    fn is_playing(&self, playback: PlaybackId) -> bool;
+    /// The volume the sound is playing at, `None` once it stopped
+    fn volume(&self, playback: PlaybackId) -> Option<f32>;
+}
+
+/// Plays nothing, used without an audio device and in the tests
+///
+/// It still keeps track of the looped sounds, so the music behaves the same as with sound.
+#[derive(Debug, Default)]
+pub struct NullAudioBackend {
+    next_id: u64,
+    looped: HashMap<PlaybackId, f32>,
+}
+
+impl AudioBackend for NullAudioBackend {
+    fn play(&mut self, _source: &AudioSource, looped: bool, volume: f32) -> Option<PlaybackId> {
+        let playback = PlaybackId(self.next_id);
+        self.next_id += 1;
+
+        if looped {
+            self.looped.insert(playback, volume);
+        }
+
+        Some(playback)
+    }
+
+    fn set_volume(&mut self, playback: PlaybackId, volume: f32) {
+        if let Some(current) = self.looped.get_mut(&playback) {
+            *current = volume;
+        }
+    }
+
+    fn stop(&mut self, playback: PlaybackId) {
+        self.looped.remove(&playback);
+    }
+
+    fn is_playing(&self, playback: PlaybackId) -> bool {
+        self.looped.contains_key(&playback)
+    }
+
+    fn volume(&self, playback: PlaybackId) -> Option<f32> {
+        self.looped.get(&playback).copied()
+    }
+}
+
+/// Plays the sounds on the default audio device
+pub struct RodioAudioBackend {
+    stream_handle: OutputStreamHandle,
+    next_id: u64,
+    sinks: HashMap<PlaybackId, Sink>,
+}
+
+/// The stream of the [`RodioAudioBackend`], which has to stay on the main thread
+pub(super) struct RodioOutput(#[allow(dead_code)] OutputStream);
+
+impl RodioAudioBackend {
+    pub(super) fn new() -> Result<(Self, RodioOutput), rodio::StreamError> {
+        let (stream, stream_handle) = OutputStream::try_default()?;
+
+        Ok((
+            RodioAudioBackend {
+                stream_handle,
+                next_id: 0,
+                sinks: HashMap::default(),
+            },
+            RodioOutput(stream),
+        ))
+    }
+}
+
+impl AudioBackend for RodioAudioBackend {
+    fn play(&mut self, source: &AudioSource, looped: bool, volume: f32) -> Option<PlaybackId> {
+        // Finished sounds are cleaned up whenever a new one starts
+        self.sinks.retain(|_, sink| !sink.empty());
+
+        let sink = match Sink::try_new(&self.stream_handle) {
+            Ok(sink) => sink,
+            Err(err) => {
+                error!("Could not play a sound: {}", err);
+                return None;
+            }
+        };
+
+        let decoder = match rodio::Decoder::new(Cursor::new(source.clone())) {
+            Ok(decoder) => decoder,
+            Err(err) => {
+                error!("Could not decode a sound: {}", err);
+                return None;
+            }
+        };
+
+        sink.set_volume(volume);
+        if looped {
+            sink.append(decoder.repeat_infinite());
+        } else {
+            sink.append(decoder);
+        }
+
+        let playback = PlaybackId(self.next_id);
+        self.next_id += 1;
+        self.sinks.insert(playback, sink);
+
+        Some(playback)
+    }
+
+    fn set_volume(&mut self, playback: PlaybackId, volume: f32) {
+        if let Some(sink) = self.sinks.get(&playback) {
+            sink.set_volume(volume);
+        }
+    }
+
+    fn stop(&mut self, playback: PlaybackId) {
+        if let Some(sink) = self.sinks.remove(&playback) {
+            sink.stop();
+        }
+    }
+
+    fn is_playing(&self, playback: PlaybackId) -> bool {
+        self.sinks
+            .get(&playback)
+            .map_or(false, |sink| !sink.empty())
+    }
+
+    fn volume(&self, playback: PlaybackId) -> Option<f32> {
+        self.sinks
+            .get(&playback)
+            .filter(|sink| !sink.empty())
+            .map(Sink::volume)
+    }
+}
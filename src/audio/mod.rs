@@ -0,0 +1,324 @@
+//! Music and sound effects, configured in the `[audio]` section of `game.config`
+//!
+//! Systems play a sound effect by sending a [`SoundEvent`], the [`MusicPlayer`] picks the music
+//! by itself. Everything is played through the [`AudioBackend`] resource, which is a
+//! [`NullAudioBackend`] when running headless or without an audio device.
+
+use bevy::{
+    audio::{AudioLoader, AudioSource},
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    camera::Free2DCamera,
+    game_config::{changed_config, ConfigChanged},
+    stages::GameState,
+    GameAssets,
+};
+
+pub use self::{
+    backend::{AudioBackend, NullAudioBackend, PlaybackId, RodioAudioBackend},
+    music::MusicPlayer,
+};
+
+mod backend;
+mod music;
+
+#[derive(Debug, Default)]
+pub struct AudioPlugin;
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum AudioSystems {
+    ChooseMusic,
+}
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AudioSource>()
+            .init_asset_loader::<AudioLoader>();
+
+        if !app.world.contains_resource::<Box<dyn AudioBackend>>() {
+            match RodioAudioBackend::new() {
+                Ok((backend, output)) => {
+                    app.insert_resource::<Box<dyn AudioBackend>>(Box::new(backend));
+                    app.insert_non_send_resource(output);
+                }
+                Err(err) => {
+                    warn!(
+                        "Could not open an audio device, the game stays silent: {}",
+                        err
+                    );
+                    app.insert_resource::<Box<dyn AudioBackend>>(Box::new(
+                        NullAudioBackend::default(),
+                    ));
+                }
+            }
+        }
+
+        app.add_event::<SoundEvent>();
+        app.init_resource::<AudioBuses>();
+        app.init_resource::<AudioSettings>();
+        app.init_resource::<MusicPlayer>();
+        app.add_system(load_audio_settings_from_config);
+        app.add_system(music::choose_music.label(AudioSystems::ChooseMusic));
+        app.add_system(music::update_music.after(AudioSystems::ChooseMusic));
+        app.add_system_to_stage(CoreStage::PostUpdate, play_sound_effects);
+    }
+}
+
+/// The sound effects of the game, the files are set in `[audio.sfx]` of `game.config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    ButtonHover,
+    ButtonClick,
+    Jump,
+    Hurt,
+    Death,
+    Stomp,
+    Checkpoint,
+    SeedDelivered,
+    SeedBroken,
+}
+
+/// Plays a [`SoundEffect`] once
+#[derive(Debug, Clone, Copy)]
+pub struct SoundEvent {
+    pub effect: SoundEffect,
+    /// Where in the world the sound comes from, `None` for sounds like the UI that are always
+    /// heard at full volume
+    pub emitter: Option<SoundEmitter>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SoundEmitter {
+    /// The entity `position` is relative to, usually the level
+    pub parent: Entity,
+    pub position: Vec2,
+}
+
+impl SoundEvent {
+    pub fn ui(effect: SoundEffect) -> Self {
+        SoundEvent {
+            effect,
+            emitter: None,
+        }
+    }
+
+    /// A sound at `position` in the local coordinates of `level`, it gets quieter the further
+    /// it is from the camera
+    pub fn in_level(effect: SoundEffect, level: Entity, position: Vec2) -> Self {
+        SoundEvent {
+            effect,
+            emitter: Some(SoundEmitter {
+                parent: level,
+                position,
+            }),
+        }
+    }
+}
+
+/// The volume of each bus, from `0.` to `1.`
+///
+/// Music and sound effects are both scaled by the master volume.
+#[derive(Debug, Clone)]
+pub struct AudioBuses {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioBuses {
+    fn default() -> Self {
+        AudioBuses {
+            master: 1.,
+            music: 0.7,
+            sfx: 1.,
+        }
+    }
+}
+
+impl AudioBuses {
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+/// Read from the `[audio]` section of `game.config`
+#[derive(Debug)]
+pub struct AudioSettings {
+    /// Seconds the music takes to fade over into the next track
+    pub crossfade_time: f32,
+    /// Sound effects further away from the camera than this (in pixels) can not be heard
+    pub hearing_distance: f32,
+    music: HashMap<GameState, String>,
+    effects: HashMap<SoundEffect, Handle<AudioSource>>,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            crossfade_time: 1.,
+            hearing_distance: 320.,
+            music: HashMap::default(),
+            effects: HashMap::default(),
+        }
+    }
+}
+
+impl AudioSettings {
+    /// The music of `state`, empty if it has none
+    pub fn state_music(&self, state: GameState) -> &str {
+        self.music.get(&state).map_or("", String::as_str)
+    }
+}
+
+fn load_audio_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    asset_server: Res<AssetServer>,
+    mut settings: ResMut<AudioSettings>,
+    mut buses: ResMut<AudioBuses>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    let audio = &config.audio;
+
+    *buses = AudioBuses {
+        master: audio.master_volume as f32,
+        music: audio.music_volume as f32,
+        sfx: audio.sfx_volume as f32,
+    };
+
+    let music = [
+        (GameState::Loading, &audio.music.loading),
+        (GameState::MainMenu, &audio.music.main_menu),
+        (GameState::LevelSelect, &audio.music.level_select),
+        (GameState::Running, &audio.music.running),
+        (GameState::LevelComplete, &audio.music.level_complete),
+        (GameState::GameOver, &audio.music.game_over),
+    ];
+
+    let effects = [
+        (SoundEffect::ButtonHover, &audio.sfx.button_hover),
+        (SoundEffect::ButtonClick, &audio.sfx.button_click),
+        (SoundEffect::Jump, &audio.sfx.jump),
+        (SoundEffect::Hurt, &audio.sfx.hurt),
+        (SoundEffect::Death, &audio.sfx.death),
+        (SoundEffect::Stomp, &audio.sfx.stomp),
+        (SoundEffect::Checkpoint, &audio.sfx.checkpoint),
+        (SoundEffect::SeedDelivered, &audio.sfx.seed_delivered),
+        (SoundEffect::SeedBroken, &audio.sfx.seed_broken),
+    ];
+
+    *settings = AudioSettings {
+        crossfade_time: audio.crossfade_time as f32,
+        hearing_distance: audio.hearing_distance as f32,
+        music: music
+            .iter()
+            .map(|(state, path)| (*state, (*path).clone()))
+            .collect(),
+        effects: effects
+            .iter()
+            .filter(|(_, path)| !path.is_empty())
+            .map(|(effect, path)| (*effect, asset_server.load(path.as_str())))
+            .collect(),
+    };
+}
+
+/// How loud a sound `distance` pixels away from the camera is, fading out linearly
+fn attenuation(distance: f32, hearing_distance: f32) -> f32 {
+    (1. - distance / hearing_distance).clamp(0., 1.)
+}
+
+fn play_sound_effects(
+    mut sound_events: EventReader<SoundEvent>,
+    settings: Res<AudioSettings>,
+    buses: Res<AudioBuses>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut backend: ResMut<Box<dyn AudioBackend>>,
+    transform_query: Query<&GlobalTransform>,
+    camera_query: Query<&GlobalTransform, With<Free2DCamera>>,
+) {
+    for event in sound_events.iter() {
+        let source = if let Some(source) = settings
+            .effects
+            .get(&event.effect)
+            .and_then(|handle| audio_sources.get(handle))
+        {
+            source
+        } else {
+            continue;
+        };
+
+        let mut volume = buses.sfx_volume();
+
+        if let Some(emitter) = event.emitter {
+            let parent = if let Ok(parent) = transform_query.get(emitter.parent) {
+                parent
+            } else {
+                continue;
+            };
+            let position = parent.mul_vec3(emitter.position.extend(0.)).truncate();
+
+            // With several cameras, the closest one hears it
+            let distance = camera_query
+                .iter()
+                .map(|camera| camera.translation.truncate().distance(position))
+                .reduce(f32::min);
+
+            if let Some(distance) = distance {
+                volume *= attenuation(distance, settings.hearing_distance);
+            }
+        }
+
+        if volume > 0. {
+            backend.play(source, false, volume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::audio::AudioSource;
+
+    use super::{attenuation, AudioBackend, NullAudioBackend};
+
+    #[test]
+    fn sounds_fade_out_with_distance() {
+        assert_eq!(attenuation(0., 320.), 1.);
+        assert_eq!(attenuation(160., 320.), 0.5);
+        assert_eq!(attenuation(400., 320.), 0.);
+    }
+
+    #[test]
+    fn null_backend_keeps_looped_sounds_until_stopped() {
+        let source = AudioSource {
+            bytes: Vec::new().into(),
+        };
+        let mut backend = NullAudioBackend::default();
+
+        let once = backend.play(&source, false, 1.).unwrap();
+        let looped = backend.play(&source, true, 1.).unwrap();
+        assert!(!backend.is_playing(once));
+        assert!(backend.is_playing(looped));
+
+        backend.set_volume(looped, 0.25);
+        assert_eq!(backend.volume(looped), Some(0.25));
+
+        backend.stop(looped);
+        assert!(!backend.is_playing(looped));
+        assert_eq!(backend.volume(looped), None);
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::{audio::AudioSource, prelude::*};
+
+use super::{AudioBackend, AudioBuses, AudioSettings, PlaybackId};
+use crate::{stages::GameState, world::CurrentLevel, GameAssets};
+
+/// Plays the music of the current [`GameState`] and crossfades when it changes
+///
+/// While running, the `Music` field of the current LDtk level takes precedence over the music
+/// of the state.
+#[derive(Debug, Default)]
+pub struct MusicPlayer {
+    current: Option<Track>,
+    fading: Vec<Track>,
+}
+
+#[derive(Debug)]
+struct Track {
+    /// Relative to the `assets` folder
+    path: String,
+    handle: Handle<AudioSource>,
+    playback: Option<PlaybackId>,
+    /// The backend could not play the track, it is not tried again
+    failed: bool,
+    /// How far the track is faded in, from `0.` to `1.`
+    fade: f32,
+}
+
+impl MusicPlayer {
+    /// The path of the track that is playing or fading in, `None` while it is quiet
+    pub fn track(&self) -> Option<&str> {
+        self.current.as_ref().map(|track| track.path.as_str())
+    }
+
+    /// Whether earlier tracks are still fading out
+    pub fn is_crossfading(&self) -> bool {
+        !self.fading.is_empty()
+    }
+
+    /// The sound of the current track, once it started playing
+    pub fn playback(&self) -> Option<PlaybackId> {
+        self.current.as_ref().and_then(|track| track.playback)
+    }
+
+    /// The sounds of the tracks that are fading out
+    pub fn fading_playbacks(&self) -> impl Iterator<Item = PlaybackId> + '_ {
+        self.fading.iter().filter_map(|track| track.playback)
+    }
+
+    fn switch_to(&mut self, path: &str, asset_server: &AssetServer) {
+        if let Some(current) = self.current.take() {
+            self.fading.push(current);
+        }
+
+        if path.is_empty() {
+            return;
+        }
+
+        // Going back to a track that is still fading out picks it up where it is
+        if let Some(index) = self.fading.iter().position(|track| track.path == path) {
+            self.current = Some(self.fading.remove(index));
+            return;
+        }
+
+        self.current = Some(Track {
+            path: path.to_owned(),
+            handle: asset_server.load(path),
+            playback: None,
+            failed: false,
+            fade: 0.,
+        });
+    }
+}
+
+pub(super) fn choose_music(
+    state: Res<State<GameState>>,
+    settings: Res<AudioSettings>,
+    current_level: Option<Res<CurrentLevel>>,
+    game_assets: Option<Res<GameAssets>>,
+    ldtk_assets: Res<Assets<crate::levels::Project>>,
+    asset_server: Res<AssetServer>,
+    mut music_player: ResMut<MusicPlayer>,
+) {
+    let level_music = match (state.current(), current_level, game_assets) {
+        (GameState::Running, Some(current_level), Some(game_assets)) => ldtk_assets
+            .get(&game_assets.levels)
+            .and_then(|project| {
+                project
+                    .levels
+                    .iter()
+                    .find(|level| level.identifier == current_level.0)
+            })
+            .map(|level| level.fields.music.clone())
+            .filter(|music| !music.is_empty()),
+        _ => None,
+    };
+
+    let path = level_music.unwrap_or_else(|| settings.state_music(*state.current()).to_owned());
+
+    if music_player.track().unwrap_or_default() != path {
+        music_player.switch_to(&path, &asset_server);
+    }
+}
+
+pub(super) fn update_music(
+    time: Res<Time>,
+    settings: Res<AudioSettings>,
+    buses: Res<AudioBuses>,
+    audio_sources: Res<Assets<AudioSource>>,
+    mut backend: ResMut<Box<dyn AudioBackend>>,
+    mut music_player: ResMut<MusicPlayer>,
+) {
+    let step = if settings.crossfade_time > 0. {
+        time.delta_seconds() / settings.crossfade_time
+    } else {
+        1.
+    };
+    let volume = buses.music_volume();
+    let music_player = &mut *music_player;
+
+    if let Some(track) = music_player.current.as_mut() {
+        match track.playback {
+            Some(playback) => {
+                track.fade = (track.fade + step).min(1.);
+                backend.set_volume(playback, track.fade * volume);
+            }
+            // The track starts fading in once it is loaded
+            None if !track.failed => {
+                if let Some(source) = audio_sources.get(&track.handle) {
+                    track.playback = backend.play(source, true, track.fade * volume);
+
+                    if track.playback.is_none() {
+                        warn!("Could not play the music {}", track.path);
+                        track.failed = true;
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    for track in music_player.fading.iter_mut() {
+        track.fade -= step;
+
+        if let Some(playback) = track.playback {
+            if track.fade > 0. {
+                backend.set_volume(playback, track.fade * volume);
+            } else {
+                backend.stop(playback);
+            }
+        }
+    }
+
+    music_player.fading.retain(|track| track.fade > 0.);
+}
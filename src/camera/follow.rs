@@ -0,0 +1,181 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use crate::utils::smooth_damp;
+
+use super::{clamp_to_limits, CameraMode, Free2DCamera};
+
+/// Below this speed (in pixels per second) the target is considered to be standing still
+const LOOK_AHEAD_THRESHOLD: f32 = 10.;
+
+/// Marks the entity that following cameras without an explicit target should track
+#[derive(Debug, Default)]
+pub struct CameraTarget;
+
+/// Settings and state of the [`CameraMode::Follow`] mode of a [`Free2DCamera`]
+#[derive(Debug, Clone)]
+pub struct CameraFollow {
+    /// The entity to follow, if none is given the first [`CameraTarget`] is used
+    pub target: Option<Entity>,
+    /// Half size (in pixels) of the area the target can move in without moving the camera
+    pub dead_zone: Vec2,
+    /// How far (in pixels) the camera looks ahead in the direction the target is moving
+    pub look_ahead: Vec2,
+    /// Roughly the time (in seconds) the camera takes to catch up with the target
+    pub smooth_time: f32,
+    focus: Option<Vec2>,
+    position: Option<Vec2>,
+    velocity: Vec2,
+    last_target_position: Option<Vec2>,
+    look_ahead_direction: Vec2,
+    look_ahead_offset: Vec2,
+    look_ahead_velocity: Vec2,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        CameraFollow {
+            target: None,
+            dead_zone: Vec2::new(24., 32.),
+            look_ahead: Vec2::new(48., 0.),
+            smooth_time: 0.25,
+            focus: None,
+            position: None,
+            velocity: Vec2::ZERO,
+            last_target_position: None,
+            look_ahead_direction: Vec2::ZERO,
+            look_ahead_offset: Vec2::ZERO,
+            look_ahead_velocity: Vec2::ZERO,
+        }
+    }
+}
+
+impl CameraFollow {
+    pub fn with_target(mut self, target: Entity) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_dead_zone(mut self, dead_zone: Vec2) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    pub fn with_look_ahead(mut self, look_ahead: Vec2) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    pub fn with_smooth_time(mut self, smooth_time: f32) -> Self {
+        self.smooth_time = smooth_time;
+        self
+    }
+
+    /// Forget the tracked state, the next update starts again from the camera position
+    pub fn reset(&mut self) {
+        self.focus = None;
+        self.position = None;
+        self.velocity = Vec2::ZERO;
+        self.last_target_position = None;
+        self.look_ahead_direction = Vec2::ZERO;
+        self.look_ahead_offset = Vec2::ZERO;
+        self.look_ahead_velocity = Vec2::ZERO;
+    }
+
+    /// Returns the point the camera should be centered on this frame
+    fn step(&mut self, target_position: Vec2, camera_position: Vec2, delta: f32) -> Vec2 {
+        // The focus only gets dragged along once the target leaves the dead zone
+        let focus = self.focus.get_or_insert(target_position);
+        let offset = target_position - *focus;
+        *focus += offset - offset.max(-self.dead_zone).min(self.dead_zone);
+        let focus = *focus;
+
+        let movement = self
+            .last_target_position
+            .map_or(Vec2::ZERO, |last| (target_position - last) / delta);
+        self.last_target_position = Some(target_position);
+
+        // Keep looking in the last direction the target moved in until it turns around
+        let moving = movement.abs().cmpgt(Vec2::splat(LOOK_AHEAD_THRESHOLD));
+        self.look_ahead_direction =
+            Vec2::select(moving, movement.signum(), self.look_ahead_direction);
+
+        self.look_ahead_offset = smooth_damp(
+            self.look_ahead_offset,
+            self.look_ahead_direction * self.look_ahead,
+            &mut self.look_ahead_velocity,
+            self.smooth_time * 2.,
+            delta,
+        );
+
+        let position = self.position.unwrap_or(camera_position);
+        let position = smooth_damp(
+            position,
+            focus + self.look_ahead_offset,
+            &mut self.velocity,
+            self.smooth_time,
+            delta,
+        );
+        self.position = Some(position);
+
+        position
+    }
+}
+
+pub(super) fn follow_camera(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    target_query: Query<&GlobalTransform>,
+    default_target_query: Query<Entity, With<CameraTarget>>,
+    mut camera_query: Query<(&mut Transform, &mut Free2DCamera)>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0. {
+        return;
+    }
+
+    let primary_window = if let Some(win) = windows.get_primary() {
+        win
+    } else {
+        error!("No primary window!");
+        return;
+    };
+
+    let screen_size = Vec2::new(primary_window.width(), primary_window.height());
+
+    for (mut transform, mut free_2d_camera) in camera_query.iter_mut() {
+        if free_2d_camera.mode != CameraMode::Follow {
+            if free_2d_camera.follow.position.is_some() {
+                free_2d_camera.follow.reset();
+            }
+            continue;
+        }
+
+        let target = free_2d_camera
+            .follow
+            .target
+            .or_else(|| default_target_query.iter().next());
+
+        let target_position =
+            if let Some(target_transform) = target.and_then(|t| target_query.get(t).ok()) {
+                target_transform.translation.xy()
+            } else {
+                continue;
+            };
+
+        let half_view_size = free_2d_camera.view_size(screen_size) / 2.;
+
+        let Free2DCamera { follow, limits, .. } = &mut *free_2d_camera;
+
+        let mut position = follow.step(target_position, transform.translation.xy(), delta);
+
+        // Keep the unsnapped position inside the limits as well, so that it does not drift off
+        // while the target is close to an edge
+        if let Some(limit) = limits.as_ref() {
+            position = clamp_to_limits(position, half_view_size, limit);
+            follow.position = Some(position);
+        }
+
+        // `align_camera` snaps the transform afterwards, the precise position stays in `follow`
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
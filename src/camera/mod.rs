@@ -0,0 +1,606 @@
+use std::ops::RangeInclusive;
+
+use bevy::{
+    ecs::schedule::ShouldRun, math::Vec3Swizzles, prelude::*,
+    render::camera::OrthographicProjection, window::WindowResized,
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    console::{AddConsoleCommand, ConsoleCommand},
+    game_config::{changed_config, ConfigChanged},
+    utils::smooth_damp,
+    GameAssets,
+};
+
+pub use self::{
+    follow::{CameraFollow, CameraTarget},
+    parallax::ParallaxLayer,
+    projection::{tile_to_world, world_to_tile, CameraProjection, CameraProjections},
+    shake::{CameraShake, CameraShakeEvent, CameraShakeSettings},
+};
+
+mod follow;
+mod parallax;
+mod pixel_perfect;
+mod projection;
+mod shake;
+
+#[derive(Debug, Default)]
+pub struct CameraPlugin;
+
+#[derive(StageLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+struct CameraStage;
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum CameraSystems {
+    Scale,
+    Limits,
+    Follow,
+    Shake,
+    Clamp,
+    Align,
+    Parallax,
+}
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_stage_before(
+            CoreStage::PostUpdate,
+            CameraStage,
+            SystemStage::single_threaded()
+                .with_system(
+                    pixel_perfect::apply_camera_scale
+                        .with_run_criteria(has_primary_window)
+                        .label(CameraSystems::Scale),
+                )
+                .with_system(
+                    animate_limits
+                        .label(CameraSystems::Limits)
+                        .after(CameraSystems::Scale),
+                )
+                .with_system(
+                    follow::follow_camera
+                        .with_run_criteria(has_primary_window)
+                        .label(CameraSystems::Follow)
+                        .after(CameraSystems::Limits),
+                )
+                .with_system(
+                    shake::apply_camera_shake
+                        .label(CameraSystems::Shake)
+                        .after(CameraSystems::Follow),
+                )
+                .with_system(
+                    clamp_camera
+                        .with_run_criteria(has_primary_window)
+                        .label(CameraSystems::Clamp)
+                        .after(CameraSystems::Shake),
+                )
+                .with_system(
+                    align_camera
+                        .label(CameraSystems::Align)
+                        .after(CameraSystems::Clamp),
+                )
+                .with_system(
+                    parallax::update_parallax_layers
+                        .label(CameraSystems::Parallax)
+                        .after(CameraSystems::Align),
+                ),
+        );
+
+        app.add_event::<CameraShakeEvent>();
+        app.init_resource::<CameraShakeSettings>();
+        app.add_system_to_stage(CoreStage::PreUpdate, shake::restore_shaken_cameras);
+        app.add_system(shake::receive_shake_events);
+        app.add_system(shake::load_shake_settings_from_config);
+
+        app.add_system(update_camera.with_run_criteria(has_primary_window));
+        app.add_system(load_camera_settings_from_config);
+        app.add_system(toggle_camera_mode);
+        app.add_console_command(
+            ConsoleCommand::new("camera", "camera free|follow", set_camera_mode).with_completion(
+                |args, _| match args {
+                    [] => vec![String::from("free"), String::from("follow")],
+                    _ => Vec::new(),
+                },
+            ),
+        );
+        app.add_system(pixel_perfect::update_letterbox.with_run_criteria(has_primary_window));
+    }
+}
+
+/// The cameras are sized after the primary window, without one (e.g. headless) they are left alone
+fn has_primary_window(windows: Res<Windows>) -> ShouldRun {
+    if windows.get_primary().is_some() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+#[derive(Debug)]
+struct DragPosition {
+    cursor_position: Vec2,
+    camera_position: Vec2,
+}
+
+#[derive(Debug)]
+struct LimitsTransition {
+    from: Rect<f32>,
+    to: Rect<f32>,
+    elapsed: f32,
+}
+
+/// How a [`Free2DCamera`] decides where to go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Moved by the arrow keys and by dragging with the right mouse button
+    Free,
+    /// Follows a target entity, see [`CameraFollow`]
+    Follow,
+}
+
+pub struct Free2DCamera {
+    pub current_scale: f32,
+    /// The scale the camera is zooming towards
+    pub target_scale: f32,
+    pub scale_levels: RangeInclusive<f32>,
+    /// Speed (in world pixels per second) of panning with the keyboard
+    pub pan_speed: f32,
+    /// How much the scale changes per line scrolled
+    pub zoom_speed: f32,
+    /// Roughly the time (in seconds) zooming takes, `0.` zooms immediately
+    ///
+    /// Pixel perfect cameras ignore it, a scale between two whole steps would make the pixels
+    /// shimmer.
+    pub zoom_smooth_time: f32,
+    pub limits: Option<Rect<f32>>,
+    /// Time (in seconds) it takes to move from old to new limits, see [`Free2DCamera::set_limits`]
+    pub limits_transition_time: f32,
+    /// Logical resolution that gets rendered at the largest integer scale fitting the window
+    pub virtual_resolution: Option<Vec2>,
+    pub mode: CameraMode,
+    pub follow: CameraFollow,
+    start_drag: Option<DragPosition>,
+    limits_transition: Option<LimitsTransition>,
+    zoom_velocity: f32,
+    zoom_remainder: f32,
+}
+
+impl Free2DCamera {
+    pub fn new(current_scale: f32) -> Free2DCamera {
+        Self {
+            current_scale,
+            target_scale: current_scale,
+            scale_levels: current_scale..=current_scale,
+            pan_speed: 600.,
+            zoom_speed: 1.,
+            zoom_smooth_time: 0.,
+            limits: None,
+            limits_transition_time: 0.,
+            virtual_resolution: None,
+            mode: CameraMode::Free,
+            follow: CameraFollow::default(),
+            start_drag: None,
+            limits_transition: None,
+            zoom_velocity: 0.,
+            zoom_remainder: 0.,
+        }
+    }
+
+    pub fn with_scale_range(mut self, scale_levels: RangeInclusive<f32>) -> Self {
+        self.scale_levels = scale_levels;
+        self
+    }
+
+    pub fn with_pan_speed(mut self, pan_speed: f32) -> Self {
+        self.pan_speed = pan_speed;
+        self
+    }
+
+    pub fn with_zoom_speed(mut self, zoom_speed: f32) -> Self {
+        self.zoom_speed = zoom_speed;
+        self
+    }
+
+    /// Eases the zoom towards the new scale instead of jumping there, unless the camera is pixel
+    /// perfect
+    pub fn with_zoom_smooth_time(mut self, zoom_smooth_time: f32) -> Self {
+        self.zoom_smooth_time = zoom_smooth_time;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Option<Rect<f32>>) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_limits_transition_time(mut self, limits_transition_time: f32) -> Self {
+        self.limits_transition_time = limits_transition_time;
+        self
+    }
+
+    /// Changes the limits of the camera
+    ///
+    /// If the camera already has limits and a transition time is set, the limits are animated
+    /// towards the new ones instead of jumping there.
+    pub fn set_limits(&mut self, limits: Option<Rect<f32>>) {
+        match (self.limits, limits) {
+            (Some(from), Some(to)) if self.limits_transition_time > 0. && from != to => {
+                self.limits_transition = Some(LimitsTransition {
+                    from,
+                    to,
+                    elapsed: 0.,
+                });
+            }
+            _ => {
+                self.limits_transition = None;
+                self.limits = limits;
+            }
+        }
+    }
+
+    /// Renders the given logical resolution in pixel perfect mode
+    ///
+    /// The view gets scaled by the largest integer factor that fits the window, the remaining
+    /// space is letterboxed. Zooming then only happens in whole steps.
+    pub fn with_virtual_resolution(mut self, virtual_resolution: Vec2) -> Self {
+        self.virtual_resolution = Some(virtual_resolution);
+        self
+    }
+
+    /// How many screen pixels one world pixel covers at a scale of `1.`
+    pub fn pixel_scale(&self, window_size: Vec2) -> f32 {
+        match self.virtual_resolution {
+            Some(virtual_resolution) => (window_size / virtual_resolution)
+                .min_element()
+                .floor()
+                .max(1.),
+            None => 1.,
+        }
+    }
+
+    /// The total scale from world pixels to screen pixels
+    pub fn total_scale(&self, window_size: Vec2) -> f32 {
+        self.current_scale * self.pixel_scale(window_size)
+    }
+
+    /// The size (in screen pixels) of the area the camera renders into
+    pub fn viewport_size(&self, window_size: Vec2) -> Vec2 {
+        match self.virtual_resolution {
+            Some(virtual_resolution) => {
+                (virtual_resolution * self.pixel_scale(window_size)).min(window_size)
+            }
+            None => window_size,
+        }
+    }
+
+    /// The size (in world pixels) of the area visible through the camera
+    pub fn view_size(&self, window_size: Vec2) -> Vec2 {
+        self.viewport_size(window_size) / self.total_scale(window_size)
+    }
+
+    /// Moves the target scale by `zoom_scroll` lines, pixel perfect cameras only zoom in whole
+    /// steps and keep the remainder for the next scroll
+    fn zoom_by(&mut self, zoom_scroll: f32) {
+        let zoom = zoom_scroll * self.zoom_speed;
+
+        let new_scale = if self.virtual_resolution.is_some() {
+            self.zoom_remainder += zoom;
+            let steps = self.zoom_remainder.trunc();
+            self.zoom_remainder -= steps;
+
+            (self.target_scale + steps).round()
+        } else {
+            self.target_scale + zoom
+        };
+
+        self.target_scale = new_scale
+            .min(*self.scale_levels.end())
+            .max(*self.scale_levels.start());
+    }
+
+    /// The scale for this frame when easing towards the target scale
+    fn eased_scale(&mut self, delta: f32) -> f32 {
+        // Pixel perfect cameras only ever use whole steps, so they jump there
+        if self.virtual_resolution.is_some()
+            || self.zoom_smooth_time <= 0.
+            || (self.target_scale - self.current_scale).abs() < 0.001
+        {
+            self.zoom_velocity = 0.;
+            return self.target_scale;
+        }
+
+        let mut velocity = Vec2::new(self.zoom_velocity, 0.);
+        let scale = smooth_damp(
+            Vec2::new(self.current_scale, 0.),
+            Vec2::new(self.target_scale, 0.),
+            &mut velocity,
+            self.zoom_smooth_time,
+            delta,
+        );
+        self.zoom_velocity = velocity.x;
+
+        scale.x
+    }
+
+    pub fn with_mode(mut self, mode: CameraMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_follow(mut self, follow: CameraFollow) -> Self {
+        self.follow = follow;
+        self
+    }
+}
+
+fn animate_limits(time: Res<Time>, mut camera_query: Query<&mut Free2DCamera>) {
+    for mut free_2d_camera in camera_query.iter_mut() {
+        if free_2d_camera.limits_transition.is_none() {
+            continue;
+        }
+
+        let free_2d_camera = &mut *free_2d_camera;
+        let transition = free_2d_camera.limits_transition.as_mut().unwrap();
+
+        transition.elapsed += time.delta_seconds();
+
+        let progress = (transition.elapsed / free_2d_camera.limits_transition_time).min(1.);
+        let progress = progress * progress * (3. - 2. * progress);
+        let lerp = |from: f32, to: f32| from + (to - from) * progress;
+
+        free_2d_camera.limits = Some(Rect {
+            left: lerp(transition.from.left, transition.to.left),
+            right: lerp(transition.from.right, transition.to.right),
+            top: lerp(transition.from.top, transition.to.top),
+            bottom: lerp(transition.from.bottom, transition.to.bottom),
+        });
+
+        if progress >= 1. {
+            free_2d_camera.limits_transition = None;
+        }
+    }
+}
+
+/// Applies the `[camera]` section of `game.config` to all cameras
+fn load_camera_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut camera_query: Query<&mut Free2DCamera>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    let settings = &config.camera;
+    for mut free_2d_camera in camera_query.iter_mut() {
+        free_2d_camera.scale_levels = (settings.scale_min as f32)..=(settings.scale_max as f32);
+        free_2d_camera.target_scale = free_2d_camera
+            .target_scale
+            .min(settings.scale_max as f32)
+            .max(settings.scale_min as f32);
+        free_2d_camera.pan_speed = settings.pan_speed as f32;
+        free_2d_camera.zoom_smooth_time = settings.zoom_smooth_time as f32;
+    }
+}
+
+/// Switches between following and free movement, useful to look around while debugging
+fn toggle_camera_mode(actions: Res<ActionState>, mut camera_query: Query<&mut Free2DCamera>) {
+    if !actions.just_pressed(Action::ToggleFreeCamera) {
+        return;
+    }
+
+    for mut free_2d_camera in camera_query.iter_mut() {
+        free_2d_camera.mode = match free_2d_camera.mode {
+            CameraMode::Free => CameraMode::Follow,
+            CameraMode::Follow => CameraMode::Free,
+        };
+        info!("Switched camera to {:?} mode", free_2d_camera.mode);
+    }
+}
+
+fn set_camera_mode(args: &[&str], world: &mut World) -> Result<String, String> {
+    let mode = match args {
+        ["free"] => CameraMode::Free,
+        ["follow"] => CameraMode::Follow,
+        _ => return Err(String::from("expected free or follow")),
+    };
+
+    for mut free_2d_camera in world.query::<&mut Free2DCamera>().iter_mut(world) {
+        free_2d_camera.mode = mode;
+    }
+
+    Ok(format!("Switched camera to {:?} mode", mode))
+}
+
+fn update_camera(
+    time: Res<Time>,
+    mut cursor_movement_events: EventReader<CursorMoved>,
+    actions: Res<ActionState>,
+    windows: Res<Windows>,
+    mut camera_query: Query<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut Free2DCamera,
+    )>,
+    mut last_cursor_position: Local<Vec2>,
+) {
+    // Without cameras, we don't care
+    if camera_query.is_empty() {
+        return;
+    }
+
+    let zoom_scroll = actions.value(Action::ZoomIn) - actions.value(Action::ZoomOut);
+
+    let cursor_position = cursor_movement_events.iter().map(|mov| mov.position).last();
+
+    if let Some(cursor_pos) = cursor_position {
+        *last_cursor_position = cursor_pos;
+    }
+
+    let pan_direction = Vec2::new(
+        actions.axis(Action::CameraPanLeft, Action::CameraPanRight),
+        actions.axis(Action::CameraPanDown, Action::CameraPanUp),
+    )
+    .normalize_or_zero();
+
+    let delta = time.delta_seconds();
+
+    let primary_window = if let Some(win) = windows.get_primary() {
+        win
+    } else {
+        error!("No primary window!");
+        return;
+    };
+
+    let screen_size = Vec2::new(primary_window.width(), primary_window.height());
+
+    for (mut transform, mut ortographic_project, mut free_2d_camera) in camera_query.iter_mut() {
+        if zoom_scroll != 0. {
+            free_2d_camera.zoom_by(zoom_scroll);
+        }
+
+        if free_2d_camera.current_scale != free_2d_camera.target_scale {
+            let cursor_position = primary_window
+                .cursor_position()
+                .unwrap_or(screen_size / 2.0);
+
+            let world_under_cursor =
+                CameraProjection::new(&free_2d_camera, transform.translation.xy(), screen_size)
+                    .window_to_world(cursor_position);
+
+            free_2d_camera.current_scale = free_2d_camera.eased_scale(delta);
+
+            let projection =
+                CameraProjection::new(&free_2d_camera, transform.translation.xy(), screen_size);
+
+            // Keep the world position under the cursor in place
+            let pos_change = world_under_cursor - projection.window_to_world(cursor_position);
+
+            transform.translation += pos_change.extend(0.);
+            pixel_perfect::set_scale(&mut transform, &mut ortographic_project, projection.scale);
+        }
+
+        if free_2d_camera.mode != CameraMode::Free {
+            free_2d_camera.start_drag = None;
+            continue;
+        }
+
+        if actions.pressed(Action::CameraDrag) {
+            if free_2d_camera.start_drag.is_some() {
+                let start_drag = free_2d_camera.start_drag.as_ref().unwrap();
+                transform.translation = start_drag.camera_position.extend(transform.translation.z)
+                    + (start_drag.cursor_position - *last_cursor_position).extend(0.)
+                        / free_2d_camera.total_scale(screen_size);
+            } else {
+                free_2d_camera.start_drag = Some(DragPosition {
+                    camera_position: transform.translation.xy(),
+                    cursor_position: *last_cursor_position,
+                });
+            }
+        } else {
+            free_2d_camera.start_drag = None;
+        }
+
+        if pan_direction != Vec2::ZERO {
+            transform.translation += (pan_direction * free_2d_camera.pan_speed * delta).extend(0.);
+        }
+    }
+}
+
+fn clamp_camera(
+    windows: Res<Windows>,
+    mut window_resize_events: EventReader<WindowResized>,
+    mut camera_query: Query<(&mut Transform, &Free2DCamera, ChangeTrackers<Free2DCamera>)>,
+) {
+    let primary_window = if let Some(win) = windows.get_primary() {
+        win
+    } else {
+        error!("No primary window!");
+        return;
+    };
+
+    let resize_event = window_resize_events.iter().last().is_some();
+
+    let screen_size = Vec2::new(primary_window.width(), primary_window.height());
+
+    for (mut transform, free_2d_camera, change_free_2d_camera) in camera_query.iter_mut() {
+        // If any of the inputs changed, we re-calculate the
+        if !(transform.is_changed() || change_free_2d_camera.is_changed() || resize_event) {
+            continue;
+        }
+
+        if let Some(limit) = free_2d_camera.limits.as_ref() {
+            let half_scaled_screen_size = free_2d_camera.view_size(screen_size) / 2.;
+
+            let center = transform.translation.xy();
+            let new_center = clamp_to_limits(center, half_scaled_screen_size, limit);
+
+            if new_center != center {
+                transform.translation = new_center.extend(transform.translation.z);
+            }
+        }
+    }
+}
+
+/// Moves `center` so that a view of `half_size` around it stays inside of `limits`
+///
+/// If the view is larger than the limits on an axis, it gets centered between them instead.
+fn clamp_to_limits(center: Vec2, half_size: Vec2, limits: &Rect<f32>) -> Vec2 {
+    fn clamp_axis(center: f32, half_size: f32, min: f32, max: f32) -> f32 {
+        if (max - min) <= half_size * 2. {
+            (min + max) / 2.
+        } else {
+            center.max(min + half_size).min(max - half_size)
+        }
+    }
+
+    Vec2::new(
+        clamp_axis(center.x, half_size.x, limits.left, limits.right),
+        clamp_axis(center.y, half_size.y, limits.bottom, limits.top),
+    )
+}
+
+fn align_camera(mut camera_query: Query<&mut Transform, (Changed<Transform>, With<Free2DCamera>)>) {
+    for mut transform in camera_query.iter_mut() {
+        if transform.translation != transform.translation.floor() {
+            transform.translation = transform.translation.floor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::Free2DCamera;
+
+    #[test]
+    fn pixel_perfect_zoom_stays_on_whole_steps() {
+        let mut free_2d_camera = Free2DCamera::new(1.)
+            .with_scale_range(1.0..=4.)
+            .with_zoom_smooth_time(0.1)
+            .with_virtual_resolution(Vec2::new(320., 180.));
+
+        free_2d_camera.zoom_by(1.);
+        free_2d_camera.current_scale = free_2d_camera.eased_scale(1. / 60.);
+        assert_eq!(free_2d_camera.current_scale, 2.);
+    }
+
+    #[test]
+    fn zoom_eases_without_a_virtual_resolution() {
+        let mut free_2d_camera = Free2DCamera::new(1.)
+            .with_scale_range(1.0..=4.)
+            .with_zoom_smooth_time(0.1);
+
+        free_2d_camera.zoom_by(1.);
+        free_2d_camera.current_scale = free_2d_camera.eased_scale(1. / 60.);
+        assert!(free_2d_camera.current_scale > 1. && free_2d_camera.current_scale < 2.);
+    }
+}
@@ -0,0 +1,72 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use super::Free2DCamera;
+
+/// Moves an entity along with the active camera to fake depth, for example for backgrounds
+///
+/// The position is relative to the parent of the entity, if it has one.
+#[derive(Debug, Clone)]
+pub struct ParallaxLayer {
+    /// How much the layer moves compared to the world, per axis
+    ///
+    /// `1.` moves like the rest of the world, `0.` stays fixed on the screen.
+    pub factor: Vec2,
+    /// Where the layer is placed when the camera is at the world origin
+    pub origin: Vec2,
+    /// The content repeats every `repeat_width` pixels, the layer gets shifted by multiples of it
+    /// to always cover the camera
+    pub repeat_width: Option<f32>,
+}
+
+impl ParallaxLayer {
+    pub fn new(factor: Vec2) -> Self {
+        ParallaxLayer {
+            factor,
+            origin: Vec2::ZERO,
+            repeat_width: None,
+        }
+    }
+
+    pub fn with_origin(mut self, origin: Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_repeat_width(mut self, repeat_width: f32) -> Self {
+        self.repeat_width = Some(repeat_width);
+        self
+    }
+}
+
+pub(super) fn update_parallax_layers(
+    camera_query: Query<&Transform, (With<Free2DCamera>, Without<ParallaxLayer>)>,
+    parent_query: Query<&GlobalTransform>,
+    mut layer_query: Query<(&ParallaxLayer, &mut Transform, Option<&Parent>)>,
+) {
+    let camera_position = if let Some(camera_transform) = camera_query.iter().next() {
+        camera_transform.translation.xy()
+    } else {
+        return;
+    };
+
+    for (parallax_layer, mut transform, parent) in layer_query.iter_mut() {
+        // The camera position is snapped to whole pixels already, flooring the offset keeps the
+        // layer on the pixel grid as well
+        let offset = (camera_position * (Vec2::ONE - parallax_layer.factor)).floor();
+        let mut position = parallax_layer.origin + offset;
+
+        if let Some(repeat_width) = parallax_layer.repeat_width {
+            let parent_x = parent
+                .and_then(|parent| parent_query.get(parent.0).ok())
+                .map_or(0., |parent_transform| parent_transform.translation.x);
+
+            let distance = camera_position.x - (parent_x + position.x);
+            position.x += (distance / repeat_width).floor() * repeat_width;
+        }
+
+        let translation = position.extend(transform.translation.z);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
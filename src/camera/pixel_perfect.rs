@@ -0,0 +1,150 @@
+use bevy::{prelude::*, render::camera::OrthographicProjection};
+
+use super::Free2DCamera;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LetterboxSide {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// One of the bars covering the window around the viewport of a pixel perfect camera
+struct LetterboxBar(LetterboxSide);
+
+pub(super) fn set_scale(
+    transform: &mut Transform,
+    ortographic_project: &mut OrthographicProjection,
+    total_scale: f32,
+) {
+    transform.scale = Vec3::splat(1. / total_scale);
+    ortographic_project.far = (1000. * total_scale).floor();
+}
+
+/// Keeps the scale of the cameras in sync with the window size, which changes the integer scale
+/// of pixel perfect cameras
+pub(super) fn apply_camera_scale(
+    windows: Res<Windows>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection, &Free2DCamera)>,
+) {
+    let primary_window = if let Some(win) = windows.get_primary() {
+        win
+    } else {
+        error!("No primary window!");
+        return;
+    };
+
+    let screen_size = Vec2::new(primary_window.width(), primary_window.height());
+
+    for (mut transform, mut ortographic_project, free_2d_camera) in camera_query.iter_mut() {
+        let total_scale = free_2d_camera.total_scale(screen_size);
+
+        if transform.scale != Vec3::splat(1. / total_scale) {
+            set_scale(&mut transform, &mut ortographic_project, total_scale);
+        }
+    }
+}
+
+pub(super) fn update_letterbox(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    camera_query: Query<&Free2DCamera>,
+    mut bar_query: Query<(&LetterboxBar, &mut Style)>,
+) {
+    let primary_window = if let Some(win) = windows.get_primary() {
+        win
+    } else {
+        error!("No primary window!");
+        return;
+    };
+
+    let screen_size = Vec2::new(primary_window.width(), primary_window.height());
+
+    let viewport_size = camera_query
+        .iter()
+        .find(|free_2d_camera| free_2d_camera.virtual_resolution.is_some())
+        .map(|free_2d_camera| free_2d_camera.viewport_size(screen_size));
+
+    let viewport_size = if let Some(viewport_size) = viewport_size {
+        viewport_size
+    } else {
+        for (_, mut style) in bar_query.iter_mut() {
+            if style.display != Display::None {
+                style.display = Display::None;
+            }
+        }
+        return;
+    };
+
+    if bar_query.is_empty() {
+        let black_material = materials.add(Color::BLACK.into());
+
+        for side in [
+            LetterboxSide::Left,
+            LetterboxSide::Right,
+            LetterboxSide::Top,
+            LetterboxSide::Bottom,
+        ] {
+            commands
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..Default::default()
+                    },
+                    material: black_material.clone(),
+                    ..Default::default()
+                })
+                .insert(LetterboxBar(side));
+        }
+
+        // The bars get sized once they exist
+        return;
+    }
+
+    let gap = ((screen_size - viewport_size) / 2.).max(Vec2::ZERO);
+
+    for (bar, mut style) in bar_query.iter_mut() {
+        let (position, size) = match bar.0 {
+            LetterboxSide::Left => (
+                Rect {
+                    left: Val::Px(0.),
+                    top: Val::Px(0.),
+                    ..Default::default()
+                },
+                Size::new(Val::Px(gap.x), Val::Percent(100.)),
+            ),
+            LetterboxSide::Right => (
+                Rect {
+                    right: Val::Px(0.),
+                    top: Val::Px(0.),
+                    ..Default::default()
+                },
+                Size::new(Val::Px(gap.x), Val::Percent(100.)),
+            ),
+            LetterboxSide::Top => (
+                Rect {
+                    left: Val::Px(0.),
+                    top: Val::Px(0.),
+                    ..Default::default()
+                },
+                Size::new(Val::Percent(100.), Val::Px(gap.y)),
+            ),
+            LetterboxSide::Bottom => (
+                Rect {
+                    left: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    ..Default::default()
+                },
+                Size::new(Val::Percent(100.), Val::Px(gap.y)),
+            ),
+        };
+
+        if style.position != position || style.size != size || style.display != Display::Flex {
+            style.position = position;
+            style.size = size;
+            style.display = Display::Flex;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::{
+    ecs::system::SystemParam,
+    math::{IVec2, Vec3Swizzles},
+    prelude::*,
+};
+
+use super::Free2DCamera;
+
+/// Converts between window, world and tile coordinates as seen through a [`Free2DCamera`]
+///
+/// Window coordinates are in screen pixels with the origin in the bottom left corner, like
+/// [`Window::cursor_position`]. Tiles are centered on their grid position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraProjection {
+    /// The world position in the center of the window
+    pub translation: Vec2,
+    /// How many screen pixels a world pixel covers
+    pub scale: f32,
+    /// The size of the window in screen pixels
+    pub window_size: Vec2,
+}
+
+impl CameraProjection {
+    pub fn new(free_2d_camera: &Free2DCamera, translation: Vec2, window_size: Vec2) -> Self {
+        CameraProjection {
+            translation,
+            scale: free_2d_camera.total_scale(window_size),
+            window_size,
+        }
+    }
+
+    pub fn window_to_world(&self, window_position: Vec2) -> Vec2 {
+        self.translation + (window_position - self.window_size / 2.) / self.scale
+    }
+
+    pub fn world_to_window(&self, world_position: Vec2) -> Vec2 {
+        (world_position - self.translation) * self.scale + self.window_size / 2.
+    }
+
+    /// The tile under the given window position, in a grid of `tile_size` starting at `origin`
+    pub fn window_to_tile(&self, window_position: Vec2, origin: Vec2, tile_size: Vec2) -> IVec2 {
+        world_to_tile(self.window_to_world(window_position), origin, tile_size)
+    }
+
+    /// The window position of the center of the given tile
+    pub fn tile_to_window(&self, tile: IVec2, origin: Vec2, tile_size: Vec2) -> Vec2 {
+        self.world_to_window(tile_to_world(tile, origin, tile_size))
+    }
+}
+
+/// The tile containing `world_position`, in a grid of `tile_size` starting at `origin`
+pub fn world_to_tile(world_position: Vec2, origin: Vec2, tile_size: Vec2) -> IVec2 {
+    let tile = ((world_position - origin + tile_size / 2.) / tile_size).floor();
+    IVec2::new(tile.x as i32, tile.y as i32)
+}
+
+/// The world position of the center of `tile`, in a grid of `tile_size` starting at `origin`
+pub fn tile_to_world(tile: IVec2, origin: Vec2, tile_size: Vec2) -> Vec2 {
+    origin + tile.as_f32() * tile_size
+}
+
+/// Gives access to the [`CameraProjection`] of every [`Free2DCamera`]
+#[derive(SystemParam)]
+pub struct CameraProjections<'w, 's> {
+    windows: Res<'w, Windows>,
+    camera_query: Query<'w, 's, (Entity, &'static Transform, &'static Free2DCamera)>,
+}
+
+impl<'w, 's> CameraProjections<'w, 's> {
+    pub fn get(&self, camera: Entity) -> Option<CameraProjection> {
+        let primary_window = self.windows.get_primary()?;
+        let window_size = Vec2::new(primary_window.width(), primary_window.height());
+
+        let (_, transform, free_2d_camera) = self.camera_query.get(camera).ok()?;
+
+        Some(CameraProjection::new(
+            free_2d_camera,
+            transform.translation.xy(),
+            window_size,
+        ))
+    }
+
+    /// The projection of the first camera, for when there is only one
+    pub fn single(&self) -> Option<CameraProjection> {
+        let (camera, _, _) = self.camera_query.iter().next()?;
+        self.get(camera)
+    }
+
+    /// The world position under the mouse cursor, if it is inside of the window
+    pub fn cursor_to_world(&self, camera: Entity) -> Option<Vec2> {
+        let cursor_position = self.windows.get_primary()?.cursor_position()?;
+        Some(self.get(camera)?.window_to_world(cursor_position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{IVec2, Vec2};
+
+    use super::{tile_to_world, world_to_tile, CameraProjection};
+    use crate::camera::Free2DCamera;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).abs().max_element() < 0.001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn window_center_is_camera_translation() {
+        let projection = CameraProjection::new(
+            &Free2DCamera::new(2.),
+            Vec2::new(100., -50.),
+            Vec2::new(800., 600.),
+        );
+
+        assert_close(
+            projection.window_to_world(Vec2::new(400., 300.)),
+            Vec2::new(100., -50.),
+        );
+        assert_close(
+            projection.window_to_world(Vec2::new(410., 300.)),
+            Vec2::new(105., -50.),
+        );
+    }
+
+    #[test]
+    fn world_window_round_trip() {
+        let projection = CameraProjection::new(
+            &Free2DCamera::new(3.),
+            Vec2::new(-12., 40.),
+            Vec2::new(1024., 768.),
+        );
+
+        let world = Vec2::new(33., -71.);
+        assert_close(
+            projection.window_to_world(projection.world_to_window(world)),
+            world,
+        );
+    }
+
+    #[test]
+    fn virtual_resolution_uses_integer_scale() {
+        let free_2d_camera = Free2DCamera::new(1.).with_virtual_resolution(Vec2::new(320., 180.));
+
+        let projection = CameraProjection::new(&free_2d_camera, Vec2::ZERO, Vec2::new(1300., 740.));
+
+        assert_eq!(projection.scale, 4.);
+        assert_close(
+            projection.window_to_world(Vec2::new(650. + 8., 370.)),
+            Vec2::new(2., 0.),
+        );
+    }
+
+    #[test]
+    fn tiles_are_centered_on_grid() {
+        let tile_size = Vec2::new(16., 16.);
+
+        assert_eq!(
+            world_to_tile(Vec2::new(7.9, -8.1), Vec2::ZERO, tile_size),
+            IVec2::new(0, -1)
+        );
+        assert_eq!(
+            world_to_tile(Vec2::new(40., 24.), Vec2::new(16., 0.), tile_size),
+            IVec2::new(2, 2)
+        );
+        assert_close(
+            tile_to_world(IVec2::new(2, 3), Vec2::new(16., 0.), tile_size),
+            Vec2::new(48., 48.),
+        );
+    }
+}
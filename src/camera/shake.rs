@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+
+use crate::{
+    game_config::{changed_config, ConfigChanged},
+    replay::GameSeed,
+    GameAssets,
+};
+
+/// Adds trauma to all cameras with a [`CameraShake`], `1.` being the strongest shake
+#[derive(Debug, Clone, Copy)]
+pub struct CameraShakeEvent(pub f32);
+
+/// Scales all camera shake, set it to `0.` to disable shaking completely
+#[derive(Debug)]
+pub struct CameraShakeSettings {
+    pub intensity: f32,
+}
+
+impl Default for CameraShakeSettings {
+    fn default() -> Self {
+        CameraShakeSettings { intensity: 1. }
+    }
+}
+
+/// Trauma based shaking of a camera
+///
+/// The shake is applied on top of the camera position and undone at the start of the next frame,
+/// so it never accumulates. The shaken camera is still clamped to its limits and snapped to whole
+/// pixels.
+#[derive(Debug, Clone)]
+pub struct CameraShake {
+    /// Between `0.` and `1.`, the shake grows with the square of it
+    pub trauma: f32,
+    /// How much trauma is lost per second
+    pub decay: f32,
+    /// Offset (in world pixels) at full shake
+    pub max_offset: Vec2,
+    /// Rotation (in radians) at full shake
+    pub max_angle: f32,
+    /// How fast the shake moves, in noise samples per second
+    pub frequency: f32,
+    time: f32,
+    restore: Option<Vec3>,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake {
+            trauma: 0.,
+            decay: 1.5,
+            max_offset: Vec2::new(8., 8.),
+            max_angle: 0.05,
+            frequency: 20.,
+            time: 0.,
+            restore: None,
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn with_max_offset(mut self, max_offset: Vec2) -> Self {
+        self.max_offset = max_offset;
+        self
+    }
+
+    pub fn with_max_angle(mut self, max_angle: f32) -> Self {
+        self.max_angle = max_angle;
+        self
+    }
+
+    pub fn with_decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.).max(0.);
+    }
+}
+
+pub(super) fn receive_shake_events(
+    mut shake_events: EventReader<CameraShakeEvent>,
+    mut camera_query: Query<&mut CameraShake>,
+) {
+    for CameraShakeEvent(trauma) in shake_events.iter() {
+        for mut camera_shake in camera_query.iter_mut() {
+            camera_shake.add_trauma(*trauma);
+        }
+    }
+}
+
+/// Undoes the shake of the last frame, before anything else moves the cameras
+pub(super) fn restore_shaken_cameras(mut camera_query: Query<(&mut Transform, &mut CameraShake)>) {
+    for (mut transform, mut camera_shake) in camera_query.iter_mut() {
+        if let Some(translation) = camera_shake.restore.take() {
+            transform.translation = translation;
+            transform.rotation = Quat::IDENTITY;
+        }
+    }
+}
+
+pub(super) fn apply_camera_shake(
+    time: Res<Time>,
+    settings: Res<CameraShakeSettings>,
+    game_seed: Res<GameSeed>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShake)>,
+) {
+    let seed = game_seed.derive(b"camera shake");
+
+    for (mut transform, mut camera_shake) in camera_query.iter_mut() {
+        if camera_shake.trauma <= 0. {
+            continue;
+        }
+
+        camera_shake.time += time.delta_seconds();
+        camera_shake.trauma =
+            (camera_shake.trauma - camera_shake.decay * time.delta_seconds()).max(0.);
+
+        let shake = camera_shake.trauma * camera_shake.trauma * settings.intensity.max(0.);
+        if shake <= 0. {
+            continue;
+        }
+
+        let sample = camera_shake.time * camera_shake.frequency;
+
+        let offset = camera_shake.max_offset
+            * shake
+            * Vec2::new(
+                perlin_1d(sample, seed),
+                perlin_1d(sample, seed.wrapping_add(1)),
+            );
+        let angle = camera_shake.max_angle * shake * perlin_1d(sample, seed.wrapping_add(2));
+
+        camera_shake.restore = Some(transform.translation);
+        transform.translation += offset.extend(0.);
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+pub(super) fn load_shake_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<CameraShakeSettings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    settings.intensity = config.accessibility.camera_shake as f32;
+}
+
+/// One dimensional gradient noise, smoothly varying between roughly `-1.` and `1.`
+fn perlin_1d(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+
+    let gradient_0 = gradient(cell as i32, seed);
+    let gradient_1 = gradient(cell as i32 + 1, seed);
+
+    let fade = t * t * t * (t * (t * 6. - 15.) + 10.);
+    let value_0 = gradient_0 * t;
+    let value_1 = gradient_1 * (t - 1.);
+
+    (value_0 + (value_1 - value_0) * fade) * 2.
+}
+
+/// A pseudo random gradient between `-1.` and `1.` for every integer
+fn gradient(cell: i32, seed: u32) -> f32 {
+    let mut hash = (cell as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+
+    (hash as f32 / u32::MAX as f32) * 2. - 1.
+}
@@ -0,0 +1,282 @@
+//! The command line flags of the game, parsed before the [`App`] is built
+
+use std::path::PathBuf;
+
+use bevy::{
+    app::{PluginGroupBuilder, ScheduleRunnerPlugin},
+    asset::AssetPlugin,
+    core::CorePlugin,
+    diagnostic::DiagnosticsPlugin,
+    input::InputPlugin,
+    log::LogPlugin,
+    prelude::*,
+    render::{
+        renderer::{HeadlessRenderResourceContext, RenderResourceContext},
+        RenderPlugin,
+    },
+    sprite::SpritePlugin,
+    text::TextPlugin,
+    transform::TransformPlugin,
+    window::WindowPlugin,
+};
+
+use crate::audio::{AudioBackend, NullAudioBackend};
+
+pub const USAGE: &str = "\
+Usage: bevy_spicy_gamebase [OPTIONS]
+
+Options:
+    --level <NAME>      Start right in the level, skipping the menus
+    --skip-menu         Start right in the first level of the progression
+    --windowed <WxH>    Open a window of the given size, e.g. 1280x720
+    --no-hot-reload     Don't reload assets when they change on disk
+    --seed <N>          Seed everything random in the game
+    --replay <FILE>     Play back a replay recorded earlier, starting in its level
+    --verify            Check the replay instead of only playing it, exits with 1 if it diverged,
+                        replays are always checked with --headless
+    --record <FILE>     Record every attempt at a level, the file is written when the level is left
+    --ghost <FILE>      Race against a replay recorded earlier, starting in its level
+    --headless          Run without a window or a GPU
+    --help              Print this message";
+
+/// What the game was asked to do from the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchOptions {
+    pub level: Option<String>,
+    pub skip_menu: bool,
+    pub window_size: Option<Vec2>,
+    pub hot_reload: bool,
+    pub seed: Option<u64>,
+    pub replay: Option<PathBuf>,
+    /// Verify the [`replay`](Self::replay) instead of playing it
+    pub verify: bool,
+    pub record: Option<PathBuf>,
+    /// A replay to show as a ghost while playing
+    pub ghost: Option<PathBuf>,
+    pub headless: bool,
+    pub help: bool,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        LaunchOptions {
+            level: None,
+            skip_menu: false,
+            window_size: None,
+            hot_reload: true,
+            seed: None,
+            replay: None,
+            verify: false,
+            record: None,
+            ghost: None,
+            headless: false,
+            help: false,
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Parses the arguments without the name of the program
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+
+            match arg.as_str() {
+                "--level" => options.level = Some(value("--level")?),
+                "--skip-menu" => options.skip_menu = true,
+                "--windowed" => {
+                    let size = value("--windowed")?;
+                    options.window_size = Some(
+                        parse_window_size(&size)
+                            .ok_or_else(|| format!("invalid window size '{}'", size))?,
+                    );
+                }
+                "--no-hot-reload" => options.hot_reload = false,
+                "--seed" => {
+                    let seed = value("--seed")?;
+                    options.seed = Some(
+                        seed.parse()
+                            .map_err(|_| format!("invalid seed '{}'", seed))?,
+                    );
+                }
+                "--replay" => options.replay = Some(PathBuf::from(value("--replay")?)),
+                "--verify" => options.verify = true,
+                "--record" => options.record = Some(PathBuf::from(value("--record")?)),
+                "--ghost" => options.ghost = Some(PathBuf::from(value("--ghost")?)),
+                "--headless" => options.headless = true,
+                "--help" | "-h" => options.help = true,
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if options.verify && options.replay.is_none() {
+            return Err(String::from("--verify needs a --replay to check"));
+        }
+
+        let replay_modes = [
+            options.replay.is_some(),
+            options.record.is_some(),
+            options.ghost.is_some(),
+        ];
+        if replay_modes.iter().filter(|enabled| **enabled).count() > 1 {
+            return Err(String::from(
+                "only one of --replay, --record and --ghost can be used at once",
+            ));
+        }
+
+        Ok(options)
+    }
+
+    /// Whether to go straight from loading into a level
+    pub fn skips_menu(&self) -> bool {
+        self.skip_menu || self.level.is_some() || self.replay.is_some() || self.ghost.is_some()
+    }
+}
+
+fn parse_window_size(size: &str) -> Option<Vec2> {
+    let (width, height) = size.split_once('x')?;
+    let size = Vec2::new(width.parse().ok()?, height.parse().ok()?);
+
+    if size.min_element() > 0. {
+        Some(size)
+    } else {
+        None
+    }
+}
+
+/// Everything the [`GamePlugins`](crate::GamePlugins) need from bevy, without a window or a GPU
+///
+/// The [`LogPlugin`] can only be added once per process, disable it when running several apps.
+pub struct HeadlessPlugins;
+
+impl PluginGroup for HeadlessPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(LogPlugin::default())
+            .add(CorePlugin::default())
+            .add(ScheduleRunnerPlugin::default())
+            .add(DiagnosticsPlugin::default())
+            .add(TransformPlugin::default())
+            .add(InputPlugin::default())
+            .add(WindowPlugin {
+                add_primary_window: false,
+                ..Default::default()
+            })
+            .add(AssetPlugin::default())
+            .add(HeadlessRenderPlugin)
+            .add(RenderPlugin::default())
+            .add(SpritePlugin::default())
+            .add(TextPlugin::default())
+            .add(bevy::ui::UiPlugin::default())
+            .add(HeadlessAudioPlugin);
+    }
+}
+
+/// Lets the [`RenderPlugin`] run without a GPU, has to be added before it
+struct HeadlessRenderPlugin;
+
+impl Plugin for HeadlessRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource::<Box<dyn RenderResourceContext>>(Box::new(
+            HeadlessRenderResourceContext::default(),
+        ));
+    }
+}
+
+/// Keeps the game silent without touching the audio device, has to be added before the
+/// [`AudioPlugin`](crate::audio::AudioPlugin)
+struct HeadlessAudioPlugin;
+
+impl Plugin for HeadlessAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource::<Box<dyn AudioBackend>>(Box::new(NullAudioBackend::default()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use super::LaunchOptions;
+
+    fn parse(args: &[&str]) -> Result<LaunchOptions, String> {
+        LaunchOptions::parse(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn no_arguments_start_in_the_menu() {
+        let options = parse(&[]).unwrap();
+
+        assert_eq!(options, LaunchOptions::default());
+        assert!(options.hot_reload);
+        assert!(!options.skips_menu());
+    }
+
+    #[test]
+    fn all_flags_are_parsed() {
+        let options = parse(&[
+            "--level",
+            "Level_1",
+            "--windowed",
+            "1280x720",
+            "--no-hot-reload",
+            "--seed",
+            "42",
+            "--headless",
+        ])
+        .unwrap();
+
+        assert_eq!(options.level.as_deref(), Some("Level_1"));
+        assert_eq!(options.window_size, Some(Vec2::new(1280., 720.)));
+        assert!(!options.hot_reload);
+        assert_eq!(options.seed, Some(42));
+        assert!(options.headless);
+        assert!(options.skips_menu());
+    }
+
+    #[test]
+    fn replays_can_be_verified() {
+        let options = parse(&["--replay", "run.ron", "--verify", "--headless"]).unwrap();
+
+        assert_eq!(
+            options.replay.as_deref(),
+            Some(std::path::Path::new("run.ron"))
+        );
+        assert!(options.verify);
+        assert!(options.skips_menu());
+    }
+
+    #[test]
+    fn replays_can_be_recorded_and_raced() {
+        let options = parse(&["--record", "bug.ron"]).unwrap();
+        assert_eq!(
+            options.record.as_deref(),
+            Some(std::path::Path::new("bug.ron"))
+        );
+        assert!(!options.skips_menu());
+
+        let options = parse(&["--ghost", "best.ron"]).unwrap();
+        assert_eq!(
+            options.ghost.as_deref(),
+            Some(std::path::Path::new("best.ron"))
+        );
+        assert!(options.skips_menu());
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        assert!(parse(&["--level"]).is_err());
+        assert!(parse(&["--windowed", "1280"]).is_err());
+        assert!(parse(&["--windowed", "0x720"]).is_err());
+        assert!(parse(&["--seed", "many"]).is_err());
+        assert!(parse(&["--verify"]).is_err());
+        assert!(parse(&["--record"]).is_err());
+        assert!(parse(&["--replay", "run.ron", "--ghost", "run.ron"]).is_err());
+        assert!(parse(&["--record", "bug.ron", "--ghost", "run.ron"]).is_err());
+        assert!(parse(&["--fullscreen"]).is_err());
+    }
+}
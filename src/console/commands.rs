@@ -0,0 +1,225 @@
+use bevy::{asset::AssetServerSettings, prelude::*};
+
+use super::{AddConsoleCommand, ConsoleCommand, ConsoleCommands};
+use crate::{
+    actions::{Action, Binding, InputBindings},
+    game_config::{self, ConfigOverrides},
+    stages::{GameState, TransitionEffect, TransitionEvent},
+    GameAssets,
+};
+
+const STATES: [(&str, GameState); 6] = [
+    ("Loading", GameState::Loading),
+    ("MainMenu", GameState::MainMenu),
+    ("LevelSelect", GameState::LevelSelect),
+    ("Running", GameState::Running),
+    ("LevelComplete", GameState::LevelComplete),
+    ("GameOver", GameState::GameOver),
+];
+
+pub fn add_builtin_commands(app: &mut App) {
+    app.add_console_command(
+        ConsoleCommand::new("help", "help", help).with_completion(complete_help),
+    );
+    app.add_console_command(
+        ConsoleCommand::new("state", "state <GameState>", set_state).with_completion(|args, _| {
+            match args {
+                [] => STATES.iter().map(|(name, _)| (*name).to_owned()).collect(),
+                _ => Vec::new(),
+            }
+        }),
+    );
+    app.add_console_command(
+        ConsoleCommand::new("config", "config set <key> <value>", set_config)
+            .with_completion(complete_config),
+    );
+    app.add_console_command(
+        ConsoleCommand::new("bind", "bind <Action> [<Binding>|reset]", bind).with_completion(
+            |args, _| match args {
+                [] => Action::ALL
+                    .iter()
+                    .map(|action| format!("{:?}", action))
+                    .collect(),
+                [_] => vec![String::from("reset")],
+                _ => Vec::new(),
+            },
+        ),
+    );
+}
+
+fn help(args: &[&str], world: &mut World) -> Result<String, String> {
+    let commands = world
+        .get_resource::<ConsoleCommands>()
+        .ok_or("there are no commands")?;
+
+    let usages: Vec<&str> = match args {
+        [] => commands.iter().map(|command| command.usage).collect(),
+        [name] => vec![
+            commands
+                .get(name)
+                .ok_or_else(|| format!("unknown command '{}'", name))?
+                .usage,
+        ],
+        _ => return Err(String::from("expected at most one command")),
+    };
+
+    Ok(usages.join("\n"))
+}
+
+fn complete_help(args: &[&str], world: &World) -> Vec<String> {
+    match (args, world.get_resource::<ConsoleCommands>()) {
+        ([], Some(commands)) => commands
+            .iter()
+            .map(|command| command.name.to_owned())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn set_state(args: &[&str], world: &mut World) -> Result<String, String> {
+    let name = match args {
+        [name] => *name,
+        _ => return Err(String::from("expected a state")),
+    };
+
+    let state = STATES
+        .iter()
+        .find(|(state_name, _)| state_name.eq_ignore_ascii_case(name))
+        .map(|(_, state)| *state)
+        .ok_or_else(|| format!("unknown state '{}'", name))?;
+
+    // Like the menus, so leaving a state behaves the same as in the game
+    world
+        .get_resource_mut::<Events<TransitionEvent>>()
+        .ok_or("the game has no screen transitions")?
+        .send(TransitionEvent::new(state, TransitionEffect::Fade));
+
+    Ok(format!("Switching to {:?}", state))
+}
+
+/// Without a binding, the next input pressed once the console is closed is bound
+fn bind(args: &[&str], world: &mut World) -> Result<String, String> {
+    let (name, binding) = match args {
+        [name] => (*name, None),
+        [name, binding] => (*name, Some(*binding)),
+        _ => return Err(String::from("expected an action and at most one binding")),
+    };
+
+    let action = Action::ALL
+        .iter()
+        .copied()
+        .find(|action| format!("{:?}", action).eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown action '{}'", name))?;
+
+    let mut bindings = world
+        .get_resource_mut::<InputBindings>()
+        .ok_or("the game has no input bindings")?;
+
+    match binding {
+        None => {
+            bindings.capture_next(action);
+            Ok(format!(
+                "Close the console and press the input for {:?}",
+                action
+            ))
+        }
+        Some("reset") => {
+            bindings.reset(action);
+            Ok(format!("Reset {:?} to the bindings of game.config", action))
+        }
+        Some(binding) => {
+            let binding = Binding::parse(binding)?;
+            bindings.rebind(action, vec![binding]);
+            Ok(format!("Bound {:?} to {}", action, binding))
+        }
+    }
+}
+
+fn read_config(world: &World) -> Result<toml::Value, String> {
+    game_config::read_config_file(world.get_resource::<AssetServerSettings>())
+}
+
+/// The dotted paths to all values of the config, like `player.jump_speed`
+fn config_keys(value: &toml::Value, prefix: &str, keys: &mut Vec<String>) {
+    if let toml::Value::Table(table) = value {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+
+            if value.is_table() {
+                config_keys(value, &path, keys);
+            } else {
+                keys.push(path);
+            }
+        }
+    }
+}
+
+/// Values are written like in `game.config`, anything that is not valid TOML is taken as a string
+fn parse_config_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()))
+}
+
+fn set_config(args: &[&str], world: &mut World) -> Result<String, String> {
+    let (key, text) = match args {
+        ["set", key, value @ ..] if !value.is_empty() => (*key, value.join(" ")),
+        _ => return Err(String::from("expected a key and a value")),
+    };
+    let value = parse_config_value(&text);
+
+    let mut config = world
+        .get_resource::<ConfigOverrides>()
+        .ok_or("the game has no config overrides")?
+        .apply_to_file(world.get_resource::<AssetServerSettings>())?;
+    game_config::set_config_value(&mut config, key, value.clone())?;
+
+    let root: crate::config::Root = config
+        .try_into()
+        .map_err(|err| format!("invalid value for '{}': {}", key, err))?;
+    game_config::validate(&root).map_err(|errors| {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+
+    let handle = world
+        .get_resource::<GameAssets>()
+        .ok_or("the config is not loaded yet")?
+        .config
+        .clone();
+
+    // Every system using the config picks the change up like a hot reload
+    world
+        .get_resource_mut::<Assets<crate::config::Root>>()
+        .ok_or("there are no config assets")?
+        .set(handle, root);
+
+    world
+        .get_resource_mut::<ConfigOverrides>()
+        .ok_or("the game has no config overrides")?
+        .insert_applied(key, value);
+
+    Ok(format!("Set {} to {}", key, text))
+}
+
+fn complete_config(args: &[&str], world: &World) -> Vec<String> {
+    match args {
+        [] => vec![String::from("set")],
+        ["set"] => {
+            let mut keys = Vec::new();
+            if let Ok(config) = read_config(world) {
+                config_keys(&config, "", &mut keys);
+            }
+            keys
+        }
+        _ => Vec::new(),
+    }
+}
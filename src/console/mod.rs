@@ -0,0 +1,428 @@
+//! The developer console, opened with the backtick key
+//!
+//! Plugins add their own commands through [`AddConsoleCommand`], the commands get the whole
+//! [`World`] to work with.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::{actions::InputBindings, GameAssets};
+
+mod commands;
+
+/// How many lines of output are kept and shown
+const MAX_OUTPUT_LINES: usize = 12;
+const MAX_HISTORY: usize = 50;
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+const TOGGLE_CHARACTER: char = '`';
+
+#[derive(Debug, Default)]
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>();
+        app.init_resource::<ConsoleCommands>();
+        app.add_system(toggle_console.label(ConsoleSystems::Toggle));
+        app.add_system(
+            edit_console_input
+                .label(ConsoleSystems::Input)
+                .after(ConsoleSystems::Toggle),
+        );
+        app.add_system(run_console_commands.exclusive_system().at_end());
+        app.add_system_to_stage(CoreStage::PostUpdate, update_console_ui);
+
+        commands::add_builtin_commands(app);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum ConsoleSystems {
+    Toggle,
+    Input,
+}
+
+/// Runs a command with its arguments, the returned text is printed to the console
+pub type RunCommand = fn(&[&str], &mut World) -> Result<String, String>;
+
+/// Lists the candidates for the next argument, given the arguments before it
+pub type CompleteCommand = fn(&[&str], &World) -> Vec<String>;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    pub name: &'static str,
+    /// Shown by `help`, e.g. `tp <x> <y>`
+    pub usage: &'static str,
+    pub run: RunCommand,
+    pub complete: CompleteCommand,
+}
+
+impl ConsoleCommand {
+    pub fn new(name: &'static str, usage: &'static str, run: RunCommand) -> Self {
+        ConsoleCommand {
+            name,
+            usage,
+            run,
+            complete: |_, _| Vec::new(),
+        }
+    }
+
+    pub fn with_completion(mut self, complete: CompleteCommand) -> Self {
+        self.complete = complete;
+        self
+    }
+}
+
+/// All commands the console knows, by name
+#[derive(Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    pub fn add(&mut self, command: ConsoleCommand) {
+        if self.commands.insert(command.name, command).is_some() {
+            warn!("The console command {} was added twice", command.name);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ConsoleCommand> {
+        self.commands.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ConsoleCommand> {
+        self.commands.values()
+    }
+}
+
+pub trait AddConsoleCommand {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world
+            .get_resource_mut::<ConsoleCommands>()
+            .expect("The resource was just initialized")
+            .add(command);
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: VecDeque<String>,
+    history: Vec<String>,
+    /// The entry of the history shown in the input, counted from the newest
+    history_index: Option<usize>,
+    /// Submitted lines waiting to be run
+    pending: Vec<String>,
+    complete_requested: bool,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        for line in line.into().lines() {
+            self.output.push_back(line.to_owned());
+        }
+
+        while self.output.len() > MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Runs `line` at the end of the frame, as if it was typed in
+    pub fn submit(&mut self, line: impl Into<String>) {
+        self.pending.push(line.into());
+    }
+
+    fn show_history(&mut self, index: Option<usize>) {
+        self.history_index = index;
+        self.input = match index {
+            Some(index) => self.history[self.history.len() - 1 - index].clone(),
+            None => String::new(),
+        };
+    }
+}
+
+#[derive(Debug, Default)]
+struct ConsoleUi;
+
+#[derive(Debug, Default)]
+struct ConsoleText;
+
+fn toggle_console(
+    keyboard: Res<Input<KeyCode>>,
+    mut console: ResMut<Console>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let close = console.open && keyboard.just_pressed(KeyCode::Escape);
+
+    if !keyboard.just_pressed(TOGGLE_KEY) && !close {
+        return;
+    }
+
+    console.open = !console.open;
+    bindings.set_suspended(console.open);
+}
+
+fn edit_console_input(
+    keyboard: Res<Input<KeyCode>>,
+    mut character_events: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+) {
+    if !console.open {
+        // Don't type what was entered while the console was closed
+        character_events.iter().for_each(drop);
+        return;
+    }
+
+    for event in character_events.iter() {
+        if !event.char.is_control() && event.char != TOGGLE_CHARACTER {
+            console.input.push(event.char);
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+
+    if keyboard.just_pressed(KeyCode::Tab) {
+        console.complete_requested = true;
+    }
+
+    if keyboard.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let newest = console.history.len() - 1;
+        let index = console
+            .history_index
+            .map_or(0, |index| (index + 1).min(newest));
+        console.show_history(Some(index));
+    }
+
+    if keyboard.just_pressed(KeyCode::Down) {
+        let index = console.history_index.and_then(|index| index.checked_sub(1));
+        console.show_history(index);
+    }
+
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.history_index = None;
+
+        if line.trim().is_empty() {
+            return;
+        }
+
+        if console.history.last() != Some(&line) {
+            console.history.push(line.clone());
+            if console.history.len() > MAX_HISTORY {
+                console.history.remove(0);
+            }
+        }
+
+        console.submit(line);
+    }
+}
+
+/// Runs the submitted lines and completes the input, both need the whole world
+fn run_console_commands(world: &mut World) {
+    let idle = world.get_resource::<Console>().map_or(true, |console| {
+        console.pending.is_empty() && !console.complete_requested
+    });
+    if idle {
+        return;
+    }
+
+    let (pending, complete_input) = {
+        let mut console = world
+            .get_resource_mut::<Console>()
+            .expect("The ConsolePlugin adds the console");
+
+        let complete_input = if std::mem::take(&mut console.complete_requested) {
+            Some(console.input.clone())
+        } else {
+            None
+        };
+
+        (std::mem::take(&mut console.pending), complete_input)
+    };
+
+    let mut output = Vec::new();
+
+    for line in pending {
+        output.push(format!("> {}", line));
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = if let Some((name, args)) = words.split_first() {
+            (*name, args)
+        } else {
+            continue;
+        };
+
+        let command = world
+            .get_resource::<ConsoleCommands>()
+            .and_then(|commands| commands.get(name).copied());
+
+        match command {
+            Some(command) => match (command.run)(args, world) {
+                Ok(result) if result.is_empty() => {}
+                Ok(result) => output.push(result),
+                Err(err) => output.push(format!("{}: {}\nusage: {}", name, err, command.usage)),
+            },
+            None => output.push(format!("Unknown command '{}', try 'help'", name)),
+        }
+    }
+
+    let completed = complete_input.map(|input| complete(&input, world));
+
+    let mut console = world
+        .get_resource_mut::<Console>()
+        .expect("The ConsolePlugin adds the console");
+
+    for line in output {
+        info!("{}", line);
+        console.print(line);
+    }
+
+    if let Some((input, candidates)) = completed {
+        console.input = input;
+        if candidates.len() > 1 {
+            console.print(candidates.join("  "));
+        }
+    }
+}
+
+/// Extends the last word of `input` as far as all candidates agree, returns the new input and
+/// the candidates
+fn complete(input: &str, world: &World) -> (String, Vec<String>) {
+    let commands = if let Some(commands) = world.get_resource::<ConsoleCommands>() {
+        commands
+    } else {
+        return (input.to_owned(), Vec::new());
+    };
+
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    // A trailing space starts a new, empty word
+    let current = if input.ends_with(char::is_whitespace) || words.is_empty() {
+        ""
+    } else {
+        words.pop().unwrap_or_default()
+    };
+
+    let candidates: Vec<String> = match words.split_first() {
+        None => commands
+            .iter()
+            .map(|command| command.name.to_owned())
+            .collect(),
+        Some((name, args)) => commands
+            .get(name)
+            .map(|command| (command.complete)(args, world))
+            .unwrap_or_default(),
+    };
+
+    let mut candidates: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    let completion = match candidates.as_slice() {
+        [] => current.to_owned(),
+        [only] => format!("{} ", only),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |prefix, candidate| {
+            prefix
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }),
+    };
+
+    words.push(&completion);
+    let mut completed = words.join(" ");
+    // Keep the space typed after a word that had no candidates
+    if completion.is_empty() && input.ends_with(char::is_whitespace) {
+        completed.push(' ');
+    }
+
+    (completed, candidates)
+}
+
+fn update_console_ui(
+    mut commands: Commands,
+    console: Res<Console>,
+    game_assets: Option<Res<GameAssets>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ui_query: Query<Entity, With<ConsoleUi>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    if !console.open {
+        for entity in ui_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let prompt = format!("> {}_", console.input);
+    let contents = console
+        .output
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::once(prompt.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if let Some(mut text) = text_query.iter_mut().next() {
+        text.sections[0].value = contents;
+        return;
+    }
+
+    let game_assets = if let Some(game_assets) = game_assets {
+        game_assets
+    } else {
+        return;
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    right: Val::Px(0.),
+                    top: Val::Px(0.),
+                    bottom: Val::Undefined,
+                },
+                padding: Rect::all(Val::Px(6.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.8).into()),
+            ..Default::default()
+        })
+        .insert(ConsoleUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        contents,
+                        TextStyle {
+                            font: game_assets.main_font.clone(),
+                            font_size: 12.,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ConsoleText);
+        });
+}
@@ -0,0 +1,506 @@
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    ecs::schedule::ShouldRun,
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+    sprite::SpriteResizeMode,
+};
+
+use crate::{
+    actions::{Action, ActionState},
+    camera::Free2DCamera,
+    stages::GameState,
+    world::{
+        Collider, CollisionMap, CurrentLevel, Enemy, Exit, Level, LevelBounds, LevelName, Player,
+        Seed, TileKind, WorldLevels,
+    },
+    GameAssets,
+};
+
+/// Above everything in the levels, but still in front of the camera
+const OVERLAY_Z: f32 = 100.;
+const LINE_WIDTH: f32 = 1.;
+const NAME_FONT_SIZE: f32 = 8.;
+
+/// Draws the collision grid, the colliders, the camera limits and the level bounds on top of
+/// the game, together with a panel of the current state
+///
+/// Toggled through [`Action::ToggleDebugOverlay`].
+#[derive(Debug, Default)]
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin::default());
+        app.init_resource::<DebugOverlay>();
+        app.init_resource::<DebugMaterials>();
+        app.add_system(toggle_debug_overlay.label(DebugSystems::Toggle));
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(overlay_enabled)
+                .after(DebugSystems::Toggle)
+                .with_system(spawn_level_overlays)
+                .with_system(spawn_collider_overlays)
+                .with_system(update_camera_limits_overlay)
+                .with_system(update_debug_panel),
+        );
+        app.add_system_to_stage(CoreStage::PostUpdate, layout_outlines);
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum DebugSystems {
+    Toggle,
+}
+
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+struct DebugMaterials {
+    level_bounds: Handle<ColorMaterial>,
+    camera_limits: Handle<ColorMaterial>,
+    collider: Handle<ColorMaterial>,
+}
+
+impl FromWorld for DebugMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world
+            .get_resource_mut::<Assets<ColorMaterial>>()
+            .expect("The SpritePlugin adds the color materials");
+
+        DebugMaterials {
+            level_bounds: materials.add(Color::rgb(1., 1., 1.).into()),
+            camera_limits: materials.add(Color::rgb(1., 0.85, 0.).into()),
+            collider: materials.add(Color::rgb(0., 1., 0.4).into()),
+        }
+    }
+}
+
+/// Marks the entities spawned for the overlay, they are despawned when it is turned off
+#[derive(Debug, Default)]
+struct DebugOverlayEntity;
+
+/// The overlay drawn for a level or collider, replaced when the source changes
+#[derive(Debug)]
+struct DebugDrawn(Entity);
+
+#[derive(Debug, Default)]
+struct CameraLimitsOverlay;
+
+#[derive(Debug, Default)]
+struct DebugPanel;
+
+/// A rectangle drawn as four lines around the position of the entity
+#[derive(Debug, Clone, Copy)]
+struct DebugOutline {
+    size: Vec2,
+}
+
+fn overlay_enabled(debug_overlay: Res<DebugOverlay>) -> ShouldRun {
+    if debug_overlay.enabled {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn toggle_debug_overlay(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut debug_overlay: ResMut<DebugOverlay>,
+    overlay_query: Query<Entity, With<DebugOverlayEntity>>,
+    drawn_query: Query<Entity, With<DebugDrawn>>,
+) {
+    if !actions.just_pressed(Action::ToggleDebugOverlay) {
+        return;
+    }
+
+    debug_overlay.enabled = !debug_overlay.enabled;
+    info!(
+        "Turned the debug overlay {}",
+        if debug_overlay.enabled { "on" } else { "off" }
+    );
+
+    if !debug_overlay.enabled {
+        for entity in overlay_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        for entity in drawn_query.iter() {
+            commands.entity(entity).remove::<DebugDrawn>();
+        }
+    }
+}
+
+/// One pixel per cell, the empty cells are left transparent
+fn render_collision_map(collision_map: &CollisionMap) -> Texture {
+    let width = collision_map.size.x.max(1) as usize;
+    let height = collision_map.size.y.max(1) as usize;
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    // Textures start at the top, cells at the bottom
+    for y in (0..height as i32).rev() {
+        for x in 0..width as i32 {
+            let color: [u8; 4] = match collision_map.tile(IVec2::new(x, y)) {
+                TileKind::Empty => [0, 0, 0, 0],
+                TileKind::Solid => [60, 90, 255, 110],
+                TileKind::Lava => [255, 60, 20, 140],
+                TileKind::Ladder => [40, 220, 90, 120],
+                TileKind::Hidden => [200, 60, 255, 110],
+            };
+            data.extend_from_slice(&color);
+        }
+    }
+
+    let mut texture = Texture::new(
+        Extent3d::new(width as u32, height as u32, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.mag_filter = FilterMode::Nearest;
+
+    texture
+}
+
+/// The lines of a [`DebugOutline`], placed by [`layout_outlines`]
+fn spawn_lines(parent: &mut ChildBuilder, material: Handle<ColorMaterial>) {
+    for _ in 0..4 {
+        parent.spawn_bundle(SpriteBundle {
+            material: material.clone(),
+            sprite: Sprite {
+                resize_mode: SpriteResizeMode::Manual,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+    }
+}
+
+fn spawn_outline(
+    parent: &mut ChildBuilder,
+    translation: Vec3,
+    size: Vec2,
+    material: Handle<ColorMaterial>,
+) {
+    parent
+        .spawn_bundle((
+            Transform::from_translation(translation),
+            GlobalTransform::default(),
+            DebugOutline { size },
+        ))
+        .with_children(|parent| spawn_lines(parent, material));
+}
+
+/// Sizes and places the lines of changed outlines
+fn layout_outlines(
+    outline_query: Query<(&DebugOutline, &Children), Changed<DebugOutline>>,
+    mut line_query: Query<(&mut Sprite, &mut Transform)>,
+) {
+    for (outline, children) in outline_query.iter() {
+        let half_size = outline.size / 2.;
+        let lines = [
+            (
+                Vec2::new(0., half_size.y),
+                Vec2::new(outline.size.x, LINE_WIDTH),
+            ),
+            (
+                Vec2::new(0., -half_size.y),
+                Vec2::new(outline.size.x, LINE_WIDTH),
+            ),
+            (
+                Vec2::new(-half_size.x, 0.),
+                Vec2::new(LINE_WIDTH, outline.size.y),
+            ),
+            (
+                Vec2::new(half_size.x, 0.),
+                Vec2::new(LINE_WIDTH, outline.size.y),
+            ),
+        ];
+
+        for (child, (offset, size)) in children.iter().zip(lines) {
+            if let Ok((mut sprite, mut transform)) = line_query.get_mut(*child) {
+                sprite.size = size;
+                transform.translation = offset.extend(0.);
+            }
+        }
+    }
+}
+
+/// Draws the collision grid and the bounds of every loaded level
+fn spawn_level_overlays(
+    mut commands: Commands,
+    debug_materials: Res<DebugMaterials>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    mut texture_assets: ResMut<Assets<Texture>>,
+    level_query: Query<
+        (
+            Entity,
+            &CollisionMap,
+            &LevelBounds,
+            &GlobalTransform,
+            Option<&DebugDrawn>,
+        ),
+        With<Level>,
+    >,
+    changed_query: Query<(), Or<(Changed<CollisionMap>, Changed<LevelBounds>)>>,
+) {
+    for (entity, collision_map, bounds, global_transform, drawn) in level_query.iter() {
+        if let Some(DebugDrawn(overlay)) = drawn {
+            if changed_query.get(entity).is_err() {
+                continue;
+            }
+            commands.entity(*overlay).despawn_recursive();
+        }
+
+        let grid_size = Vec2::splat(collision_map.grid_size);
+        let map_size = collision_map.size.as_f32() * grid_size;
+        // Cell centers are at multiples of the grid size
+        let map_center = (map_size - grid_size) / 2.;
+
+        let texture = texture_assets.add(render_collision_map(collision_map));
+
+        let bounds_size = Vec2::new(
+            bounds.0.right - bounds.0.left,
+            bounds.0.top - bounds.0.bottom,
+        );
+        let bounds_center = Vec2::new(
+            bounds.0.left + bounds.0.right,
+            bounds.0.bottom + bounds.0.top,
+        ) / 2.
+            - global_transform.translation.truncate();
+
+        let mut overlay = None;
+        commands.entity(entity).with_children(|parent| {
+            overlay = Some(
+                parent
+                    .spawn_bundle((
+                        Transform::from_translation(Vec3::new(0., 0., OVERLAY_Z)),
+                        GlobalTransform::default(),
+                        DebugOverlayEntity,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(SpriteBundle {
+                            material: material_assets.add(texture.into()),
+                            sprite: Sprite {
+                                size: map_size,
+                                resize_mode: SpriteResizeMode::Manual,
+                                ..Default::default()
+                            },
+                            transform: Transform::from_translation(map_center.extend(0.)),
+                            ..Default::default()
+                        });
+
+                        spawn_outline(
+                            parent,
+                            bounds_center.extend(0.),
+                            bounds_size,
+                            debug_materials.level_bounds.clone(),
+                        );
+                    })
+                    .id(),
+            );
+        });
+
+        if let Some(overlay) = overlay {
+            commands.entity(entity).insert(DebugDrawn(overlay));
+        }
+    }
+}
+
+fn debug_name(
+    entity: Entity,
+    player: Option<&Player>,
+    enemy: Option<&Enemy>,
+    seed: Option<&Seed>,
+    exit: Option<&Exit>,
+) -> String {
+    match (player, enemy, seed, exit) {
+        (Some(_), ..) => String::from("Player"),
+        (_, Some(enemy), ..) => format!("{:?}", enemy.kind),
+        (_, _, Some(seed), _) => format!("{:?} seed", seed.kind),
+        (.., Some(exit)) => format!("Exit {}/{}", exit.delivered_seeds, exit.required_seeds),
+        _ => format!("{:?}", entity),
+    }
+}
+
+/// Draws the box and the name of every entity with a collider
+fn spawn_collider_overlays(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    debug_materials: Res<DebugMaterials>,
+    collider_query: Query<
+        (
+            Entity,
+            &Collider,
+            Option<&Player>,
+            Option<&Enemy>,
+            Option<&Seed>,
+            Option<&Exit>,
+        ),
+        Without<DebugDrawn>,
+    >,
+) {
+    let text_style = TextStyle {
+        font: game_assets.main_font.clone(),
+        font_size: NAME_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    for (entity, collider, player, enemy, seed, exit) in collider_query.iter() {
+        let size = collider.half_size * 2.;
+        let name = debug_name(entity, player, enemy, seed, exit);
+
+        let mut overlay = None;
+        commands.entity(entity).with_children(|parent| {
+            overlay = Some(
+                parent
+                    .spawn_bundle((
+                        Transform::from_translation(Vec3::new(0., 0., OVERLAY_Z)),
+                        GlobalTransform::default(),
+                        DebugOverlayEntity,
+                    ))
+                    .with_children(|parent| {
+                        spawn_outline(parent, Vec3::ZERO, size, debug_materials.collider.clone());
+
+                        parent.spawn_bundle(Text2dBundle {
+                            text: Text::with_section(
+                                name,
+                                text_style.clone(),
+                                TextAlignment {
+                                    vertical: VerticalAlign::Bottom,
+                                    horizontal: HorizontalAlign::Center,
+                                },
+                            ),
+                            transform: Transform::from_translation(Vec3::new(
+                                0.,
+                                collider.half_size.y + 2.,
+                                0.,
+                            )),
+                            ..Default::default()
+                        });
+                    })
+                    .id(),
+            );
+        });
+
+        if let Some(overlay) = overlay {
+            commands.entity(entity).insert(DebugDrawn(overlay));
+        }
+    }
+}
+
+fn update_camera_limits_overlay(
+    mut commands: Commands,
+    debug_materials: Res<DebugMaterials>,
+    camera_query: Query<&Free2DCamera>,
+    mut overlay_query: Query<(&mut DebugOutline, &mut Transform), With<CameraLimitsOverlay>>,
+) {
+    let limits = camera_query
+        .iter()
+        .find_map(|free_2d_camera| free_2d_camera.limits);
+
+    let (size, center) = if let Some(limits) = limits {
+        (
+            Vec2::new(limits.right - limits.left, limits.top - limits.bottom),
+            Vec2::new(limits.left + limits.right, limits.bottom + limits.top) / 2.,
+        )
+    } else {
+        // Hide the rectangle while the cameras are not limited
+        (Vec2::ZERO, Vec2::ZERO)
+    };
+
+    if let Some((mut outline, mut transform)) = overlay_query.iter_mut().next() {
+        if outline.size != size {
+            outline.size = size;
+        }
+        transform.translation = center.extend(OVERLAY_Z);
+        return;
+    }
+
+    commands
+        .spawn_bundle((
+            Transform::from_translation(center.extend(OVERLAY_Z)),
+            GlobalTransform::default(),
+            DebugOutline { size },
+            CameraLimitsOverlay,
+            DebugOverlayEntity,
+        ))
+        .with_children(|parent| spawn_lines(parent, debug_materials.camera_limits.clone()));
+}
+
+fn update_debug_panel(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    diagnostics: Res<Diagnostics>,
+    game_state: Res<State<GameState>>,
+    world_levels: Option<Res<WorldLevels>>,
+    current_level: Option<Res<CurrentLevel>>,
+    level_query: Query<(&LevelName, Option<&CollisionMap>)>,
+    mut panel_query: Query<&mut Text, With<DebugPanel>>,
+) {
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+        .unwrap_or_default();
+
+    let mut lines = vec![
+        format!("FPS: {:.0}", fps),
+        format!("State: {:?}", game_state.current()),
+        String::from("Levels:"),
+    ];
+
+    if let Some(world_levels) = world_levels {
+        let mut levels: Vec<String> = world_levels
+            .level_map
+            .iter()
+            .map(|(name, entity)| {
+                let loaded = match level_query.get(entity) {
+                    Ok((_, Some(_))) => "loaded",
+                    Ok((_, None)) => "loading",
+                    Err(_) => "missing",
+                };
+                let current = current_level
+                    .as_ref()
+                    .map_or(false, |current_level| current_level.0 == name);
+
+                format!("{} {} ({})", if current { "*" } else { "-" }, name, loaded)
+            })
+            .collect();
+        levels.sort();
+        lines.extend(levels);
+    }
+
+    let contents = lines.join("\n");
+
+    if let Some(mut text) = panel_query.iter_mut().next() {
+        text.sections[0].value = contents;
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                contents,
+                TextStyle {
+                    font: game_assets.main_font.clone(),
+                    font_size: 12.,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.),
+                    top: Val::Px(8.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(DebugPanel)
+        .insert(DebugOverlayEntity);
+}
@@ -0,0 +1,470 @@
+//! Validates `game.config` whenever it is (re)loaded and tells the game about it
+//!
+//! The layout of the config is defined by `assets/game.config` itself, see
+//! [`crate::config::Root`]. Systems reading it should listen for [`ConfigChanged`] and use
+//! [`changed_config`], so they only ever see a config that passed [`validate`].
+
+use bevy::{asset::AssetServerSettings, prelude::*, utils::HashMap};
+
+use crate::{actions::Binding, utils::asset_file_path, GameAssets};
+
+const CONFIG_FILE: &str = "game.config";
+
+#[derive(Debug, Default)]
+pub struct GameConfigPlugin;
+
+impl Plugin for GameConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConfigChanged>();
+        app.init_resource::<ConfigOverrides>();
+        app.add_system(validate_config.label(GameConfigSystems::Validate));
+    }
+}
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum GameConfigSystems {
+    /// Sends [`ConfigChanged`], systems reading it can run after this to apply changes the
+    /// same frame
+    Validate,
+}
+
+/// Sent when `game.config` was loaded or hot reloaded and is valid
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigChanged;
+
+/// A value of `game.config` that is out of range
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// The dotted path to the value, like `player.walk_speed`
+    pub key: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Values set through the `config set` console command, applied on top of `game.config` until the
+/// game is closed, also after it is hot reloaded
+#[derive(Debug, Default)]
+pub struct ConfigOverrides {
+    values: HashMap<String, toml::Value>,
+    /// The config asset already holds all values, its next change does not need patching
+    applied: bool,
+}
+
+impl ConfigOverrides {
+    /// Adds a value that the caller has already written into the config asset
+    pub fn insert_applied(&mut self, key: &str, value: toml::Value) {
+        self.values.insert(key.to_owned(), value);
+        self.applied = true;
+    }
+
+    /// `game.config` as it is on disk, with all overrides applied
+    pub fn apply_to_file(
+        &self,
+        asset_settings: Option<&AssetServerSettings>,
+    ) -> Result<toml::Value, String> {
+        let mut config = read_config_file(asset_settings)?;
+
+        for (key, value) in self.values.iter() {
+            set_config_value(&mut config, key, value.clone())?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Reads `game.config` from disk without going through the asset server
+pub fn read_config_file(
+    asset_settings: Option<&AssetServerSettings>,
+) -> Result<toml::Value, String> {
+    let path = asset_file_path(asset_settings, CONFIG_FILE);
+
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+
+    toml::from_str(&contents).map_err(|err| format!("could not parse {}: {}", CONFIG_FILE, err))
+}
+
+/// Replaces the value at `key`, which has to exist already
+pub fn set_config_value(
+    config: &mut toml::Value,
+    key: &str,
+    value: toml::Value,
+) -> Result<(), String> {
+    let entry = key
+        .split('.')
+        .try_fold(config, |value, part| value.get_mut(part))
+        .ok_or_else(|| format!("unknown key '{}'", key))?;
+
+    if entry.is_table() {
+        return Err(format!("'{}' is a section, not a value", key));
+    }
+
+    *entry = value;
+    Ok(())
+}
+
+/// The config, if it changed since the last call with these `events`
+pub fn changed_config<'a>(
+    events: &mut EventReader<ConfigChanged>,
+    config_assets: &'a Assets<crate::config::Root>,
+    game_assets: Option<&GameAssets>,
+) -> Option<&'a crate::config::Root> {
+    // Read all events, so that a single reload is applied once
+    if events.iter().count() == 0 {
+        return None;
+    }
+
+    config_assets.get(&game_assets?.config)
+}
+
+#[derive(Debug, Default)]
+struct Validator {
+    errors: Vec<ConfigError>,
+}
+
+impl Validator {
+    fn check(&mut self, key: &str, valid: bool, message: impl FnOnce() -> String) {
+        if !valid {
+            self.errors.push(ConfigError {
+                key: key.to_owned(),
+                message: message(),
+            });
+        }
+    }
+
+    fn positive(&mut self, key: &str, value: f64) {
+        self.check(key, value > 0., || {
+            format!("must be greater than 0, found {}", value)
+        });
+    }
+
+    fn non_negative(&mut self, key: &str, value: f64) {
+        self.check(key, value >= 0., || {
+            format!("must not be negative, found {}", value)
+        });
+    }
+
+    fn factor(&mut self, key: &str, value: f64) {
+        self.check(key, (0. ..=1.).contains(&value), || {
+            format!("must be between 0 and 1, found {}", value)
+        });
+    }
+
+    fn bindings(&mut self, key: &str, bindings: &[String]) {
+        for (index, binding) in bindings.iter().enumerate() {
+            if let Err(err) = Binding::parse(binding) {
+                self.check(&format!("{}[{}]", key, index), false, || err);
+            }
+        }
+    }
+}
+
+/// Checks all values of the config, returns every invalid one
+pub fn validate(config: &crate::config::Root) -> Result<(), Vec<ConfigError>> {
+    let mut v = Validator::default();
+
+    let input = &config.input;
+    v.bindings("input.camera_pan_left", &input.camera_pan_left);
+    v.bindings("input.camera_pan_right", &input.camera_pan_right);
+    v.bindings("input.camera_pan_up", &input.camera_pan_up);
+    v.bindings("input.camera_pan_down", &input.camera_pan_down);
+    v.bindings("input.camera_drag", &input.camera_drag);
+    v.bindings("input.zoom_in", &input.zoom_in);
+    v.bindings("input.zoom_out", &input.zoom_out);
+    v.bindings("input.toggle_free_camera", &input.toggle_free_camera);
+    v.bindings("input.toggle_debug_overlay", &input.toggle_debug_overlay);
+    v.bindings("input.move_left", &input.move_left);
+    v.bindings("input.move_right", &input.move_right);
+    v.bindings("input.jump", &input.jump);
+    v.bindings("input.interact", &input.interact);
+    v.bindings("input.menu_confirm", &input.menu_confirm);
+    v.bindings("input.menu_back", &input.menu_back);
+
+    v.positive("tick.rate", config.tick.rate as f64);
+    v.positive(
+        "tick.max_ticks_per_frame",
+        config.tick.max_ticks_per_frame as f64,
+    );
+
+    v.non_negative(
+        "accessibility.camera_shake",
+        config.accessibility.camera_shake as f64,
+    );
+
+    v.positive(
+        "physics.max_fall_speed",
+        config.physics.max_fall_speed as f64,
+    );
+
+    let camera = &config.camera;
+    v.positive("camera.scale_min", camera.scale_min as f64);
+    v.check(
+        "camera.scale_max",
+        camera.scale_max >= camera.scale_min,
+        || {
+            format!(
+                "must be at least camera.scale_min ({}), found {}",
+                camera.scale_min, camera.scale_max
+            )
+        },
+    );
+    v.non_negative("camera.pan_speed", camera.pan_speed as f64);
+    v.non_negative("camera.zoom_smooth_time", camera.zoom_smooth_time as f64);
+
+    let background = &config.parallax.background;
+    v.factor("parallax.background.factor_x", background.factor_x as f64);
+    v.factor("parallax.background.factor_y", background.factor_y as f64);
+
+    v.positive("ui.scale", config.ui.scale as f64);
+    v.non_negative("ui.transition_time", config.ui.transition_time as f64);
+
+    let audio = &config.audio;
+    v.factor("audio.master_volume", audio.master_volume as f64);
+    v.factor("audio.music_volume", audio.music_volume as f64);
+    v.factor("audio.sfx_volume", audio.sfx_volume as f64);
+    v.non_negative("audio.crossfade_time", audio.crossfade_time as f64);
+    v.positive("audio.hearing_distance", audio.hearing_distance as f64);
+
+    let enemies = &config.enemies;
+    v.non_negative("enemies.gravity", enemies.gravity as f64);
+    v.non_negative(
+        "enemies.mushroom.walk_speed",
+        enemies.mushroom.walk_speed as f64,
+    );
+    v.non_negative(
+        "enemies.mushroom.hop_interval",
+        enemies.mushroom.hop_interval as f64,
+    );
+    v.non_negative(
+        "enemies.mushroom.hop_speed",
+        enemies.mushroom.hop_speed as f64,
+    );
+    v.non_negative(
+        "enemies.mushroom.bounce_speed",
+        enemies.mushroom.bounce_speed as f64,
+    );
+    v.non_negative(
+        "enemies.tortoise.walk_speed",
+        enemies.tortoise.walk_speed as f64,
+    );
+    v.non_negative(
+        "enemies.tortoise.shell_speed",
+        enemies.tortoise.shell_speed as f64,
+    );
+    v.non_negative(
+        "enemies.tortoise.bounce_speed",
+        enemies.tortoise.bounce_speed as f64,
+    );
+    v.non_negative(
+        "enemies.tortoise.shell_time",
+        enemies.tortoise.shell_time as f64,
+    );
+
+    let player = &config.player;
+    v.positive("player.walk_speed", player.walk_speed as f64);
+    v.non_negative("player.jump_speed", player.jump_speed as f64);
+    v.non_negative("player.gravity", player.gravity as f64);
+    v.positive("player.max_health", player.max_health as f64);
+    v.non_negative("player.invulnerable_time", player.invulnerable_time as f64);
+    v.non_negative("player.death_time", player.death_time as f64);
+    v.non_negative("player.death_hop_speed", player.death_hop_speed as f64);
+
+    let seeds = &config.seeds;
+    v.non_negative("seeds.gravity", seeds.gravity as f64);
+    v.factor("seeds.heavy.speed_factor", seeds.heavy.speed_factor as f64);
+    v.factor("seeds.heavy.jump_factor", seeds.heavy.jump_factor as f64);
+    v.positive(
+        "seeds.fragile.break_speed",
+        seeds.fragile.break_speed as f64,
+    );
+
+    let levels = &config.progression.levels;
+    v.check("progression.levels", !levels.is_empty(), || {
+        String::from("needs at least one level")
+    });
+    for (index, level) in levels.iter().enumerate() {
+        v.check(
+            "progression.levels",
+            !levels[..index].contains(level),
+            || format!("{} is listed twice", level),
+        );
+    }
+    v.non_negative("progression.lives", config.progression.lives as f64);
+
+    if v.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(v.errors)
+    }
+}
+
+/// Validates the config when it is (re)loaded, an invalid config is not applied
+///
+/// A hot reload replaces the values set through the console, so they are applied again first and
+/// the patched config is validated once it arrives.
+fn validate_config(
+    mut config_events: EventReader<AssetEvent<crate::config::Root>>,
+    mut changed_events: EventWriter<ConfigChanged>,
+    mut config_assets: ResMut<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut overrides: ResMut<ConfigOverrides>,
+    asset_settings: Option<Res<AssetServerSettings>>,
+) {
+    let game_assets = if let Some(game_assets) = game_assets {
+        game_assets
+    } else {
+        return;
+    };
+
+    let changed = config_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == game_assets.config
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+
+    if !changed {
+        return;
+    }
+
+    if !overrides.values.is_empty() && !overrides.applied {
+        let patched_config =
+            overrides
+                .apply_to_file(asset_settings.as_deref())
+                .and_then(|config| {
+                    config
+                        .try_into::<crate::config::Root>()
+                        .map_err(|err| err.to_string())
+                });
+
+        match patched_config {
+            Ok(config) => {
+                config_assets.set(game_assets.config.clone(), config);
+                overrides.applied = true;
+                return;
+            }
+            Err(err) => error!(
+                "Could not apply the console overrides to game.config, {}",
+                err
+            ),
+        }
+    }
+    overrides.applied = false;
+
+    let config = if let Some(config) = config_assets.get(&game_assets.config) {
+        config
+    } else {
+        return;
+    };
+
+    match validate(config) {
+        Ok(()) => {
+            info!("Applied game.config");
+            changed_events.send(ConfigChanged);
+        }
+        Err(errors) => {
+            for err in errors {
+                error!("Invalid value in game.config, {}", err);
+            }
+            warn!("Kept the previous game.config values, fix the errors above to apply it");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_config_value, validate};
+
+    fn load_config(edit: impl FnOnce(&mut toml::Value)) -> crate::config::Root {
+        let mut config: toml::Value =
+            toml::from_str(include_str!("../assets/game.config")).unwrap();
+        edit(&mut config);
+        config.try_into().unwrap()
+    }
+
+    fn set(config: &mut toml::Value, section: &str, key: &str, value: toml::Value) {
+        config[section].as_table_mut().unwrap()[key] = value;
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        assert_eq!(validate(&load_config(|_| ())), Ok(()));
+    }
+
+    #[test]
+    fn parallax_factors_are_checked() {
+        let config = load_config(|config| {
+            config["parallax"]["background"].as_table_mut().unwrap()["factor_y"] =
+                toml::Value::Float(1.5);
+        });
+
+        let keys: Vec<_> = validate(&config)
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.key)
+            .collect();
+
+        assert_eq!(keys, ["parallax.background.factor_y"]);
+    }
+
+    #[test]
+    fn overrides_replace_existing_values_only() {
+        let mut config: toml::Value =
+            toml::from_str(include_str!("../assets/game.config")).unwrap();
+
+        set_config_value(&mut config, "player.jump_speed", toml::Value::Float(1.)).unwrap();
+        assert_eq!(config["player"]["jump_speed"], toml::Value::Float(1.));
+
+        assert!(set_config_value(&mut config, "player.fly_speed", toml::Value::Float(1.)).is_err());
+        assert!(set_config_value(&mut config, "player", toml::Value::Float(1.)).is_err());
+    }
+
+    #[test]
+    fn errors_name_the_invalid_keys() {
+        let config = load_config(|config| {
+            set(config, "tick", "rate", toml::Value::Integer(0));
+            set(config, "camera", "scale_max", toml::Value::Float(0.5));
+            set(
+                config,
+                "input",
+                "jump",
+                toml::Value::Array(vec![
+                    toml::Value::String(String::from("Key:Space")),
+                    toml::Value::String(String::from("Space")),
+                ]),
+            );
+            set(
+                config,
+                "progression",
+                "levels",
+                toml::Value::Array(vec![
+                    toml::Value::String(String::from("Level_0")),
+                    toml::Value::String(String::from("Level_0")),
+                ]),
+            );
+        });
+
+        let keys: Vec<_> = validate(&config)
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.key)
+            .collect();
+
+        assert_eq!(
+            keys,
+            [
+                "input.jump[1]",
+                "tick.rate",
+                "camera.scale_max",
+                "progression.levels"
+            ]
+        );
+    }
+}
@@ -1,6 +1,11 @@
 #![allow(clippy::type_complexity)]
 
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{
+    app::{PluginGroupBuilder, ScheduleRunnerSettings},
+    prelude::*,
+};
 use bevy_simple_tilemap::plugin::SimpleTileMapPlugin;
 use bevy_spicy_aseprite::AsepriteImage;
 
@@ -8,32 +13,130 @@ use bevy_spicy_aseprite::AsepriteImage;
 bevy_spicy_aseprite::aseprite!(pub world_sprites, "assets/world.aseprite");
 // Import the world aseprite as used in the world ldtk
 bevy_spicy_aseprite::aseprite!(pub ui_sprites, "assets/ui.aseprite");
+// Import the sprites of the player and enemies
+bevy_spicy_aseprite::aseprite!(pub entity_sprites, "assets/entities.aseprite");
 // The world data
 bevy_spicy_ldtk::ldtk!(pub levels, "assets/world.ldtk");
 // Configuration for the game
 bevy_spicy_data::data_config!(pub config, "assets/game.config");
 
+mod actions;
+mod audio;
 mod camera;
+mod cli;
+mod console;
+mod debug;
+mod game_config;
+mod particles;
+mod progression;
+mod replay;
 mod stages;
 mod startup;
+#[cfg(test)]
+mod testing;
+mod tick;
 mod ui;
 mod utils;
 mod world;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(SimpleTileMapPlugin)
-        .add_plugin(bevy_spicy_data::TomlConfigPlugin::<config::Root>::default())
-        .add_plugin(bevy_spicy_aseprite::AsepritePlugin)
-        .add_plugin(bevy_spicy_ldtk::LdtkPlugin::<levels::Project>::default())
-        .add_plugin(utils::UtilsPlugin::default())
-        .add_plugin(ui::UiPlugin::default())
-        .add_plugin(camera::CameraPlugin::default())
-        .add_plugin(stages::StagesPlugin::default())
-        .add_plugin(startup::StartupPlugin::default())
-        .add_plugin(world::WorldPlugin::default())
-        .run();
+    let options = match cli::LaunchOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let mut app = App::new();
+
+    if let Some(size) = options.window_size {
+        app.insert_resource(WindowDescriptor {
+            width: size.x,
+            height: size.y,
+            ..Default::default()
+        });
+    }
+
+    if let Some(seed) = options.seed {
+        app.insert_resource(replay::GameSeed(seed));
+    }
+
+    if let Some(level) = &options.level {
+        app.insert_resource(world::DefaultLevels(vec![level.clone()]));
+    }
+
+    if let Some(path) = &options.record {
+        app.insert_resource(replay::ReplayMode::Record(path.clone()));
+    }
+
+    if let Some(path) = options.replay.as_ref().or(options.ghost.as_ref()) {
+        match replay::Replay::load(path) {
+            Ok(replay) => {
+                app.insert_resource(world::DefaultLevels(vec![replay.level.clone()]));
+                if options.ghost.is_some() {
+                    app.insert_resource(replay::ReplayMode::Ghost(replay));
+                } else if options.verify || options.headless {
+                    // Playing a replay without a window would never end
+                    app.insert_resource(replay::ReplayMode::Verify(replay))
+                        .add_system_to_stage(CoreStage::Last, replay::exit_on_mismatch);
+                } else {
+                    app.insert_resource(replay::ReplayMode::Play(replay));
+                }
+            }
+            Err(err) => {
+                eprintln!("Could not load the replay {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if options.headless {
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+            1. / 60.,
+        )))
+        .add_plugins(cli::HeadlessPlugins);
+    } else {
+        // The `AudioPlugin` of the game replaces the one of bevy
+        app.add_plugins_with(DefaultPlugins, |group| {
+            group.disable::<bevy::audio::AudioPlugin>()
+        });
+    }
+
+    app.insert_resource(options).add_plugins(GamePlugins).run();
+}
+
+/// Everything the game adds on top of the bevy plugins
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(SimpleTileMapPlugin)
+            .add(bevy_spicy_data::TomlConfigPlugin::<config::Root>::default())
+            .add(game_config::GameConfigPlugin::default())
+            .add(bevy_spicy_aseprite::AsepritePlugin)
+            .add(bevy_spicy_ldtk::LdtkPlugin::<levels::Project>::default())
+            .add(utils::UtilsPlugin::default())
+            .add(tick::FixedTickPlugin::default())
+            .add(actions::ActionsPlugin::default())
+            .add(console::ConsolePlugin::default())
+            .add(ui::UiPlugin::default())
+            .add(audio::AudioPlugin::default())
+            .add(particles::ParticlesPlugin::default())
+            .add(camera::CameraPlugin::default())
+            .add(debug::DebugPlugin::default())
+            .add(stages::StagesPlugin::default())
+            .add(startup::StartupPlugin::default())
+            .add(world::WorldPlugin::default())
+            .add(progression::ProgressionPlugin::default())
+            .add(replay::ReplayPlugin::default());
+    }
 }
 
 pub struct GameAssets {
@@ -42,6 +145,7 @@ pub struct GameAssets {
     pub world_sprites: Handle<AsepriteImage>,
     pub world_tile_atlas: Handle<TextureAtlas>,
     pub ui_sprites: Handle<AsepriteImage>,
+    pub entity_sprites: Handle<AsepriteImage>,
     pub main_font: Handle<Font>,
 }
 impl GameAssets {
@@ -51,6 +155,7 @@ impl GameAssets {
         loading.add(&self.world_sprites);
         loading.add(&self.world_tile_atlas);
         loading.add(&self.ui_sprites);
+        loading.add(&self.entity_sprites);
         loading.add(&self.main_font);
     }
 }
@@ -0,0 +1,211 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+/// How an emitter spawns its particles, loaded from a RON `.emitter` file
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "fd3de4dc-9023-4ad4-9868-2b73056bf5cf"]
+pub struct ParticleEffect {
+    /// Tag in `entities.aseprite`, its frames play once over the life of a particle
+    pub tag: String,
+    #[serde(default)]
+    pub shape: EmitterShape,
+    /// Particles spawned as soon as the emitter starts
+    #[serde(default)]
+    pub burst: u32,
+    /// Particles spawned per second after the burst
+    #[serde(default)]
+    pub rate: f32,
+    /// Seconds the emitter spawns particles, `None` to keep spawning until it is despawned
+    #[serde(default)]
+    pub duration: Option<f32>,
+    /// Seconds each particle lives, picked between the two
+    pub lifetime: (f32, f32),
+    /// Starting velocity in pixels per second, each axis is picked between the two
+    pub velocity: ([f32; 2], [f32; 2]),
+    /// Downwards acceleration in pixels per second squared
+    #[serde(default)]
+    pub gravity: f32,
+    /// The colors a particle goes through over its life, evenly spaced
+    pub colors: Vec<[f32; 4]>,
+    /// The size of the pool, no more particles than this are alive at once in an `Emitter`
+    ///
+    /// The pool of the single bursts grows when they overlap.
+    pub max_particles: usize,
+    #[serde(default = "default_z")]
+    pub z: f32,
+}
+
+fn default_z() -> f32 {
+    6.
+}
+
+/// Where around the emitter particles appear
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum EmitterShape {
+    Point,
+    Circle { radius: f32 },
+    Rect { width: f32, height: f32 },
+}
+
+impl Default for EmitterShape {
+    fn default() -> Self {
+        EmitterShape::Point
+    }
+}
+
+impl EmitterShape {
+    /// An offset from the emitter, `rng` is advanced for every random number used
+    pub fn sample(&self, rng: &mut ParticleRng) -> Vec2 {
+        match *self {
+            EmitterShape::Point => Vec2::ZERO,
+            EmitterShape::Circle { radius } => {
+                let angle = rng.next_f32() * std::f32::consts::TAU;
+                // The square root keeps the particles evenly spread over the area
+                let distance = rng.next_f32().sqrt() * radius;
+                Vec2::new(angle.cos(), angle.sin()) * distance
+            }
+            EmitterShape::Rect { width, height } => Vec2::new(
+                (rng.next_f32() - 0.5) * width,
+                (rng.next_f32() - 0.5) * height,
+            ),
+        }
+    }
+}
+
+impl ParticleEffect {
+    /// Whether an emitter started `elapsed` seconds ago still spawns particles
+    pub fn is_spawning(&self, elapsed: f32) -> bool {
+        self.duration.map_or(true, |duration| elapsed < duration)
+    }
+
+    /// The color at `t`, from `0.` (just spawned) to `1.` (about to die)
+    pub fn color_at(&self, t: f32) -> Color {
+        let to_color = |[r, g, b, a]: [f32; 4]| Color::rgba(r, g, b, a);
+
+        match self.colors.as_slice() {
+            [] => Color::WHITE,
+            [color] => to_color(*color),
+            colors => {
+                let position = t.clamp(0., 1.) * (colors.len() - 1) as f32;
+                let index = (position.floor() as usize).min(colors.len() - 2);
+                let blend = position - index as f32;
+
+                let from = Vec4::from(colors[index]);
+                let to = Vec4::from(colors[index + 1]);
+                to_color((from + (to - from) * blend).into())
+            }
+        }
+    }
+}
+
+/// Cheap random numbers for the look of the particles
+///
+/// Emitters seed it from the [`GameSeed`](crate::replay::GameSeed), so the particles look the same in
+/// every replay.
+#[derive(Debug, Clone)]
+pub struct ParticleRng(u32);
+
+impl ParticleRng {
+    pub fn new(seed: u32) -> Self {
+        // Xorshift gets stuck on zero
+        ParticleRng(seed.max(1))
+    }
+
+    /// A number between `0.` and `1.`
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ParticleEffectLoader;
+
+impl AssetLoader for ParticleEffectLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let effect: ParticleEffect = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(effect));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["emitter"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::{EmitterShape, ParticleEffect, ParticleRng};
+
+    fn effect(colors: Vec<[f32; 4]>) -> ParticleEffect {
+        ParticleEffect {
+            tag: String::from("dust"),
+            shape: EmitterShape::Point,
+            burst: 1,
+            rate: 0.,
+            duration: None,
+            lifetime: (1., 1.),
+            velocity: ([0., 0.], [0., 0.]),
+            gravity: 0.,
+            colors,
+            max_particles: 1,
+            z: 0.,
+        }
+    }
+
+    #[test]
+    fn colors_blend_over_life() {
+        let effect = effect(vec![[1., 1., 1., 1.], [1., 0., 0., 1.], [1., 0., 0., 0.]]);
+
+        assert_eq!(effect.color_at(0.), Color::rgba(1., 1., 1., 1.));
+        assert_eq!(effect.color_at(0.25), Color::rgba(1., 0.5, 0.5, 1.));
+        assert_eq!(effect.color_at(0.75), Color::rgba(1., 0., 0., 0.5));
+        assert_eq!(effect.color_at(1.), Color::rgba(1., 0., 0., 0.));
+    }
+
+    #[test]
+    fn shapes_stay_inside_their_bounds() {
+        let mut rng = ParticleRng::new(7);
+        let circle = EmitterShape::Circle { radius: 4. };
+        let rect = EmitterShape::Rect {
+            width: 10.,
+            height: 2.,
+        };
+
+        for _ in 0..100 {
+            assert!(circle.sample(&mut rng).length() <= 4.);
+
+            let offset = rect.sample(&mut rng);
+            assert!(offset.x.abs() <= 5. && offset.y.abs() <= 1.);
+        }
+    }
+
+    #[test]
+    fn effects_load_from_ron() {
+        let effect: ParticleEffect =
+            ron::de::from_str(include_str!("../../assets/particles/dust.emitter")).unwrap();
+
+        assert_eq!(effect.tag, "dust");
+        assert!(effect.max_particles > 0);
+    }
+}
@@ -0,0 +1,530 @@
+//! Pooled CPU particles for small pixel art effects
+//!
+//! A [`ParticleEmitter`] plays a [`ParticleEffect`] loaded from a RON `.emitter` file in
+//! `assets/particles`. Emitters are placed in LDtk (the `Emitter` entity) or play single bursts
+//! sent through a [`ParticleEvent`]. Each emitter spawns a fixed pool of sprites next to it
+//! once, so particles coming and going never spawn or despawn entities. All bursts of an effect
+//! in a level share one emitter and its pool, which stay until the level is despawned. That pool
+//! grows when bursts overlap, up to the most particles the bursts had alive at once.
+
+use bevy::{asset::LoadState, prelude::*, utils::HashMap};
+use bevy_spicy_aseprite::AsepriteImage;
+
+use crate::{
+    replay::GameSeed,
+    utils::{GetSubHandle, SubHandles},
+    GameAssets,
+};
+
+pub use self::effect::{EmitterShape, ParticleEffect, ParticleEffectLoader, ParticleRng};
+
+mod effect;
+
+/// How many looks a particle goes through over its life at least, each is a material shared by
+/// the whole pool
+const MATERIAL_STEPS: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct ParticlesPlugin;
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum ParticleSystems {
+    Spawn,
+    Reload,
+    Pool,
+    Emit,
+}
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ParticleEffect>()
+            .init_asset_loader::<ParticleEffectLoader>()
+            .add_event::<ParticleEvent>()
+            .init_resource::<OneShotEmitters>()
+            .add_system(spawn_particle_events.label(ParticleSystems::Spawn))
+            .add_system(
+                reload_emitters
+                    .label(ParticleSystems::Reload)
+                    .after(ParticleSystems::Spawn),
+            )
+            .add_system(
+                fill_pools
+                    .label(ParticleSystems::Pool)
+                    .after(ParticleSystems::Reload),
+            )
+            .add_system(
+                emit_particles
+                    .label(ParticleSystems::Emit)
+                    .after(ParticleSystems::Pool),
+            )
+            .add_system(update_particles.after(ParticleSystems::Emit));
+    }
+}
+
+/// Plays a [`ParticleEffect`] once, with the pool shared by every burst of the effect in `parent`
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleEvent {
+    /// The `.emitter` file, relative to `assets/`
+    pub effect: &'static str,
+    /// The entity `position` is relative to, usually the level
+    pub parent: Entity,
+    pub position: Vec2,
+}
+
+impl ParticleEvent {
+    pub fn in_level(effect: &'static str, level: Entity, position: Vec2) -> Self {
+        ParticleEvent {
+            effect,
+            parent: level,
+            position,
+        }
+    }
+}
+
+/// Spawns the particles of its effect around its position
+///
+/// The particles are siblings of the emitter, so they stay where they were spawned when the
+/// emitter moves.
+#[derive(Debug)]
+pub struct ParticleEmitter {
+    pub effect: Handle<ParticleEffect>,
+    /// Only plays the bursts of [`ParticleEvent`]s, each at its own position
+    pub one_shot: bool,
+    elapsed: f32,
+    /// Particles owed by the spawn rate, carried over between frames
+    pending: f32,
+    bursts: Vec<Burst>,
+    pool: Vec<Entity>,
+    /// The color and frame of each step of the life of a particle
+    materials: Vec<Handle<ColorMaterial>>,
+    /// Seeded from the [`GameSeed`] and the position once the pool is filled
+    rng: ParticleRng,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: Handle<ParticleEffect>) -> Self {
+        ParticleEmitter {
+            effect,
+            one_shot: false,
+            elapsed: 0.,
+            pending: 0.,
+            bursts: Vec::new(),
+            pool: Vec::new(),
+            materials: Vec::new(),
+            rng: ParticleRng::new(1),
+        }
+    }
+
+    pub fn one_shot(mut self) -> Self {
+        self.one_shot = true;
+        self
+    }
+
+    /// Starts the effect over, the pool is filled again the next frame
+    fn restart(&mut self, commands: &mut Commands) {
+        for particle in self.pool.drain(..) {
+            commands.entity(particle).despawn_recursive();
+        }
+        self.materials.clear();
+        self.elapsed = 0.;
+        self.pending = 0.;
+    }
+}
+
+/// A [`ParticleEvent`] played by a one shot [`ParticleEmitter`]
+#[derive(Debug)]
+struct Burst {
+    /// In the coordinates of the parent of the emitter
+    origin: Vec2,
+    elapsed: f32,
+    pending: f32,
+    /// Particles that found no free sprite yet, spawned once the pool grew
+    owed: u32,
+}
+
+/// The one shot emitter of each effect and parent, spawned by the first [`ParticleEvent`]
+#[derive(Debug, Default)]
+struct OneShotEmitters(HashMap<(&'static str, Entity), Entity>);
+
+/// One sprite of the pool of a [`ParticleEmitter`]
+#[derive(Debug, Default)]
+pub struct Particle {
+    /// In the coordinates of the parent of the emitter, the sprite is snapped to whole pixels
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    alive: bool,
+}
+
+fn spawn_particle_events(
+    mut commands: Commands,
+    mut particle_events: EventReader<ParticleEvent>,
+    asset_server: Res<AssetServer>,
+    mut one_shot_emitters: ResMut<OneShotEmitters>,
+    mut emitter_query: Query<&mut ParticleEmitter>,
+) {
+    // The emitters are despawned together with their level
+    one_shot_emitters
+        .0
+        .retain(|_, emitter| emitter_query.get_mut(*emitter).is_ok());
+
+    let mut new_emitters: Vec<((&'static str, Entity), ParticleEmitter)> = Vec::new();
+
+    for event in particle_events.iter() {
+        let key = (event.effect, event.parent);
+        let burst = Burst {
+            origin: event.position,
+            elapsed: 0.,
+            pending: 0.,
+            owed: 0,
+        };
+
+        if let Some(mut emitter) = one_shot_emitters
+            .0
+            .get(&key)
+            .and_then(|emitter| emitter_query.get_mut(*emitter).ok())
+        {
+            emitter.bursts.push(burst);
+        } else if let Some((_, emitter)) = new_emitters.iter_mut().find(|(other, _)| *other == key)
+        {
+            emitter.bursts.push(burst);
+        } else {
+            let mut emitter = ParticleEmitter::new(asset_server.load(event.effect)).one_shot();
+            emitter.bursts.push(burst);
+            new_emitters.push((key, emitter));
+        }
+    }
+
+    for ((effect, parent), emitter) in new_emitters {
+        // At the origin of the parent, so the bursts are in its coordinates
+        let entity = commands
+            .spawn()
+            .insert(emitter)
+            .insert(Transform::default())
+            .insert(GlobalTransform::default())
+            .id();
+        commands.entity(parent).push_children(&[entity]);
+        one_shot_emitters.0.insert((effect, parent), entity);
+    }
+}
+
+/// Restarts the emitters of an effect when its file changed
+fn reload_emitters(
+    mut commands: Commands,
+    mut effect_events: EventReader<AssetEvent<ParticleEffect>>,
+    mut emitter_query: Query<&mut ParticleEmitter>,
+) {
+    for event in effect_events.iter() {
+        let handle = if let AssetEvent::Modified { handle } = event {
+            handle
+        } else {
+            continue;
+        };
+
+        for mut emitter in emitter_query.iter_mut() {
+            if emitter.effect == *handle {
+                emitter.restart(&mut commands);
+            }
+        }
+    }
+}
+
+/// Spawns the sprites of an emitter once its effect and frames are loaded
+fn fill_pools(
+    mut commands: Commands,
+    effects: Res<Assets<ParticleEffect>>,
+    aseprite_assets: Res<Assets<AsepriteImage>>,
+    game_assets: Option<Res<GameAssets>>,
+    game_seed: Res<GameSeed>,
+    mut sub_handles: ResMut<SubHandles>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut emitter_query: Query<(&mut ParticleEmitter, &Transform, Option<&Parent>)>,
+    particle_query: Query<&Particle>,
+) {
+    let game_assets = if let Some(game_assets) = game_assets {
+        game_assets
+    } else {
+        return;
+    };
+
+    for (mut emitter, transform, parent) in emitter_query.iter_mut() {
+        if !emitter.pool.is_empty() {
+            if let (true, Some(effect)) = (emitter.one_shot, effects.get(&emitter.effect)) {
+                grow_one_shot_pool(&mut commands, &mut emitter, effect, parent, &particle_query);
+            }
+            continue;
+        }
+
+        let (effect, aseprite) = match (
+            effects.get(&emitter.effect),
+            aseprite_assets.get(&game_assets.entity_sprites),
+        ) {
+            (Some(effect), Some(aseprite)) => (effect, aseprite),
+            _ => continue,
+        };
+
+        let mut frames = Vec::new();
+        match aseprite.aseprite().tags().get_by_name(&effect.tag) {
+            Some(tag) => {
+                for frame in tag.frames.clone() {
+                    match game_assets
+                        .entity_sprites
+                        .get_sub_handle(&format!("Frame{}", frame), &mut sub_handles)
+                    {
+                        Ok(texture) => frames.push(texture),
+                        Err(err) => error!("Could not get a particle frame: {}", err),
+                    }
+                }
+            }
+            // Without frames the particles are drawn as single pixels
+            None => warn!("There is no tag {} in entities.aseprite", effect.tag),
+        }
+
+        // Emitters of the same effect still look different, but the same in every replay
+        let position = transform.translation.truncate().round().as_i32();
+        let salt: Vec<u8> = [position.x, position.y]
+            .iter()
+            .flat_map(|coordinate| coordinate.to_le_bytes())
+            .collect();
+        emitter.rng = ParticleRng::new(game_seed.derive(&salt));
+
+        // Every frame of the tag gets at least one step
+        let steps = MATERIAL_STEPS.max(frames.len());
+        emitter.materials = (0..steps)
+            .map(|step| {
+                let frame = step * frames.len() / steps;
+                materials.add(ColorMaterial {
+                    color: effect.color_at(step as f32 / (steps - 1) as f32),
+                    texture: frames.get(frame).cloned(),
+                })
+            })
+            .collect();
+
+        emitter.pool = spawn_pool(
+            &mut commands,
+            effect.max_particles.max(1),
+            &emitter.materials[0],
+            parent,
+        );
+    }
+}
+
+/// Spawns hidden particle sprites next to the emitter
+fn spawn_pool(
+    commands: &mut Commands,
+    size: usize,
+    material: &Handle<ColorMaterial>,
+    parent: Option<&Parent>,
+) -> Vec<Entity> {
+    let pool: Vec<Entity> = (0..size)
+        .map(|_| {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: material.clone(),
+                    // Resized to the frame, if there is one
+                    sprite: Sprite::new(Vec2::ONE),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .insert(Particle::default())
+                .id()
+        })
+        .collect();
+
+    if let Some(parent) = parent {
+        commands.entity(parent.0).push_children(&pool);
+    }
+
+    pool
+}
+
+/// Adds sprites for the particles the queued bursts need on top of the ones still alive
+fn grow_one_shot_pool(
+    commands: &mut Commands,
+    emitter: &mut ParticleEmitter,
+    effect: &ParticleEffect,
+    parent: Option<&Parent>,
+    particle_query: &Query<&Particle>,
+) {
+    let alive = emitter
+        .pool
+        .iter()
+        .filter(|particle| {
+            particle_query
+                .get(**particle)
+                .map_or(false, |particle| particle.alive)
+        })
+        .count();
+    let needed: u32 = emitter
+        .bursts
+        .iter()
+        .map(|burst| burst.owed + if burst.elapsed == 0. { effect.burst } else { 0 })
+        .sum();
+
+    let missing = (alive + needed as usize).saturating_sub(emitter.pool.len());
+    if missing > 0 {
+        let grown = spawn_pool(commands, missing, &emitter.materials[0], parent);
+        emitter.pool.extend(grown);
+    }
+}
+
+/// Advances the clock of an emitter or a burst, returns how many particles it spawns this frame
+fn particles_due(effect: &ParticleEffect, elapsed: &mut f32, pending: &mut f32, delta: f32) -> u32 {
+    let mut due = 0;
+    if *elapsed == 0. {
+        due += effect.burst;
+    }
+    if effect.is_spawning(*elapsed) {
+        *pending += effect.rate * delta;
+        due += pending.floor() as u32;
+        *pending = pending.fract();
+    }
+    *elapsed += delta;
+
+    due
+}
+
+fn emit_particles(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    effects: Res<Assets<ParticleEffect>>,
+    mut emitter_query: Query<(&mut ParticleEmitter, &Transform)>,
+    mut particle_query: Query<&mut Particle>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut emitter, transform) in emitter_query.iter_mut() {
+        let effect = match effects.get(&emitter.effect) {
+            Some(effect) if !emitter.pool.is_empty() => effect,
+            _ => {
+                // Bursts would pile up waiting for an effect that can not be loaded
+                if emitter.one_shot
+                    && asset_server.get_load_state(&emitter.effect) == LoadState::Failed
+                {
+                    emitter.bursts.clear();
+                }
+                continue;
+            }
+        };
+
+        let emitter = &mut *emitter;
+        // The burst each particle belongs to, if any, and where it starts
+        let mut origins = Vec::new();
+
+        if emitter.one_shot {
+            for (index, burst) in emitter.bursts.iter_mut().enumerate() {
+                let due = particles_due(effect, &mut burst.elapsed, &mut burst.pending, delta);
+                let owed = std::mem::take(&mut burst.owed) + due;
+                origins.extend(std::iter::repeat((Some(index), burst.origin)).take(owed as usize));
+            }
+        } else {
+            let due = particles_due(effect, &mut emitter.elapsed, &mut emitter.pending, delta);
+            origins.extend(
+                std::iter::repeat((None, transform.translation.truncate())).take(due as usize),
+            );
+        }
+
+        let mut origins = origins.into_iter();
+
+        for particle_entity in emitter.pool.iter() {
+            let mut particle = if let Ok(particle) = particle_query.get_mut(*particle_entity) {
+                particle
+            } else {
+                continue;
+            };
+
+            if particle.alive {
+                continue;
+            }
+
+            let origin = if let Some((_, origin)) = origins.next() {
+                origin
+            } else {
+                break;
+            };
+
+            let (velocity_min, velocity_max) = effect.velocity;
+            *particle = Particle {
+                position: origin + effect.shape.sample(&mut emitter.rng),
+                velocity: Vec2::new(
+                    emitter.rng.range(velocity_min[0], velocity_max[0]),
+                    emitter.rng.range(velocity_min[1], velocity_max[1]),
+                ),
+                age: 0.,
+                lifetime: emitter.rng.range(effect.lifetime.0, effect.lifetime.1),
+                alive: true,
+            };
+        }
+
+        // Bursts keep what they still owe until their pool grew, an emitter of a fixed pool drops
+        // the particles owed while all of its particles are alive
+        for (burst, _) in origins {
+            if let Some(burst) = burst.and_then(|index| emitter.bursts.get_mut(index)) {
+                burst.owed += 1;
+            }
+        }
+        emitter
+            .bursts
+            .retain(|burst| effect.is_spawning(burst.elapsed) || burst.owed > 0);
+    }
+}
+
+fn update_particles(
+    time: Res<Time>,
+    effects: Res<Assets<ParticleEffect>>,
+    emitter_query: Query<&ParticleEmitter>,
+    mut particle_query: Query<(
+        &mut Particle,
+        &mut Transform,
+        &mut Visible,
+        &mut Handle<ColorMaterial>,
+    )>,
+) {
+    let delta = time.delta_seconds();
+
+    for emitter in emitter_query.iter() {
+        let effect = if let Some(effect) = effects.get(&emitter.effect) {
+            effect
+        } else {
+            continue;
+        };
+
+        for particle_entity in emitter.pool.iter() {
+            let (mut particle, mut transform, mut visible, mut material) =
+                if let Ok(particle) = particle_query.get_mut(*particle_entity) {
+                    particle
+                } else {
+                    continue;
+                };
+
+            if !particle.alive {
+                visible.is_visible = false;
+                continue;
+            }
+
+            particle.age += delta;
+            if particle.age >= particle.lifetime {
+                particle.alive = false;
+                visible.is_visible = false;
+                continue;
+            }
+
+            particle.velocity.y -= effect.gravity * delta;
+            let velocity = particle.velocity;
+            particle.position += velocity * delta;
+
+            transform.translation = particle.position.round().extend(effect.z);
+            visible.is_visible = true;
+
+            let steps = emitter.materials.len();
+            let step = ((particle.age / particle.lifetime * steps as f32) as usize).min(steps - 1);
+            if *material != emitter.materials[step] {
+                *material = emitter.materials[step].clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_config::{changed_config, ConfigChanged},
+    stages::{GameState, TransitionEffect, TransitionEvent},
+    world::{LevelCompleteEvent, LevelStats, ResetLevelEvent},
+    GameAssets,
+};
+
+/// Where the unlocked levels and best times are saved by default, relative to the working directory
+pub const SAVE_FILE: &str = "save.ron";
+
+/// Where the [`Progression`] is read from and saved to, `None` keeps it in memory only
+///
+/// Has to be inserted before the [`ProgressionPlugin`] is added to take effect.
+#[derive(Debug, Clone)]
+pub struct SavePath(pub Option<PathBuf>);
+
+impl Default for SavePath {
+    fn default() -> Self {
+        SavePath(Some(PathBuf::from(SAVE_FILE)))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        let save_path = app
+            .world
+            .get_resource_or_insert_with(SavePath::default)
+            .clone();
+        app.insert_resource(
+            save_path
+                .0
+                .map_or_else(Progression::default, Progression::load),
+        );
+        app.add_system(load_progression_from_config);
+        app.add_system_set(
+            SystemSet::on_update(GameState::Running)
+                .with_system(complete_levels)
+                .with_system(check_game_over),
+        );
+    }
+}
+
+/// What is written to the [`SavePath`]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SaveData {
+    unlocked_levels: Vec<String>,
+    /// Fastest completion of each level in seconds
+    #[serde(default)]
+    best_times: std::collections::BTreeMap<String, f32>,
+}
+
+/// The order the levels are played in and which of them can be played already
+///
+/// The order is read from the `[progression]` section of `game.config`, the first level is always
+/// unlocked and completing a level unlocks the next one.
+#[derive(Debug, Default)]
+pub struct Progression {
+    levels: Vec<String>,
+    unlocked: HashSet<String>,
+    best_times: HashMap<String, f32>,
+    /// How often the player can die in a level before the game is over, `0` for no limit
+    pub lives: u32,
+}
+
+impl Progression {
+    /// Reads the unlocked levels from a save file, a missing file means nothing is unlocked yet
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        let save_data = match std::fs::read_to_string(path) {
+            Ok(contents) => match ron::de::from_str::<SaveData>(&contents) {
+                Ok(save_data) => save_data,
+                Err(err) => {
+                    error!("Could not read the save file {}: {}", path.display(), err);
+                    SaveData::default()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => SaveData::default(),
+            Err(err) => {
+                error!("Could not open the save file {}: {}", path.display(), err);
+                SaveData::default()
+            }
+        };
+
+        Progression {
+            unlocked: save_data.unlocked_levels.into_iter().collect(),
+            best_times: save_data.best_times.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        let mut unlocked_levels: Vec<_> = self.unlocked.iter().cloned().collect();
+        unlocked_levels.sort();
+
+        let save_data = SaveData {
+            unlocked_levels,
+            best_times: self
+                .best_times
+                .iter()
+                .map(|(level, time)| (level.clone(), *time))
+                .collect(),
+        };
+
+        let contents =
+            match ron::ser::to_string_pretty(&save_data, ron::ser::PrettyConfig::default()) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    error!("Could not serialize the save data: {}", err);
+                    return;
+                }
+            };
+
+        if let Err(err) = std::fs::write(path, contents) {
+            error!("Could not write the save file {}: {}", path.display(), err);
+        }
+    }
+
+    pub fn levels(&self) -> &[String] {
+        &self.levels
+    }
+
+    pub fn set_levels(&mut self, levels: Vec<String>) {
+        if let Some(first_level) = levels.first() {
+            self.unlocked.insert(first_level.clone());
+        }
+
+        self.levels = levels;
+    }
+
+    pub fn first_level(&self) -> Option<&str> {
+        self.levels.first().map(String::as_str)
+    }
+
+    pub fn next_level(&self, level: &str) -> Option<&str> {
+        let index = self.levels.iter().position(|other| other == level)?;
+
+        self.levels.get(index + 1).map(String::as_str)
+    }
+
+    pub fn is_unlocked(&self, level: &str) -> bool {
+        self.unlocked.contains(level)
+    }
+
+    /// Levels that are not part of the progression can always be played
+    pub fn is_playable(&self, level: &str) -> bool {
+        self.is_unlocked(level) || !self.levels.iter().any(|other| other == level)
+    }
+
+    /// Returns whether the level was locked before
+    pub fn unlock(&mut self, level: &str) -> bool {
+        self.unlocked.insert(level.to_owned())
+    }
+
+    pub fn best_time(&self, level: &str) -> Option<f32> {
+        self.best_times.get(level).copied()
+    }
+
+    /// Returns whether the time is a new best time for the level
+    pub fn record_time(&mut self, level: &str, time: f32) -> bool {
+        match self.best_times.get(level) {
+            Some(best_time) if *best_time <= time => false,
+            _ => {
+                self.best_times.insert(level.to_owned(), time);
+                true
+            }
+        }
+    }
+}
+
+/// How the last level ended, shown by the level complete and game over screens
+#[derive(Debug, Clone)]
+pub struct LevelResult {
+    pub level: String,
+    pub completed: bool,
+    pub next_level: Option<String>,
+}
+
+fn complete_levels(
+    mut commands: Commands,
+    mut complete_events: EventReader<LevelCompleteEvent>,
+    stats: Res<LevelStats>,
+    save_path: Res<SavePath>,
+    mut progression: ResMut<Progression>,
+    mut transition_events: EventWriter<TransitionEvent>,
+) {
+    let event = if let Some(event) = complete_events.iter().last() {
+        event
+    } else {
+        return;
+    };
+
+    let next_level = progression.next_level(&event.level).map(str::to_owned);
+
+    let mut changed = progression.record_time(&event.level, stats.time);
+
+    if let Some(next_level) = &next_level {
+        if progression.unlock(next_level) {
+            info!("Unlocked the level: {}", next_level);
+            changed = true;
+        }
+    }
+
+    if let (true, Some(path)) = (changed, &save_path.0) {
+        progression.save(path);
+    }
+
+    commands.insert_resource(LevelResult {
+        level: event.level.clone(),
+        completed: true,
+        next_level,
+    });
+
+    transition_events.send(TransitionEvent::new(
+        GameState::LevelComplete,
+        TransitionEffect::Dissolve,
+    ));
+}
+
+/// Ends the game once the player died more often than the configured lives allow
+fn check_game_over(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetLevelEvent>,
+    progression: Res<Progression>,
+    stats: Res<LevelStats>,
+    current_level: Option<Res<crate::world::CurrentLevel>>,
+    mut transition_events: EventWriter<TransitionEvent>,
+) {
+    // Wait for the death sequence to end, it resets the level
+    if reset_events.iter().count() == 0 {
+        return;
+    }
+
+    if progression.lives == 0 || stats.deaths < progression.lives {
+        return;
+    }
+
+    let level = if let Some(current_level) = current_level {
+        current_level.0.clone()
+    } else {
+        return;
+    };
+
+    commands.insert_resource(LevelResult {
+        level,
+        completed: false,
+        next_level: None,
+    });
+
+    transition_events.send(
+        TransitionEvent::new(GameState::GameOver, TransitionEffect::Fade)
+            .with_color(Color::rgb_u8(72, 29, 76)),
+    );
+}
+
+fn load_progression_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut progression: ResMut<Progression>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    progression.set_levels(config.progression.levels.clone());
+    progression.lives = config.progression.lives as u32;
+}
@@ -0,0 +1,617 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, asset::AssetServerSettings, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, ActionData, TickActions},
+    stages::GameState,
+    tick::{FixedTick, FixedTickStage, FixedTickSystems, Interpolated},
+    utils::{asset_file_path, GetSubHandle, SubHandles},
+    world::{DefaultLevels, Health, Player, ResetLevelEvent, Velocity},
+    GameAssets,
+};
+
+/// The config file hashed into every replay, relative to the asset folder
+const CONFIG_FILE: &str = "game.config";
+/// How far (in pixels) a replayed player may be off before the replay counts as diverged
+const POSITION_TOLERANCE: f32 = 0.01;
+const GHOST_Z: f32 = 3.9;
+
+/// Records the actions of every tick and plays them back
+///
+/// Insert a [`ReplayMode`] to use it. A replay starts on the tick the player is first seen in a
+/// level, at that point the level is reset so recording and playback start from the same state.
+#[derive(Debug, Default)]
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSeed>();
+        app.init_resource::<ReplayProgress>();
+        app.add_system_set(SystemSet::on_enter(GameState::Running).with_system(start_replay));
+        app.add_system_set_to_stage(
+            FixedTickStage,
+            SystemSet::on_update(GameState::Running)
+                .with_system(
+                    record_replay
+                        .label(FixedTickSystems::Input)
+                        .after(FixedTickSystems::Prepare),
+                )
+                .with_system(
+                    play_replay
+                        .label(FixedTickSystems::Input)
+                        .after(FixedTickSystems::Prepare),
+                )
+                .with_system(
+                    move_ghost
+                        .label(FixedTickSystems::Input)
+                        .after(FixedTickSystems::Prepare),
+                ),
+        );
+        app.add_system_set(SystemSet::on_exit(GameState::Running).with_system(finish_replay));
+    }
+}
+
+/// Seeds everything random in the game, so replays can reproduce it
+///
+/// Every source of randomness derives its own seed from it, see [`GameSeed::derive`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GameSeed(pub u64);
+
+impl GameSeed {
+    /// A seed for one source of randomness, `salt` tells the sources apart
+    pub fn derive(&self, salt: &[u8]) -> u32 {
+        let bytes: Vec<u8> = self.0.to_le_bytes().iter().chain(salt).copied().collect();
+        let hash = hash_bytes(&bytes);
+
+        (hash ^ (hash >> 32)) as u32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ReplayMode {
+    /// Records every attempt at a level and writes it to the file when the level is left
+    Record(PathBuf),
+    /// Plays the recorded actions instead of the ones of the player
+    Play(Replay),
+    /// Plays the replay and checks that the player ends up where it did when recording
+    ///
+    /// The result is inserted as a [`ReplayOutcome`], then the app exits.
+    Verify(Replay),
+    /// Shows the recorded player as a ghost next to the live one
+    Ghost(Replay),
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Ron(ron::Error),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::Ron(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// What is saved to a replay file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub level: String,
+    pub seed: u64,
+    /// Playing back with a different `game.config` most likely diverges
+    pub config_hash: u64,
+    pub tick_rate: f64,
+    pub ticks: Vec<ReplayTick>,
+    /// The player after the last tick
+    pub final_player: Option<PlayerSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayTick {
+    pub actions: Vec<(Action, ActionData)>,
+    /// Where the player was at the start of the tick, written replays like the fixtures in
+    /// `assets/replays` may leave it out to only check that the replay plays to its end
+    #[serde(default)]
+    pub player: Option<[f32; 2]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub health: u32,
+}
+
+impl PlayerSnapshot {
+    fn new(transform: &Transform, velocity: &Velocity, health: &Health) -> Self {
+        PlayerSnapshot {
+            position: transform.translation.truncate().into(),
+            velocity: velocity.0.into(),
+            health: health.current,
+        }
+    }
+
+    fn matches(&self, other: &PlayerSnapshot) -> bool {
+        let close = |a: [f32; 2], b: [f32; 2]| {
+            (Vec2::from(a) - Vec2::from(b)).abs().max_element() <= POSITION_TOLERANCE
+        };
+
+        close(self.position, other.position)
+            && close(self.velocity, other.velocity)
+            && self.health == other.health
+    }
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let contents = std::fs::read_to_string(path).map_err(ReplayError::Io)?;
+
+        ron::de::from_str(&contents).map_err(ReplayError::Ron)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let path = path.as_ref();
+
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(ReplayError::Ron)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(ReplayError::Io)?;
+        }
+
+        std::fs::write(path, contents).map_err(ReplayError::Io)
+    }
+}
+
+/// Why a verified replay did not end up like the recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    /// The player was somewhere else at the start of a tick
+    Position {
+        tick: usize,
+        expected: [f32; 2],
+        found: [f32; 2],
+    },
+    /// The player ended the replay in a different state
+    FinalState {
+        expected: PlayerSnapshot,
+        found: PlayerSnapshot,
+    },
+    /// The level was left before the replay was over, e.g. by completing it
+    Unfinished { tick: usize, ticks: usize },
+}
+
+impl std::fmt::Display for ReplayMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayMismatch::Position {
+                tick,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the player was at {:?} instead of {:?} on tick {}",
+                found, expected, tick
+            ),
+            ReplayMismatch::FinalState { expected, found } => {
+                write!(
+                    f,
+                    "the player ended as {:?} instead of {:?}",
+                    found, expected
+                )
+            }
+            ReplayMismatch::Unfinished { tick, ticks } => {
+                write!(f, "the level was left on tick {} of {}", tick, ticks)
+            }
+        }
+    }
+}
+
+/// The result of [`ReplayMode::Verify`], inserted once the replay is over
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOutcome(pub Result<(), ReplayMismatch>);
+
+#[derive(Debug, Default)]
+struct ReplayProgress {
+    /// Whether the level was reset for the replay already
+    started: bool,
+    finished: bool,
+    /// The next tick of the replay
+    tick: usize,
+    recording: Option<Replay>,
+    ghost: Option<Entity>,
+}
+
+/// Marks the sprite following the recorded player in [`ReplayMode::Ghost`]
+#[derive(Debug, Default)]
+pub struct Ghost;
+
+/// FNV-1a, which stays the same between builds unlike the std hasher
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn config_hash(asset_settings: Option<&AssetServerSettings>) -> u64 {
+    let path = asset_file_path(asset_settings, CONFIG_FILE);
+
+    match std::fs::read(&path) {
+        Ok(contents) => hash_bytes(&contents),
+        Err(err) => {
+            warn!("Could not hash the config {}: {}", path.display(), err);
+            0
+        }
+    }
+}
+
+fn start_replay(
+    mut progress: ResMut<ReplayProgress>,
+    mut game_seed: ResMut<GameSeed>,
+    mode: Option<Res<ReplayMode>>,
+    default_levels: Option<Res<DefaultLevels>>,
+    fixed_tick: Res<FixedTick>,
+    asset_settings: Option<Res<AssetServerSettings>>,
+) {
+    *progress = ReplayProgress::default();
+
+    let config_hash = config_hash(asset_settings.as_deref());
+
+    match mode.as_deref() {
+        Some(ReplayMode::Record(_)) => {
+            let level = default_levels
+                .and_then(|levels| levels.0.first().cloned())
+                .unwrap_or_default();
+
+            progress.recording = Some(Replay {
+                level,
+                seed: game_seed.0,
+                config_hash,
+                tick_rate: fixed_tick.rate(),
+                ..Default::default()
+            });
+        }
+        Some(ReplayMode::Play(replay))
+        | Some(ReplayMode::Verify(replay))
+        | Some(ReplayMode::Ghost(replay)) => {
+            game_seed.0 = replay.seed;
+
+            if replay.config_hash != config_hash {
+                warn!("The replay was recorded with a different {}", CONFIG_FILE);
+            }
+            if (replay.tick_rate - fixed_tick.rate()).abs() > f64::EPSILON {
+                warn!(
+                    "The replay was recorded at {} ticks per second, not {}",
+                    replay.tick_rate,
+                    fixed_tick.rate()
+                );
+            }
+        }
+        None => {}
+    }
+}
+
+/// Resets the level on the first tick with a player, returns whether the replay is running
+fn start_on_first_tick(
+    progress: &mut ReplayProgress,
+    tick_actions: &mut TickActions,
+    reset_events: &mut EventWriter<ResetLevelEvent>,
+    level: Entity,
+) -> bool {
+    if progress.started {
+        return true;
+    }
+
+    // Enemies may have moved before the player was spawned, so start from a fresh level
+    reset_events.send(ResetLevelEvent(level));
+    tick_actions.set_active(&[]);
+    progress.started = true;
+
+    false
+}
+
+fn record_replay(
+    mode: Option<Res<ReplayMode>>,
+    mut progress: ResMut<ReplayProgress>,
+    mut tick_actions: ResMut<TickActions>,
+    mut reset_events: EventWriter<ResetLevelEvent>,
+    player_query: Query<(&Transform, &Parent), With<Player>>,
+) {
+    if !matches!(mode.as_deref(), Some(ReplayMode::Record(_))) {
+        return;
+    }
+
+    let (transform, parent) = if let Some(player) = player_query.iter().next() {
+        player
+    } else {
+        return;
+    };
+
+    if !start_on_first_tick(
+        &mut progress,
+        &mut tick_actions,
+        &mut reset_events,
+        parent.0,
+    ) {
+        return;
+    }
+
+    let actions = tick_actions.active();
+    if let Some(recording) = &mut progress.recording {
+        recording.ticks.push(ReplayTick {
+            actions,
+            player: Some(transform.translation.truncate().into()),
+        });
+    }
+}
+
+fn play_replay(
+    mut commands: Commands,
+    mode: Option<Res<ReplayMode>>,
+    mut progress: ResMut<ReplayProgress>,
+    mut tick_actions: ResMut<TickActions>,
+    mut reset_events: EventWriter<ResetLevelEvent>,
+    mut exit_events: EventWriter<AppExit>,
+    player_query: Query<(&Transform, &Velocity, &Health, &Parent), With<Player>>,
+) {
+    let (replay, verify) = match mode.as_deref() {
+        Some(ReplayMode::Play(replay)) => (replay, false),
+        Some(ReplayMode::Verify(replay)) => (replay, true),
+        _ => return,
+    };
+
+    let (transform, velocity, health, parent) = if let Some(player) = player_query.iter().next() {
+        player
+    } else {
+        return;
+    };
+
+    if !start_on_first_tick(
+        &mut progress,
+        &mut tick_actions,
+        &mut reset_events,
+        parent.0,
+    ) {
+        return;
+    }
+
+    // The player stands still once the replay is over
+    tick_actions.set_active(&[]);
+    if progress.finished {
+        return;
+    }
+
+    let player = PlayerSnapshot::new(transform, velocity, health);
+
+    let result = if let Some(recorded) = replay.ticks.get(progress.tick) {
+        let expected = recorded
+            .player
+            .map(|position| PlayerSnapshot { position, ..player });
+
+        match expected {
+            Some(expected) if verify && !expected.matches(&player) => {
+                Err(ReplayMismatch::Position {
+                    tick: progress.tick,
+                    expected: expected.position,
+                    found: player.position,
+                })
+            }
+            _ => {
+                tick_actions.set_active(&recorded.actions);
+                progress.tick += 1;
+                return;
+            }
+        }
+    } else {
+        info!("The replay is over after {} ticks", progress.tick);
+
+        match replay.final_player {
+            Some(expected) if !expected.matches(&player) => Err(ReplayMismatch::FinalState {
+                expected,
+                found: player,
+            }),
+            _ => Ok(()),
+        }
+    };
+
+    progress.finished = true;
+
+    if verify {
+        report_outcome(&mut commands, &mut exit_events, result);
+    }
+}
+
+fn report_outcome(
+    commands: &mut Commands,
+    exit_events: &mut EventWriter<AppExit>,
+    result: Result<(), ReplayMismatch>,
+) {
+    match &result {
+        Ok(()) => info!("The replay was verified"),
+        Err(mismatch) => error!("The replay diverged: {}", mismatch),
+    }
+
+    commands.insert_resource(ReplayOutcome(result));
+    exit_events.send(AppExit);
+}
+
+/// Ends the process with a failure once a verified replay diverged, for running `--verify` in CI
+///
+/// A diverged replay exits the app like a verified one, which would end the process successfully.
+pub fn exit_on_mismatch(outcome: Option<Res<ReplayOutcome>>) {
+    if let Some(ReplayOutcome(Err(_))) = outcome.as_deref() {
+        std::process::exit(1);
+    }
+}
+
+fn move_ghost(
+    mut commands: Commands,
+    mode: Option<Res<ReplayMode>>,
+    game_assets: Res<GameAssets>,
+    mut progress: ResMut<ReplayProgress>,
+    mut sub_handles: ResMut<SubHandles>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    mut tick_actions: ResMut<TickActions>,
+    mut reset_events: EventWriter<ResetLevelEvent>,
+    player_query: Query<&Parent, With<Player>>,
+    mut ghost_query: Query<&mut Transform, With<Ghost>>,
+) {
+    let replay = if let Some(ReplayMode::Ghost(replay)) = mode.as_deref() {
+        replay
+    } else {
+        return;
+    };
+
+    let parent = if let Some(parent) = player_query.iter().next() {
+        parent
+    } else {
+        return;
+    };
+
+    if !start_on_first_tick(
+        &mut progress,
+        &mut tick_actions,
+        &mut reset_events,
+        parent.0,
+    ) {
+        return;
+    }
+
+    // The ghost stays where the recording ended
+    let last_tick = progress.tick.min(replay.ticks.len().saturating_sub(1));
+    let position = if let Some(position) = replay
+        .ticks
+        .get(..=last_tick)
+        .and_then(|ticks| ticks.iter().rev().find_map(|recorded| recorded.player))
+    {
+        Vec2::from(position)
+    } else {
+        return;
+    };
+    progress.tick += 1;
+
+    if let Some(mut transform) = progress
+        .ghost
+        .and_then(|ghost| ghost_query.get_mut(ghost).ok())
+    {
+        transform.translation = position.extend(GHOST_Z);
+        return;
+    }
+
+    let texture = match game_assets
+        .entity_sprites
+        .get_sub_handle("Frame0", &mut sub_handles)
+    {
+        Ok(texture) => texture,
+        Err(err) => {
+            error!("Could not get the ghost sprite: {}", err);
+            return;
+        }
+    };
+
+    let mut ghost = None;
+    commands.entity(parent.0).with_children(|parent| {
+        ghost = Some(
+            parent
+                .spawn_bundle(SpriteBundle {
+                    material: material_assets.add(ColorMaterial::modulated_texture(
+                        texture,
+                        Color::rgba(1., 1., 1., 0.4),
+                    )),
+                    transform: Transform::from_translation(position.extend(GHOST_Z)),
+                    ..Default::default()
+                })
+                .insert(Ghost)
+                .insert(Interpolated::default())
+                .id(),
+        );
+    });
+    progress.ghost = ghost;
+}
+
+fn finish_replay(
+    mut commands: Commands,
+    mode: Option<Res<ReplayMode>>,
+    mut progress: ResMut<ReplayProgress>,
+    mut exit_events: EventWriter<AppExit>,
+    player_query: Query<(&Transform, &Velocity, &Health), With<Player>>,
+) {
+    let path = match mode.as_deref() {
+        Some(ReplayMode::Record(path)) => path,
+        Some(ReplayMode::Verify(replay)) if progress.started && !progress.finished => {
+            // The level was left before the replay was over, e.g. by completing it
+            let found = player_query
+                .iter()
+                .next()
+                .map(|(transform, velocity, health)| {
+                    PlayerSnapshot::new(transform, velocity, health)
+                });
+
+            let result = match (
+                progress.tick < replay.ticks.len(),
+                replay.final_player,
+                found,
+            ) {
+                (true, _, _) => Err(ReplayMismatch::Unfinished {
+                    tick: progress.tick,
+                    ticks: replay.ticks.len(),
+                }),
+                (false, Some(expected), Some(found)) if !expected.matches(&found) => {
+                    Err(ReplayMismatch::FinalState { expected, found })
+                }
+                _ => Ok(()),
+            };
+
+            progress.finished = true;
+            report_outcome(&mut commands, &mut exit_events, result);
+            return;
+        }
+        _ => return,
+    };
+
+    let mut recording = if let Some(recording) = progress.recording.take() {
+        recording
+    } else {
+        return;
+    };
+
+    if recording.ticks.is_empty() {
+        return;
+    }
+
+    recording.final_player = player_query
+        .iter()
+        .next()
+        .map(|(transform, velocity, health)| PlayerSnapshot::new(transform, velocity, health));
+
+    match recording.save(path) {
+        Ok(()) => info!(
+            "Recorded {} ticks of {} to {}",
+            recording.ticks.len(),
+            recording.level,
+            path.display()
+        ),
+        Err(err) => error!("Could not save the replay {}: {}", path.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameSeed;
+
+    #[test]
+    fn derived_seeds_depend_on_the_seed_and_the_salt() {
+        let seed = GameSeed(42);
+
+        assert_eq!(seed.derive(b"shake"), GameSeed(42).derive(b"shake"));
+        assert_ne!(seed.derive(b"shake"), seed.derive(b"particles"));
+        assert_ne!(seed.derive(b"shake"), GameSeed(43).derive(b"shake"));
+    }
+}
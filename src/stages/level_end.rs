@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use bevy_spicy_aseprite::AsepriteImage;
+
+use crate::{
+    progression::LevelResult,
+    ui::{create_nine_patch, ButtonPressCommand, NinePatchButton, UiSettings},
+    utils::SubHandles,
+    world::{DefaultLevels, LevelStats},
+    GameAssets,
+};
+
+use super::{GameState, TransitionEffect, TransitionEvent};
+
+/// The level complete and game over screens, both show how the level went
+pub struct LevelEndStagePlugin;
+
+impl Plugin for LevelEndStagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelEndEvents>();
+
+        for state in [GameState::LevelComplete, GameState::GameOver] {
+            app.add_system_set(SystemSet::on_enter(state).with_system(create_level_end_screen));
+            app.add_system_set(
+                SystemSet::on_update(state)
+                    .with_system(ButtonPressCommand::<LevelEndEvents>::send_button_press)
+                    .with_system(listen_for_level_end_events),
+            );
+            app.add_system_set(SystemSet::on_exit(state).with_system(remove_level_end_screen));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum LevelEndEvents {
+    PlayLevel(String),
+    MainMenu,
+}
+
+fn listen_for_level_end_events(
+    mut commands: Commands,
+    mut transition_events: EventWriter<TransitionEvent>,
+    mut level_end_events: EventReader<LevelEndEvents>,
+) {
+    match level_end_events.iter().last() {
+        Some(LevelEndEvents::PlayLevel(level)) => {
+            commands.insert_resource(DefaultLevels(vec![level.clone()]));
+            transition_events.send(TransitionEvent::new(
+                GameState::Running,
+                TransitionEffect::Iris,
+            ));
+        }
+        Some(LevelEndEvents::MainMenu) => {
+            transition_events.send(TransitionEvent::new(
+                GameState::MainMenu,
+                TransitionEffect::Fade,
+            ));
+        }
+        None => {}
+    }
+}
+
+struct LevelEndScreenEntity(Entity);
+
+fn create_level_end_screen(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    ui_settings: Res<UiSettings>,
+    result: Option<Res<LevelResult>>,
+    stats: Res<LevelStats>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut sub_handles: ResMut<SubHandles>,
+    asprite_assets: Res<Assets<AsepriteImage>>,
+) {
+    let result = if let Some(result) = result {
+        result
+    } else {
+        error!("No level result to show");
+        return;
+    };
+
+    let ui_aseprite = if let Some(ui_aseprite) = asprite_assets.get(&game_assets.ui_sprites) {
+        ui_aseprite
+    } else {
+        return;
+    };
+
+    let slices = ui_aseprite.aseprite().slices();
+
+    let menu_nine_slice =
+        if let Some(menu_nine_slice) = slices.get_by_name(&crate::ui_sprites::slices::Menu) {
+            menu_nine_slice
+        } else {
+            error!("Could not find menu slice");
+            return;
+        };
+
+    let button_nine_slice = if let Some(button_nine_slice) =
+        slices.get_by_name(&crate::ui_sprites::slices::ButtonNormal)
+    {
+        button_nine_slice
+    } else {
+        error!("Could not find button slice");
+        return;
+    };
+
+    let title_text_style = TextStyle {
+        font: game_assets.main_font.clone(),
+        font_size: 8. * 4. * ui_settings.scale,
+        color: Color::BLACK,
+    };
+
+    let text_style = TextStyle {
+        font: game_assets.main_font.clone(),
+        font_size: 8. * 2. * ui_settings.scale,
+        color: Color::BLACK,
+    };
+
+    let title = if result.completed {
+        "Level Complete"
+    } else {
+        "Game Over"
+    };
+
+    let minutes = (stats.time / 60.) as u32;
+    let seconds = stats.time % 60.;
+    let stat_lines = [
+        format!("Level: {}", result.level),
+        format!("Time: {}:{:04.1}", minutes, seconds),
+        format!("Deaths: {}", stats.deaths),
+        format!("Time lost: {:.1}s", stats.time_lost),
+        format!(
+            "Seeds: {} delivered, {} broken",
+            stats.seeds_delivered, stats.seeds_broken
+        ),
+    ];
+
+    let mut buttons = Vec::new();
+    if let Some(next_level) = &result.next_level {
+        buttons.push(("Next Level", LevelEndEvents::PlayLevel(next_level.clone())));
+    }
+    if !result.completed {
+        buttons.push(("Retry", LevelEndEvents::PlayLevel(result.level.clone())));
+    }
+    buttons.push(("Main Menu", LevelEndEvents::MainMenu));
+
+    let transparent_material = materials.add(Color::NONE.into());
+
+    let level_end_screen = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: transparent_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            create_nine_patch(
+                parent,
+                menu_nine_slice,
+                game_assets.ui_sprites.clone(),
+                &mut materials,
+                &mut sub_handles,
+                Some(Style {
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::ColumnReverse,
+                    padding: Rect::all(Val::Px(10.)),
+                    ..Default::default()
+                }),
+            )
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(title, title_text_style, Default::default()),
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+
+                for line in stat_lines.iter() {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(line, text_style.clone(), Default::default()),
+                        style: Style {
+                            margin: Rect::all(Val::Px(3.)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+                }
+
+                for (label, event) in buttons {
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                margin: Rect::all(Val::Px(10.)),
+                                ..Default::default()
+                            },
+                            material: transparent_material.clone(),
+                            ..Default::default()
+                        })
+                        .insert(ButtonPressCommand { event })
+                        .insert(NinePatchButton {
+                            normal: crate::ui_sprites::slices::ButtonNormal,
+                            hover: Some(crate::ui_sprites::slices::ButtonHover),
+                            pressed: Some(crate::ui_sprites::slices::ButtonPressed),
+                        })
+                        .with_children(|parent| {
+                            create_nine_patch(
+                                parent,
+                                button_nine_slice,
+                                game_assets.ui_sprites.clone(),
+                                &mut materials,
+                                &mut sub_handles,
+                                None,
+                            )
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        label,
+                                        text_style.clone(),
+                                        Default::default(),
+                                    ),
+                                    style: Style {
+                                        margin: Rect::all(Val::Px(5.)),
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                });
+                            });
+                        });
+                }
+            });
+        })
+        .id();
+
+    commands.insert_resource(LevelEndScreenEntity(level_end_screen));
+    debug!("Created level end screen");
+}
+
+fn remove_level_end_screen(
+    mut commands: Commands,
+    screen_entity: Option<Res<LevelEndScreenEntity>>,
+) {
+    if let Some(screen_entity) = screen_entity {
+        commands.entity(screen_entity.0).despawn_recursive();
+    }
+
+    commands.remove_resource::<LevelEndScreenEntity>();
+}
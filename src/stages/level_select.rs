@@ -0,0 +1,385 @@
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, FilterMode, TextureDimension, TextureFormat},
+};
+use bevy_spicy_aseprite::AsepriteImage;
+
+use crate::{
+    actions::{Action, ActionState},
+    progression::Progression,
+    ui::{create_nine_patch, ButtonPressCommand, NinePatchButton, UiSettings},
+    utils::SubHandles,
+    world::{CollisionMap, DefaultLevels, TileKind},
+    GameAssets,
+};
+
+use super::{GameState, TransitionEffect, TransitionEvent};
+
+/// Width of the level thumbnails in pixels, the height follows the size of the level
+const THUMBNAIL_WIDTH: f32 = 128.;
+
+pub struct LevelSelectStagePlugin;
+
+impl Plugin for LevelSelectStagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelSelectEvents>();
+        app.add_system_set(
+            SystemSet::on_enter(GameState::LevelSelect).with_system(create_level_select),
+        );
+        app.add_system_set(
+            SystemSet::on_update(GameState::LevelSelect)
+                .with_system(ButtonPressCommand::<LevelSelectEvents>::send_button_press)
+                .with_system(listen_for_level_select_events),
+        );
+        app.add_system_set(
+            SystemSet::on_exit(GameState::LevelSelect).with_system(remove_level_select),
+        );
+    }
+}
+
+#[derive(Debug, Clone)]
+enum LevelSelectEvents {
+    PlayLevel(String),
+    Back,
+}
+
+fn listen_for_level_select_events(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut transition_events: EventWriter<TransitionEvent>,
+    mut level_select_events: EventReader<LevelSelectEvents>,
+) {
+    let last_event = level_select_events.iter().last().cloned().or_else(|| {
+        if actions.just_pressed(Action::MenuBack) {
+            Some(LevelSelectEvents::Back)
+        } else {
+            None
+        }
+    });
+
+    match last_event {
+        Some(LevelSelectEvents::PlayLevel(level)) => {
+            commands.insert_resource(DefaultLevels(vec![level]));
+            transition_events.send(TransitionEvent::new(
+                GameState::Running,
+                TransitionEffect::Iris,
+            ));
+        }
+        Some(LevelSelectEvents::Back) => {
+            transition_events.send(TransitionEvent::new(
+                GameState::MainMenu,
+                TransitionEffect::Fade,
+            ));
+        }
+        None => {}
+    }
+}
+
+struct LevelSelectScreenEntity(Entity);
+
+/// Draws the IntGrid of a level with one pixel per cell
+fn render_thumbnail(collision_map: &CollisionMap) -> Texture {
+    let width = collision_map.size.x.max(1) as usize;
+    let height = collision_map.size.y.max(1) as usize;
+
+    let mut data = Vec::with_capacity(width * height * 4);
+    // Textures start at the top, cells at the bottom
+    for y in (0..height as i32).rev() {
+        for x in 0..width as i32 {
+            let color: [u8; 4] = match collision_map.tile(IVec2::new(x, y)) {
+                TileKind::Empty => [163, 169, 194, 255],
+                TileKind::Solid => [72, 29, 76, 255],
+                TileKind::Lava => [230, 72, 46, 255],
+                TileKind::Ladder => [28, 45, 189, 255],
+                // Hidden tiles stay a secret
+                TileKind::Hidden => [163, 169, 194, 255],
+            };
+            data.extend_from_slice(&color);
+        }
+    }
+
+    let mut texture = Texture::new(
+        Extent3d::new(width as u32, height as u32, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    texture.sampler.mag_filter = FilterMode::Nearest;
+
+    texture
+}
+
+fn create_level_select(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    ui_settings: Res<UiSettings>,
+    progression: Res<Progression>,
+    ldtk_assets: Res<Assets<crate::levels::Project>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut texture_assets: ResMut<Assets<Texture>>,
+    mut sub_handles: ResMut<SubHandles>,
+    asprite_assets: Res<Assets<AsepriteImage>>,
+) {
+    let ldtk_project = if let Some(ldtk) = ldtk_assets.get(&game_assets.levels) {
+        ldtk
+    } else {
+        error!("Could not get the ldtk project for the level select");
+        return;
+    };
+
+    let ui_aseprite = if let Some(ui_aseprite) = asprite_assets.get(&game_assets.ui_sprites) {
+        ui_aseprite
+    } else {
+        return;
+    };
+
+    let slices = ui_aseprite.aseprite().slices();
+
+    let menu_nine_slice =
+        if let Some(menu_nine_slice) = slices.get_by_name(&crate::ui_sprites::slices::Menu) {
+            menu_nine_slice
+        } else {
+            error!("Could not find menu slice");
+            return;
+        };
+
+    let button_nine_slice = if let Some(button_nine_slice) =
+        slices.get_by_name(&crate::ui_sprites::slices::ButtonNormal)
+    {
+        button_nine_slice
+    } else {
+        error!("Could not find button slice");
+        return;
+    };
+
+    let title_text_style = TextStyle {
+        font: game_assets.main_font.clone(),
+        font_size: 8. * 4. * ui_settings.scale,
+        color: Color::BLACK,
+    };
+
+    let text_style = TextStyle {
+        font: game_assets.main_font.clone(),
+        font_size: 8. * 2. * ui_settings.scale,
+        color: Color::BLACK,
+    };
+
+    let locked_text_style = TextStyle {
+        color: Color::GRAY,
+        ..text_style.clone()
+    };
+
+    let transparent_material = materials.add(Color::NONE.into());
+
+    let level_select_screen = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: transparent_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            create_nine_patch(
+                parent,
+                menu_nine_slice,
+                game_assets.ui_sprites.clone(),
+                &mut materials,
+                &mut sub_handles,
+                Some(Style {
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::ColumnReverse,
+                    padding: Rect::all(Val::Px(10.)),
+                    ..Default::default()
+                }),
+            )
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section("Select Level", title_text_style, Default::default()),
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_wrap: FlexWrap::Wrap,
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        material: transparent_material.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        for ldtk_level in &ldtk_project.levels {
+                            let identifier = ldtk_level.identifier.clone();
+                            let display_name = if ldtk_level.fields.display_name.is_empty() {
+                                identifier.clone()
+                            } else {
+                                ldtk_level.fields.display_name.clone()
+                            };
+                            let playable = progression.is_playable(&identifier);
+
+                            let subtitle = if !playable {
+                                String::from("Locked")
+                            } else if let Some(best_time) = progression.best_time(&identifier) {
+                                format!(
+                                    "Best: {}:{:04.1}",
+                                    (best_time / 60.) as u32,
+                                    best_time % 60.
+                                )
+                            } else {
+                                String::from("Not completed")
+                            };
+
+                            let collision_map = CollisionMap::from_layer(&ldtk_level.layers.front);
+                            let aspect = collision_map.size.y.max(1) as f32
+                                / collision_map.size.x.max(1) as f32;
+                            let thumbnail = texture_assets.add(render_thumbnail(&collision_map));
+                            let thumbnail_color = if playable {
+                                Color::WHITE
+                            } else {
+                                Color::rgba(1., 1., 1., 0.3)
+                            };
+
+                            let mut button = parent.spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    margin: Rect::all(Val::Px(10.)),
+                                    ..Default::default()
+                                },
+                                material: transparent_material.clone(),
+                                ..Default::default()
+                            });
+
+                            if playable {
+                                button
+                                    .insert(ButtonPressCommand {
+                                        event: LevelSelectEvents::PlayLevel(identifier.clone()),
+                                    })
+                                    .insert(NinePatchButton {
+                                        normal: crate::ui_sprites::slices::ButtonNormal,
+                                        hover: Some(crate::ui_sprites::slices::ButtonHover),
+                                        pressed: Some(crate::ui_sprites::slices::ButtonPressed),
+                                    });
+                            }
+
+                            button.with_children(|parent| {
+                                create_nine_patch(
+                                    parent,
+                                    button_nine_slice,
+                                    game_assets.ui_sprites.clone(),
+                                    &mut materials,
+                                    &mut sub_handles,
+                                    Some(Style {
+                                        flex_direction: FlexDirection::ColumnReverse,
+                                        align_items: AlignItems::Center,
+                                        ..Default::default()
+                                    }),
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn_bundle(ImageBundle {
+                                        style: Style {
+                                            size: Size::new(
+                                                Val::Px(THUMBNAIL_WIDTH),
+                                                Val::Px(THUMBNAIL_WIDTH * aspect),
+                                            ),
+                                            margin: Rect::all(Val::Px(5.)),
+                                            ..Default::default()
+                                        },
+                                        material: materials.add(ColorMaterial::modulated_texture(
+                                            thumbnail,
+                                            thumbnail_color,
+                                        )),
+                                        ..Default::default()
+                                    });
+
+                                    let style = if playable {
+                                        text_style.clone()
+                                    } else {
+                                        locked_text_style.clone()
+                                    };
+
+                                    for line in [display_name, subtitle] {
+                                        parent.spawn_bundle(TextBundle {
+                                            text: Text::with_section(
+                                                line,
+                                                style.clone(),
+                                                Default::default(),
+                                            ),
+                                            style: Style {
+                                                margin: Rect::all(Val::Px(3.)),
+                                                ..Default::default()
+                                            },
+                                            ..Default::default()
+                                        });
+                                    }
+                                });
+                            });
+                        }
+                    });
+
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(10.)),
+                            ..Default::default()
+                        },
+                        material: transparent_material.clone(),
+                        ..Default::default()
+                    })
+                    .insert(ButtonPressCommand {
+                        event: LevelSelectEvents::Back,
+                    })
+                    .insert(NinePatchButton {
+                        normal: crate::ui_sprites::slices::ButtonNormal,
+                        hover: Some(crate::ui_sprites::slices::ButtonHover),
+                        pressed: Some(crate::ui_sprites::slices::ButtonPressed),
+                    })
+                    .with_children(|parent| {
+                        create_nine_patch(
+                            parent,
+                            button_nine_slice,
+                            game_assets.ui_sprites.clone(),
+                            &mut materials,
+                            &mut sub_handles,
+                            None,
+                        )
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    "Back",
+                                    text_style.clone(),
+                                    Default::default(),
+                                ),
+                                style: Style {
+                                    margin: Rect::all(Val::Px(5.)),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            });
+                        });
+                    });
+            });
+        })
+        .id();
+
+    commands.insert_resource(LevelSelectScreenEntity(level_select_screen));
+    debug!("Created level select");
+}
+
+fn remove_level_select(
+    mut commands: Commands,
+    screen_entity: Option<Res<LevelSelectScreenEntity>>,
+) {
+    if let Some(screen_entity) = screen_entity {
+        commands.entity(screen_entity.0).despawn_recursive();
+    }
+
+    commands.remove_resource::<LevelSelectScreenEntity>();
+}
@@ -2,13 +2,14 @@ use bevy::{app::AppExit, prelude::*};
 use bevy_spicy_aseprite::{AsepriteImage, AsepriteSliceName};
 
 use crate::{
-    ui::{create_nine_patch, ButtonPressCommand, NinePatchButton},
-    utils::GetSubHandle,
+    progression::Progression,
+    ui::{create_nine_patch, ButtonPressCommand, NinePatchButton, UiSettings},
+    utils::{GetSubHandle, SubHandles},
     world::DefaultLevels,
     GameAssets,
 };
 
-use super::GameState;
+use super::{GameState, TransitionEffect, TransitionEvent};
 
 pub struct MainMenuStagePlugin;
 
@@ -28,12 +29,14 @@ impl Plugin for MainMenuStagePlugin {
 #[derive(Debug, Clone, Copy)]
 enum MainMenuEvents {
     StartGame,
+    SelectLevel,
     Exit,
 }
 
 fn listen_for_menu_events(
     mut commands: Commands,
-    mut state: ResMut<State<GameState>>,
+    mut transition_events: EventWriter<TransitionEvent>,
+    progression: Res<Progression>,
     mut main_menu_events: EventReader<MainMenuEvents>,
     mut exit_events: EventWriter<AppExit>,
 ) {
@@ -44,8 +47,24 @@ fn listen_for_menu_events(
             exit_events.send(AppExit);
         }
         Some(&MainMenuEvents::StartGame) => {
-            commands.insert_resource(DefaultLevels(vec![String::from("Level_0")]));
-            state.set(GameState::Running).unwrap();
+            let first_level = if let Some(first_level) = progression.first_level() {
+                first_level
+            } else {
+                error!("No levels configured in the progression");
+                return;
+            };
+
+            commands.insert_resource(DefaultLevels(vec![first_level.to_owned()]));
+            transition_events.send(TransitionEvent::new(
+                GameState::Running,
+                TransitionEffect::Iris,
+            ));
+        }
+        Some(&MainMenuEvents::SelectLevel) => {
+            transition_events.send(TransitionEvent::new(
+                GameState::LevelSelect,
+                TransitionEffect::Fade,
+            ));
         }
         None => {}
     }
@@ -56,8 +75,9 @@ struct MainMenuScreenEntity(Entity);
 fn create_main_menu(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
+    ui_settings: Res<UiSettings>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut texture_assets: ResMut<Assets<Texture>>,
+    mut sub_handles: ResMut<SubHandles>,
     asprite_assets: Res<Assets<AsepriteImage>>,
 ) {
     let ui_aseprite = if let Some(ui_aseprite) = asprite_assets.get(&game_assets.ui_sprites) {
@@ -85,15 +105,26 @@ fn create_main_menu(
         return;
     };
 
+    let icon_texture = match game_assets.ui_sprites.get_sub_handle(
+        &crate::ui_sprites::slices::SpicyIcon.label(),
+        &mut sub_handles,
+    ) {
+        Ok(icon_texture) => icon_texture,
+        Err(err) => {
+            error!("Could not find the icon: {}", err);
+            Handle::default()
+        }
+    };
+
     let title_text_style = TextStyle {
         font: game_assets.main_font.clone(),
-        font_size: 8. * 5.,
+        font_size: 8. * 5. * ui_settings.scale,
         color: Color::BLACK,
     };
 
     let menu_text_style = TextStyle {
         font: game_assets.main_font.clone(),
-        font_size: 8. * 3.,
+        font_size: 8. * 3. * ui_settings.scale,
         color: Color::BLACK,
     };
 
@@ -136,12 +167,7 @@ fn create_main_menu(
                             },
                             ..Default::default()
                         },
-                        material: materials.add(ColorMaterial::texture(
-                            game_assets.ui_sprites.get_sub_handle(
-                                &crate::ui_sprites::slices::SpicyIcon.label(),
-                                &mut texture_assets,
-                            ),
-                        )),
+                        material: materials.add(ColorMaterial::texture(icon_texture)),
                         ..Default::default()
                     });
                     parent.spawn_bundle(TextBundle {
@@ -182,7 +208,7 @@ fn create_main_menu(
                         menu_nine_slice,
                         game_assets.ui_sprites.clone(),
                         &mut materials,
-                        &mut texture_assets,
+                        &mut sub_handles,
                         Some(Style {
                             // size: Size::new(Val::Percent(100.), Val::Auto),
                             flex_grow: 1.,
@@ -216,7 +242,7 @@ fn create_main_menu(
                                     normal_button_nine_slice,
                                     game_assets.ui_sprites.clone(),
                                     &mut materials,
-                                    &mut texture_assets,
+                                    &mut sub_handles,
                                     None,
                                 )
                                 .with_children(|parent| {
@@ -238,6 +264,56 @@ fn create_main_menu(
                                 });
                             });
 
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    margin: Rect {
+                                        left: Val::Px(25.),
+                                        right: Val::Px(25.),
+                                        bottom: Val::Px(25.),
+                                        top: Val::Px(0.),
+                                    },
+                                    ..Default::default()
+                                },
+                                material: transparent_material.clone(),
+                                ..Default::default()
+                            })
+                            .insert(ButtonPressCommand {
+                                event: MainMenuEvents::SelectLevel,
+                            })
+                            .insert(NinePatchButton {
+                                normal: crate::ui_sprites::slices::ButtonNormal,
+                                hover: Some(crate::ui_sprites::slices::ButtonHover),
+                                pressed: Some(crate::ui_sprites::slices::ButtonPressed),
+                            })
+                            .with_children(|parent| {
+                                create_nine_patch(
+                                    parent,
+                                    normal_button_nine_slice,
+                                    game_assets.ui_sprites.clone(),
+                                    &mut materials,
+                                    &mut sub_handles,
+                                    None,
+                                )
+                                .with_children(|parent| {
+                                    parent.spawn_bundle(TextBundle {
+                                        text: Text::with_section(
+                                            "Select Level",
+                                            menu_text_style.clone(),
+                                            TextAlignment {
+                                                vertical: VerticalAlign::Center,
+                                                horizontal: HorizontalAlign::Center,
+                                            },
+                                        ),
+                                        style: Style {
+                                            margin: Rect::all(Val::Px(5.)),
+                                            ..Default::default()
+                                        },
+                                        ..Default::default()
+                                    });
+                                });
+                            });
+
                         parent
                             .spawn_bundle(ButtonBundle {
                                 style: Style {
@@ -266,7 +342,7 @@ fn create_main_menu(
                                     normal_button_nine_slice,
                                     game_assets.ui_sprites.clone(),
                                     &mut materials,
-                                    &mut texture_assets,
+                                    &mut sub_handles,
                                     None,
                                 )
                                 .with_children(|parent| {
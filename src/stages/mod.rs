@@ -1,16 +1,29 @@
 use bevy::prelude::*;
 use bevy_loading::LoadingPlugin;
 
-use self::{loading::LoadingStagePlugin, main_menu::MainMenuStagePlugin};
+use crate::{cli::LaunchOptions, progression::Progression, world::DefaultLevels};
 
+use self::{
+    level_end::LevelEndStagePlugin, level_select::LevelSelectStagePlugin,
+    loading::LoadingStagePlugin, main_menu::MainMenuStagePlugin, transition::TransitionPlugin,
+};
+
+pub use self::transition::{ScreenTransition, TransitionEffect, TransitionEvent};
+
+mod level_end;
+mod level_select;
 mod loading;
 mod main_menu;
+mod transition;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum GameState {
     Loading,
     MainMenu,
+    LevelSelect,
     Running,
+    LevelComplete,
+    GameOver,
 }
 
 #[derive(Debug, Default)]
@@ -20,13 +33,50 @@ impl Plugin for StagesPlugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::Loading);
 
+        let skip_menu = app
+            .world
+            .get_resource::<LaunchOptions>()
+            .map_or(false, LaunchOptions::skips_menu);
+
         app.add_plugin(LoadingPlugin {
             loading_state: GameState::Loading,
-            next_state: GameState::MainMenu,
+            next_state: if skip_menu {
+                GameState::Running
+            } else {
+                GameState::MainMenu
+            },
         });
+        if skip_menu {
+            app.add_system_set(
+                SystemSet::on_exit(GameState::Loading).with_system(insert_first_level),
+            );
+        }
+
+        app.add_plugin(TransitionPlugin);
 
         app.add_plugin(LoadingStagePlugin);
 
         app.add_plugin(MainMenuStagePlugin);
+
+        app.add_plugin(LevelSelectStagePlugin);
+
+        app.add_plugin(LevelEndStagePlugin);
+    }
+}
+
+/// Starts in the first level of the progression when skipping the menu without a level
+fn insert_first_level(
+    mut commands: Commands,
+    progression: Res<Progression>,
+    default_levels: Option<Res<DefaultLevels>>,
+) {
+    if default_levels.is_some() {
+        return;
+    }
+
+    if let Some(first_level) = progression.first_level() {
+        commands.insert_resource(DefaultLevels(vec![first_level.to_owned()]));
+    } else {
+        error!("No levels configured in the progression");
     }
 }
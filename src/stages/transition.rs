@@ -0,0 +1,511 @@
+//! Screen transitions hiding the changes between game states
+//!
+//! Send a [`TransitionEvent`] instead of calling `State::set` directly: the screen is covered
+//! first, then the state changes and the next state is revealed once its `on_enter` systems ran.
+
+use bevy::prelude::*;
+
+use crate::ui::UiSettings;
+
+use super::GameState;
+
+/// Cells of the [`TransitionEffect::Dissolve`] grid
+const DISSOLVE_COLUMNS: usize = 16;
+const DISSOLVE_ROWS: usize = 9;
+/// Steps through the cells in an order that looks random, has to share no factor with the
+/// number of cells so every cell gets its own threshold
+const DISSOLVE_STRIDE: usize = 97;
+
+/// Horizontal strips the [`TransitionEffect::Iris`] is drawn with, fewer look more pixelated
+const IRIS_ROWS: usize = 45;
+
+/// The aspect ratio assumed without a window, like in the tests
+const DEFAULT_ASPECT: f32 = 16. / 9.;
+
+#[derive(Debug, Default)]
+pub struct TransitionPlugin;
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum TransitionSystems {
+    Start,
+    Update,
+}
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TransitionEvent>()
+            .init_resource::<ScreenTransition>()
+            .add_system(start_transitions.label(TransitionSystems::Start))
+            .add_system(
+                update_transition
+                    .label(TransitionSystems::Update)
+                    .after(TransitionSystems::Start),
+            )
+            .add_system(draw_transition.after(TransitionSystems::Update))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading).with_system(reveal_after_loading),
+            );
+    }
+}
+
+/// How the screen gets covered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionEffect {
+    /// The whole screen fades to the color
+    Fade,
+    /// Square cells of the color pop in one after another
+    Dissolve,
+    /// A circle around the center of the screen closes
+    Iris,
+}
+
+/// Switches to `to` behind a screen transition
+///
+/// Requests arriving while a transition is running wait for it to end, only the latest one is
+/// kept. It is dropped if the game is in its state by then, so pressing a button twice does not
+/// play the transition twice.
+#[derive(Debug, Clone, Copy)]
+pub struct TransitionEvent {
+    pub to: GameState,
+    pub effect: TransitionEffect,
+    pub color: Color,
+}
+
+impl TransitionEvent {
+    pub fn new(to: GameState, effect: TransitionEffect) -> Self {
+        TransitionEvent {
+            to,
+            effect,
+            color: Color::BLACK,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// The transition currently on screen
+#[derive(Debug)]
+pub struct ScreenTransition {
+    phase: TransitionPhase,
+    effect: TransitionEffect,
+    color: Color,
+    overlay: Option<Entity>,
+    /// The latest request sent while running, started once this transition ended
+    queued: Option<TransitionEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransitionPhase {
+    Idle,
+    /// Covering the screen, `progress` goes from `0.` to `1.`
+    Out {
+        to: GameState,
+        progress: f32,
+    },
+    /// The screen is covered until the state changed
+    Waiting {
+        to: GameState,
+    },
+    /// Revealing the next state, `progress` goes from `0.` to `1.`
+    In {
+        progress: f32,
+    },
+}
+
+impl Default for ScreenTransition {
+    fn default() -> Self {
+        ScreenTransition {
+            phase: TransitionPhase::Idle,
+            effect: TransitionEffect::Fade,
+            color: Color::BLACK,
+            overlay: None,
+            queued: None,
+        }
+    }
+}
+
+impl ScreenTransition {
+    pub fn is_running(&self) -> bool {
+        self.phase != TransitionPhase::Idle
+    }
+
+    /// How much of the screen is covered, from `0.` to `1.`
+    pub fn coverage(&self) -> f32 {
+        match self.phase {
+            TransitionPhase::Idle => 0.,
+            TransitionPhase::Out { progress, .. } => progress,
+            TransitionPhase::Waiting { .. } => 1.,
+            TransitionPhase::In { progress } => 1. - progress,
+        }
+    }
+}
+
+/// One part of the overlay, which parts there are depends on the [`TransitionEffect`]
+#[derive(Debug, Clone, Copy)]
+enum OverlayPart {
+    Fade,
+    /// Shown once the coverage reaches `threshold`
+    Cell {
+        threshold: f32,
+    },
+    /// Covers a row from one side of the screen up to the edge of the iris
+    IrisBar {
+        row: usize,
+        left: bool,
+    },
+}
+
+/// The order the cells of the dissolve appear in, from `0.` (first) to below `1.` (last)
+fn dissolve_threshold(index: usize) -> f32 {
+    let cells = DISSOLVE_COLUMNS * DISSOLVE_ROWS;
+    (index * DISSOLVE_STRIDE % cells) as f32 / cells as f32
+}
+
+/// The width of a bar of the iris in percent of the screen width
+///
+/// The screen is measured in heights here, so the iris stays a circle on any aspect ratio.
+fn iris_bar_width(row: usize, coverage: f32, aspect: f32) -> f32 {
+    let half_width = aspect / 2.;
+    // The circle starts out just touching the corners
+    let max_radius = (half_width * half_width + 0.25).sqrt();
+    let radius = (1. - coverage.clamp(0., 1.)) * max_radius;
+
+    let y = (row as f32 + 0.5) / IRIS_ROWS as f32 - 0.5;
+    let half_chord = (radius * radius - y * y).max(0.).sqrt();
+
+    ((half_width - half_chord) / aspect * 100.).clamp(0., 50.)
+}
+
+fn screen_aspect(windows: Option<&Windows>) -> f32 {
+    windows
+        .and_then(Windows::get_primary)
+        .filter(|window| window.height() > 0.)
+        .map_or(DEFAULT_ASPECT, |window| window.width() / window.height())
+}
+
+/// Shows as much of `part` as the `coverage` of the screen asks for
+fn cover(part: OverlayPart, coverage: f32, aspect: f32, style: &mut Style, visible: &mut Visible) {
+    match part {
+        // The color of the material fades instead
+        OverlayPart::Fade => visible.is_visible = coverage > 0.,
+        OverlayPart::Cell { threshold } => visible.is_visible = coverage > threshold,
+        OverlayPart::IrisBar { left, row } => {
+            let width = iris_bar_width(row, coverage, aspect);
+            visible.is_visible = width > 0.;
+            // Overlaps the other bar a little once the iris is closed
+            style.size.width = Val::Percent(width + 0.1);
+            if !left {
+                style.position.left = Val::Percent(100. - width);
+            }
+        }
+    }
+}
+
+fn fade_color(color: Color, coverage: f32) -> Color {
+    let mut color = color;
+    color.set_a(color.a() * coverage);
+    color
+}
+
+fn start_transitions(
+    mut commands: Commands,
+    mut transition_events: EventReader<TransitionEvent>,
+    state: Res<State<GameState>>,
+    windows: Option<Res<Windows>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut transition: ResMut<ScreenTransition>,
+) {
+    // The queued request is older than the ones sent this frame
+    if !transition.is_running() {
+        if let Some(event) = transition.queued.take() {
+            if event.to == *state.current() {
+                debug!("Already in {:?}, dropped the queued change", event.to);
+            } else {
+                start_transition(
+                    &mut commands,
+                    &mut transition,
+                    event,
+                    windows.as_deref(),
+                    &mut materials,
+                );
+            }
+        }
+    }
+
+    for event in transition_events.iter() {
+        if transition.is_running() {
+            debug!(
+                "Already in a transition, queued the change to {:?}",
+                event.to
+            );
+            transition.queued = Some(*event);
+            continue;
+        }
+
+        start_transition(
+            &mut commands,
+            &mut transition,
+            *event,
+            windows.as_deref(),
+            &mut materials,
+        );
+    }
+}
+
+fn start_transition(
+    commands: &mut Commands,
+    transition: &mut ScreenTransition,
+    event: TransitionEvent,
+    windows: Option<&Windows>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    transition.phase = TransitionPhase::Out {
+        to: event.to,
+        progress: 0.,
+    };
+    transition.effect = event.effect;
+    transition.color = event.color;
+    respawn_overlay(commands, transition, windows, materials);
+}
+
+/// Covers the screen at once, the loading screen is gone before anything could be played
+fn reveal_after_loading(
+    mut commands: Commands,
+    clear_color: Option<Res<ClearColor>>,
+    windows: Option<Res<Windows>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut transition: ResMut<ScreenTransition>,
+) {
+    transition.phase = TransitionPhase::In { progress: 0. };
+    transition.effect = TransitionEffect::Fade;
+    transition.color = clear_color.map_or(Color::BLACK, |clear_color| clear_color.0);
+    respawn_overlay(
+        &mut commands,
+        &mut transition,
+        windows.as_deref(),
+        &mut materials,
+    );
+}
+
+fn update_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<UiSettings>,
+    windows: Option<Res<Windows>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut state: ResMut<State<GameState>>,
+    mut transition: ResMut<ScreenTransition>,
+) {
+    let step = if settings.transition_time > 0. {
+        time.delta_seconds() / settings.transition_time
+    } else {
+        1.
+    };
+
+    match transition.phase {
+        TransitionPhase::Idle => {}
+        TransitionPhase::Out { to, progress } if progress < 1. => {
+            transition.phase = TransitionPhase::Out {
+                to,
+                progress: (progress + step).min(1.),
+            };
+        }
+        TransitionPhase::Out { to, .. } => match state.set(to) {
+            Ok(()) => transition.phase = TransitionPhase::Waiting { to },
+            Err(err) => {
+                error!("Could not switch to {:?}: {:?}", to, err);
+                transition.phase = TransitionPhase::In { progress: 0. };
+            }
+        },
+        TransitionPhase::Waiting { to } => {
+            if *state.current() == to {
+                transition.phase = TransitionPhase::In { progress: 0. };
+                // Screens spawned by `on_enter` would be drawn on top of the older overlay
+                respawn_overlay(
+                    &mut commands,
+                    &mut transition,
+                    windows.as_deref(),
+                    &mut materials,
+                );
+            }
+        }
+        TransitionPhase::In { progress } if progress < 1. => {
+            transition.phase = TransitionPhase::In {
+                progress: (progress + step).min(1.),
+            };
+        }
+        TransitionPhase::In { .. } => {
+            transition.phase = TransitionPhase::Idle;
+            if let Some(overlay) = transition.overlay.take() {
+                commands.entity(overlay).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn respawn_overlay(
+    commands: &mut Commands,
+    transition: &mut ScreenTransition,
+    windows: Option<&Windows>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    if let Some(overlay) = transition.overlay.take() {
+        commands.entity(overlay).despawn_recursive();
+    }
+
+    let mut parts = Vec::new();
+    match transition.effect {
+        TransitionEffect::Fade => {
+            parts.push((OverlayPart::Fade, 0., 0., 100., 100.));
+        }
+        TransitionEffect::Dissolve => {
+            let width = 100. / DISSOLVE_COLUMNS as f32;
+            let height = 100. / DISSOLVE_ROWS as f32;
+
+            for row in 0..DISSOLVE_ROWS {
+                for column in 0..DISSOLVE_COLUMNS {
+                    let threshold = dissolve_threshold(row * DISSOLVE_COLUMNS + column);
+                    parts.push((
+                        OverlayPart::Cell { threshold },
+                        column as f32 * width,
+                        row as f32 * height,
+                        width,
+                        height,
+                    ));
+                }
+            }
+        }
+        TransitionEffect::Iris => {
+            let height = 100. / IRIS_ROWS as f32;
+
+            for row in 0..IRIS_ROWS {
+                for left in [true, false] {
+                    // The width is set by `cover`
+                    parts.push((
+                        OverlayPart::IrisBar { row, left },
+                        0.,
+                        row as f32 * height,
+                        0.,
+                        height,
+                    ));
+                }
+            }
+        }
+    }
+
+    // Spawned already covering the screen, a frame without it would show the next state
+    let coverage = transition.coverage();
+    let aspect = screen_aspect(windows);
+    let color_material = materials.add(transition.color.into());
+    let fade_material = materials.add(fade_color(transition.color, coverage).into());
+
+    let overlay = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for (part, left, bottom, width, height) in parts {
+                let mut style = Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Percent(left),
+                        bottom: Val::Percent(bottom),
+                        ..Default::default()
+                    },
+                    // Rounding errors would leave gaps between the cells
+                    size: Size::new(Val::Percent(width + 0.1), Val::Percent(height + 0.1)),
+                    ..Default::default()
+                };
+                let mut visible = Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                };
+                cover(part, coverage, aspect, &mut style, &mut visible);
+
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style,
+                        material: match part {
+                            OverlayPart::Fade => fade_material.clone(),
+                            _ => color_material.clone(),
+                        },
+                        visible,
+                        ..Default::default()
+                    })
+                    .insert(part);
+            }
+        })
+        .id();
+
+    transition.overlay = Some(overlay);
+    debug!("Started a {:?} transition", transition.effect);
+}
+
+fn draw_transition(
+    transition: Res<ScreenTransition>,
+    windows: Option<Res<Windows>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut part_query: Query<(
+        &OverlayPart,
+        &mut Style,
+        &mut Visible,
+        &Handle<ColorMaterial>,
+    )>,
+) {
+    if !transition.is_running() {
+        return;
+    }
+
+    let coverage = transition.coverage();
+    let aspect = screen_aspect(windows.as_deref());
+
+    for (part, mut style, mut visible, material) in part_query.iter_mut() {
+        cover(*part, coverage, aspect, &mut style, &mut visible);
+
+        if let OverlayPart::Fade = part {
+            if let Some(material) = materials.get_mut(material) {
+                material.color = fade_color(transition.color, coverage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dissolve_threshold, iris_bar_width, DISSOLVE_COLUMNS, DISSOLVE_ROWS, IRIS_ROWS};
+
+    #[test]
+    fn every_dissolve_cell_has_its_own_threshold() {
+        let cells = DISSOLVE_COLUMNS * DISSOLVE_ROWS;
+        let mut thresholds: Vec<f32> = (0..cells).map(dissolve_threshold).collect();
+        thresholds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        thresholds.dedup();
+
+        assert_eq!(thresholds.len(), cells);
+        assert_eq!(thresholds[0], 0.);
+        assert!(thresholds[cells - 1] < 1.);
+    }
+
+    #[test]
+    fn iris_opens_and_closes_completely() {
+        for row in 0..IRIS_ROWS {
+            assert_eq!(iris_bar_width(row, 0., 16. / 9.), 0.);
+            assert_eq!(iris_bar_width(row, 1., 16. / 9.), 50.);
+        }
+
+        // Half way the middle of the screen is still open, the top is not
+        let middle = iris_bar_width(IRIS_ROWS / 2, 0.5, 16. / 9.);
+        let top = iris_bar_width(IRIS_ROWS - 1, 0.5, 16. / 9.);
+        assert!(middle < top);
+        assert!(middle < 50.);
+    }
+}
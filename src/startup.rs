@@ -2,7 +2,8 @@ use bevy::prelude::*;
 use bevy_loading::prelude::AssetsLoading;
 
 use crate::{
-    camera::Free2DCamera,
+    camera::{CameraMode, CameraShake, Free2DCamera},
+    cli::LaunchOptions,
     utils::{AsepriteTextureAtlasConfiguration, AsepriteTileAtlasBundle},
     GameAssets,
 };
@@ -23,8 +24,14 @@ fn load_assets(
     asset_server: ResMut<AssetServer>,
     texture_atlas_assets: ResMut<Assets<TextureAtlas>>,
     mut loading: ResMut<AssetsLoading>,
+    launch_options: Option<Res<LaunchOptions>>,
 ) {
-    asset_server.watch_for_changes().unwrap();
+    let hot_reload = launch_options.map_or(true, |options| options.hot_reload);
+    if hot_reload {
+        if let Err(err) = asset_server.watch_for_changes() {
+            error!("Could not watch the assets for changes: {:?}", err);
+        }
+    }
 
     let world_sprites = asset_server.load("world.aseprite");
     let world_tile_atlas = texture_atlas_assets.get_handle(Handle::<TextureAtlas>::default());
@@ -46,6 +53,7 @@ fn load_assets(
         world_sprites,
         world_tile_atlas,
         ui_sprites: asset_server.load("ui.aseprite"),
+        entity_sprites: asset_server.load("entities.aseprite"),
         main_font: asset_server.load("PressStart2P-Regular.ttf"),
     };
 
@@ -57,7 +65,15 @@ fn load_assets(
 fn spawn_cameras(mut commands: Commands) {
     commands
         .spawn_bundle(OrthographicCameraBundle::new_2d())
-        .insert(Free2DCamera::new(1.0).with_scale_range((1.)..=4.));
+        .insert(
+            Free2DCamera::new(1.0)
+                .with_scale_range((1.)..=4.)
+                .with_zoom_smooth_time(0.1)
+                .with_limits_transition_time(0.5)
+                .with_virtual_resolution(Vec2::new(320., 180.))
+                .with_mode(CameraMode::Follow),
+        )
+        .insert(CameraShake::default());
 
     commands.spawn_bundle(UiCameraBundle::default());
 }
@@ -0,0 +1,173 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bevy::{audio::AudioSource, prelude::*};
+
+use super::{TestApp, LOAD_TIMEOUT};
+use crate::{
+    audio::{AudioBackend, MusicPlayer, PlaybackId, SoundEffect, SoundEvent},
+    stages::GameState,
+};
+
+fn music_track(test_app: &TestApp) -> Option<String> {
+    test_app
+        .app
+        .world
+        .get_resource::<MusicPlayer>()
+        .unwrap()
+        .track()
+        .map(str::to_owned)
+}
+
+fn music_player(test_app: &TestApp) -> &MusicPlayer {
+    test_app.app.world.get_resource::<MusicPlayer>().unwrap()
+}
+
+fn music_volume(test_app: &TestApp, playback: PlaybackId) -> Option<f32> {
+    test_app
+        .app
+        .world
+        .get_resource::<Box<dyn AudioBackend>>()
+        .unwrap()
+        .volume(playback)
+}
+
+/// Fails to play anything, like a device that can not decode the files
+#[derive(Debug, Default)]
+struct BrokenAudioBackend {
+    looped_plays: Arc<AtomicUsize>,
+}
+
+impl AudioBackend for BrokenAudioBackend {
+    fn play(&mut self, _source: &AudioSource, looped: bool, _volume: f32) -> Option<PlaybackId> {
+        if looped {
+            self.looped_plays.fetch_add(1, Ordering::SeqCst);
+        }
+        None
+    }
+
+    fn set_volume(&mut self, _playback: PlaybackId, _volume: f32) {}
+
+    fn stop(&mut self, _playback: PlaybackId) {}
+
+    fn is_playing(&self, _playback: PlaybackId) -> bool {
+        false
+    }
+
+    fn volume(&self, _playback: PlaybackId) -> Option<f32> {
+        None
+    }
+}
+
+#[test]
+fn music_follows_the_game_state() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+    test_app.step(1);
+    assert_eq!(music_track(&test_app).as_deref(), Some("music/menu.mp3"));
+
+    test_app.submit_command("config set audio.music.level_select music/select.mp3");
+    test_app.step(3);
+
+    test_app.set_state(GameState::LevelSelect);
+    test_app.step(2);
+    assert_eq!(music_track(&test_app).as_deref(), Some("music/select.mp3"));
+
+    test_app.set_state(GameState::MainMenu);
+    test_app.step(2);
+    assert_eq!(music_track(&test_app).as_deref(), Some("music/menu.mp3"));
+}
+
+#[test]
+fn jumping_plays_a_sound_in_the_level() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    // Land on the ground first
+    test_app.step(30);
+
+    let mut reader = test_app
+        .app
+        .world
+        .get_resource::<Events<SoundEvent>>()
+        .unwrap()
+        .get_reader();
+
+    test_app.press(KeyCode::Space);
+    test_app.step(2);
+    test_app.release(KeyCode::Space);
+
+    let events = test_app
+        .app
+        .world
+        .get_resource::<Events<SoundEvent>>()
+        .unwrap();
+    let jump = reader
+        .iter(events)
+        .find(|event| event.effect == SoundEffect::Jump)
+        .expect("jumping sends a sound event");
+    assert!(jump.emitter.is_some());
+}
+
+#[test]
+fn music_crossfades_into_the_level() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    // The menu music is fully faded in after the crossfade time of a second
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .get_resource::<MusicPlayer>()
+        .unwrap()
+        .playback()
+        .is_some()));
+    test_app.step(70);
+
+    let menu = music_player(&test_app).playback().unwrap();
+    assert_eq!(music_volume(&test_app, menu), Some(0.7));
+
+    test_app.start_level("Level_0");
+    assert!(test_app.wait_for_state(GameState::Running));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .get_resource::<MusicPlayer>()
+        .unwrap()
+        .playback()
+        .map_or(false, |playback| playback != menu)));
+    test_app.step(30);
+
+    // Half way through, the menu music is fading out while the level music fades in
+    let level = music_player(&test_app).playback().unwrap();
+    let fading: Vec<_> = music_player(&test_app).fading_playbacks().collect();
+    assert_eq!(fading, vec![menu]);
+
+    let menu_volume = music_volume(&test_app, menu).unwrap();
+    let level_volume = music_volume(&test_app, level).unwrap();
+    assert!(menu_volume > 0. && menu_volume < 0.7, "{}", menu_volume);
+    assert!(level_volume > 0. && level_volume < 0.7, "{}", level_volume);
+
+    test_app.step(70);
+    assert_eq!(music_volume(&test_app, menu), None);
+    assert_eq!(music_volume(&test_app, level), Some(0.7));
+}
+
+#[test]
+fn music_that_can_not_be_played_is_tried_once() {
+    let looped_plays = Arc::new(AtomicUsize::new(0));
+
+    let mut test_app = TestApp::new();
+    test_app
+        .app
+        .world
+        .insert_resource::<Box<dyn AudioBackend>>(Box::new(BrokenAudioBackend {
+            looped_plays: looped_plays.clone(),
+        }));
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    assert!(test_app.run_until(LOAD_TIMEOUT, |_| looped_plays.load(Ordering::SeqCst) > 0));
+    test_app.step(30);
+
+    assert_eq!(looped_plays.load(Ordering::SeqCst), 1);
+    assert_eq!(music_track(&test_app).as_deref(), Some("music/menu.mp3"));
+    assert_eq!(music_player(&test_app).playback(), None);
+}
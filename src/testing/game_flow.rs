@@ -0,0 +1,320 @@
+use bevy::prelude::*;
+
+use super::{TestApp, LOAD_TIMEOUT};
+use crate::{
+    actions::{Action, Binding, InputBindings},
+    console::Console,
+    debug::DebugOverlay,
+    progression::Progression,
+    stages::{GameState, ScreenTransition, TransitionEffect, TransitionEvent},
+    world::{CollisionMap, Exit, Level, LevelName, Player, Seed},
+};
+
+fn player_position(world: &mut World) -> Option<Vec3> {
+    world
+        .query_filtered::<&Transform, With<Player>>()
+        .iter(world)
+        .next()
+        .map(|transform| transform.translation)
+}
+
+fn transition(world: &mut World) -> &ScreenTransition {
+    world.get_resource::<ScreenTransition>().unwrap()
+}
+
+#[test]
+fn loading_ends_in_the_main_menu() {
+    let mut test_app = TestApp::new();
+    assert_eq!(test_app.state(), GameState::Loading);
+
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+}
+
+#[test]
+fn running_spawns_the_level_and_the_player() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    let levels: Vec<String> = test_app
+        .app
+        .world
+        .query_filtered::<&LevelName, (With<Level>, With<CollisionMap>)>()
+        .iter(&test_app.app.world)
+        .map(|name| name.0.clone())
+        .collect();
+
+    assert_eq!(levels, vec![String::from("Level_0")]);
+    assert_eq!(test_app.count::<Player>(), 1);
+}
+
+#[test]
+fn new_game_from_the_main_menu_spawns_the_first_level() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| {
+        world.query::<&Button>().iter(world).next().is_some()
+    }));
+
+    assert!(test_app.click("New Game"));
+    assert!(test_app.wait_for_state(GameState::Running));
+
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| {
+        world
+            .query_filtered::<(), (With<Level>, With<CollisionMap>)>()
+            .iter(world)
+            .next()
+            .is_some()
+    }));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| player_position(world).is_some()));
+}
+
+#[test]
+fn every_progression_level_has_a_start_and_enough_seeds() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    let levels = test_app
+        .app
+        .world
+        .get_resource::<Progression>()
+        .unwrap()
+        .levels()
+        .to_vec();
+    assert!(!levels.is_empty());
+
+    for level in levels {
+        let mut test_app = TestApp::new();
+        assert!(
+            test_app.start_level_and_wait_for_player(&level),
+            "{} has no Start entry",
+            level
+        );
+
+        let required_seeds: u32 = test_app
+            .app
+            .world
+            .query::<&Exit>()
+            .iter(&test_app.app.world)
+            .map(|exit| exit.required_seeds)
+            .sum();
+
+        assert!(required_seeds > 0, "{} has no exit", level);
+        assert!(
+            test_app.count::<Seed>() as u32 >= required_seeds,
+            "{} has fewer seeds than its exits require",
+            level
+        );
+    }
+}
+
+#[test]
+fn player_walks_while_the_key_is_held() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    // Land on the ground first
+    test_app.step(30);
+    let start = player_position(&mut test_app.app.world).unwrap();
+
+    test_app.press(KeyCode::D);
+    test_app.step(30);
+    test_app.release(KeyCode::D);
+    test_app.step(1);
+
+    let end = player_position(&mut test_app.app.world).unwrap();
+    assert!(end.x > start.x, "{} should be right of {}", end, start);
+}
+
+#[test]
+fn menu_back_leaves_the_level_select() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    test_app.set_state(GameState::LevelSelect);
+    test_app.step(2);
+    assert_eq!(test_app.state(), GameState::LevelSelect);
+
+    test_app.press(KeyCode::Escape);
+    test_app.step(2);
+    // The level select stays until the screen is covered
+    assert_eq!(test_app.state(), GameState::LevelSelect);
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+}
+
+#[test]
+fn closing_the_console_with_escape_stays_in_the_level_select() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    test_app.set_state(GameState::LevelSelect);
+    test_app.step(2);
+
+    let console_open =
+        |test_app: &TestApp| test_app.app.world.get_resource::<Console>().unwrap().open;
+
+    test_app.press(KeyCode::Grave);
+    test_app.step(1);
+    test_app.release(KeyCode::Grave);
+    test_app.step(1);
+    assert!(console_open(&test_app));
+
+    test_app.press(KeyCode::Escape);
+    test_app.step(1);
+    assert!(!console_open(&test_app));
+
+    // Still holding Escape does not count as pressing it in the level select
+    test_app.step(30);
+    assert_eq!(test_app.state(), GameState::LevelSelect);
+    assert!(!transition(&mut test_app.app.world).is_running());
+
+    test_app.release(KeyCode::Escape);
+    test_app.step(1);
+    test_app.press(KeyCode::Escape);
+    test_app.step(1);
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+}
+
+#[test]
+fn transitions_cover_the_state_change() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| !transition(world).is_running()));
+
+    test_app
+        .app
+        .world
+        .get_resource_mut::<Events<TransitionEvent>>()
+        .unwrap()
+        .send(TransitionEvent::new(
+            GameState::LevelSelect,
+            TransitionEffect::Dissolve,
+        ));
+
+    assert!(test_app.wait_for_state(GameState::LevelSelect));
+    assert_eq!(transition(&mut test_app.app.world).coverage(), 1.);
+
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| !transition(world).is_running()));
+    assert_eq!(test_app.state(), GameState::LevelSelect);
+}
+
+#[test]
+fn requests_during_a_transition_wait_for_it() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| !transition(world).is_running()));
+
+    let request = |test_app: &mut TestApp, to: GameState| {
+        test_app
+            .app
+            .world
+            .get_resource_mut::<Events<TransitionEvent>>()
+            .unwrap()
+            .send(TransitionEvent::new(to, TransitionEffect::Fade));
+    };
+
+    request(&mut test_app, GameState::LevelSelect);
+    test_app.step(2);
+    request(&mut test_app, GameState::MainMenu);
+    test_app.step(1);
+
+    assert!(test_app.wait_for_state(GameState::LevelSelect));
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| !transition(world).is_running()));
+
+    // Only the latest request is kept, and it is dropped as the game is there already
+    request(&mut test_app, GameState::LevelSelect);
+    test_app.step(2);
+    request(&mut test_app, GameState::MainMenu);
+    request(&mut test_app, GameState::LevelSelect);
+    test_app.step(1);
+
+    assert!(test_app.wait_for_state(GameState::LevelSelect));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| !transition(world).is_running()));
+    test_app.step(2);
+    assert!(!transition(&mut test_app.app.world).is_running());
+    assert_eq!(test_app.state(), GameState::LevelSelect);
+}
+
+#[test]
+fn f3_toggles_the_debug_overlay() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+    let entities = test_app.app.world.entities().len();
+
+    test_app.press(KeyCode::F3);
+    test_app.step(2);
+    test_app.release(KeyCode::F3);
+    test_app.step(1);
+    assert!(
+        test_app
+            .app
+            .world
+            .get_resource::<DebugOverlay>()
+            .unwrap()
+            .enabled
+    );
+    assert!(test_app.app.world.entities().len() > entities);
+
+    test_app.press(KeyCode::F3);
+    test_app.step(2);
+    assert!(
+        !test_app
+            .app
+            .world
+            .get_resource::<DebugOverlay>()
+            .unwrap()
+            .enabled
+    );
+}
+
+#[test]
+fn console_commands_move_the_player_and_switch_states() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    test_app.submit_command("tp 40 200");
+    test_app.step(1);
+    let position = player_position(&mut test_app.app.world).unwrap();
+    assert!(
+        (position.x - 40.).abs() < 1.,
+        "{} should be at x 40",
+        position
+    );
+
+    test_app.submit_command("state MainMenu");
+    test_app.step(2);
+    // The level stays until the screen is covered
+    assert_eq!(test_app.state(), GameState::Running);
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+}
+
+#[test]
+fn rebinds_from_the_console_survive_config_changes() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    test_app.submit_command("bind Jump");
+    test_app.step(2);
+    test_app.press(KeyCode::W);
+    test_app.step(1);
+    test_app.release(KeyCode::W);
+
+    let jump_bindings = |test_app: &TestApp| {
+        test_app
+            .app
+            .world
+            .get_resource::<InputBindings>()
+            .unwrap()
+            .get(Action::Jump)
+            .to_vec()
+    };
+    assert_eq!(jump_bindings(&test_app), [Binding::Key(KeyCode::W)]);
+
+    test_app.submit_command(r#"config set input.jump ["Key:K"]"#);
+    test_app.step(3);
+    assert_eq!(jump_bindings(&test_app), [Binding::Key(KeyCode::W)]);
+
+    test_app.submit_command("bind Jump reset");
+    test_app.step(1);
+    assert_eq!(jump_bindings(&test_app), [Binding::Key(KeyCode::K)]);
+}
@@ -0,0 +1,220 @@
+//! A headless version of the game to write tests against
+//!
+//! [`TestApp`] runs the [`GamePlugins`](crate::GamePlugins) on top of the
+//! [`HeadlessPlugins`], without a window or a GPU. Every frame advances the clock by
+//! [`FRAME_TIME`], so the game behaves the same no matter how fast the test runs.
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::AssetServerSettings,
+    ecs::component::Component,
+    input::{keyboard::KeyboardInput, ElementState},
+    log::LogPlugin,
+    prelude::*,
+};
+
+use crate::{
+    cli::HeadlessPlugins,
+    console::Console,
+    progression::SavePath,
+    stages::GameState,
+    world::{DefaultLevels, Player},
+};
+
+mod audio;
+mod game_flow;
+mod particles;
+mod replay;
+
+/// How much time passes each frame
+pub const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// How long to wait for the assets to be loaded from disk
+pub const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(StageLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+struct TestStage;
+
+pub struct TestApp {
+    pub app: App,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let mut app = App::new();
+
+        app.insert_resource(AssetServerSettings {
+            asset_folder: concat!(env!("CARGO_MANIFEST_DIR"), "/assets").to_owned(),
+        })
+        // Tests start without progress and never touch the save file of the player
+        .insert_resource(SavePath(None))
+        // Every test builds its own app, but logging can only be set up once per process
+        .add_plugins_with(HeadlessPlugins, |group| group.disable::<LogPlugin>())
+        .add_plugins(crate::GamePlugins)
+        .add_stage_after(
+            CoreStage::First,
+            TestStage,
+            SystemStage::single_threaded().with_system(advance_time),
+        );
+
+        TestApp { app }
+    }
+
+    pub fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Steps frames until `condition` holds, returns false if that took longer than `timeout`
+    ///
+    /// The timeout is in real time, as loading assets does not depend on the frames.
+    pub fn run_until(
+        &mut self,
+        timeout: Duration,
+        mut condition: impl FnMut(&mut World) -> bool,
+    ) -> bool {
+        let start = Instant::now();
+
+        while !condition(&mut self.app.world) {
+            if start.elapsed() > timeout {
+                return false;
+            }
+
+            self.app.update();
+            // Give the asset loading tasks a chance to run
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        true
+    }
+
+    pub fn state(&self) -> GameState {
+        *self
+            .app
+            .world
+            .get_resource::<State<GameState>>()
+            .expect("The StagesPlugin adds the GameState")
+            .current()
+    }
+
+    /// Switches the state the next frame, without the transition the menus play
+    pub fn set_state(&mut self, state: GameState) {
+        self.app
+            .world
+            .get_resource_mut::<State<GameState>>()
+            .expect("The StagesPlugin adds the GameState")
+            .set(state)
+            .unwrap();
+    }
+
+    pub fn wait_for_state(&mut self, state: GameState) -> bool {
+        self.run_until(LOAD_TIMEOUT, |world| {
+            world
+                .get_resource::<State<GameState>>()
+                .map_or(false, |current| *current.current() == state)
+        })
+    }
+
+    /// Starts the game at `level`, like picking it in the level select does
+    pub fn start_level(&mut self, level: &str) {
+        self.app
+            .world
+            .insert_resource(DefaultLevels(vec![level.to_owned()]));
+        self.set_state(GameState::Running);
+    }
+
+    /// Starts the game at `level` once the main menu is shown, returns false if that or the
+    /// player of the level took longer than [`LOAD_TIMEOUT`]
+    pub fn start_level_and_wait_for_player(&mut self, level: &str) -> bool {
+        if !self.wait_for_state(GameState::MainMenu) {
+            return false;
+        }
+
+        self.start_level(level);
+        self.run_until(LOAD_TIMEOUT, |world| {
+            world
+                .query_filtered::<(), With<Player>>()
+                .iter(world)
+                .next()
+                .is_some()
+        })
+    }
+
+    /// Clicks the button showing `label`, returns false if there is no such button
+    pub fn click(&mut self, label: &str) -> bool {
+        let world = &mut self.app.world;
+
+        let text_entity = world
+            .query::<(Entity, &Text)>()
+            .iter(world)
+            .find(|(_, text)| text.sections.iter().any(|section| section.value == label))
+            .map(|(entity, _)| entity);
+
+        let mut entity = if let Some(entity) = text_entity {
+            entity
+        } else {
+            return false;
+        };
+
+        // The label is a child of the button
+        loop {
+            if world.get::<Button>(entity).is_some() {
+                if let Some(mut interaction) = world.get_mut::<Interaction>(entity) {
+                    *interaction = Interaction::Clicked;
+                    return true;
+                }
+            }
+
+            entity = if let Some(parent) = world.get::<Parent>(entity) {
+                parent.0
+            } else {
+                return false;
+            };
+        }
+    }
+
+    pub fn press(&mut self, key: KeyCode) {
+        self.send_key(key, ElementState::Pressed);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.send_key(key, ElementState::Released);
+    }
+
+    fn send_key(&mut self, key: KeyCode, state: ElementState) {
+        self.app
+            .world
+            .get_resource_mut::<Events<KeyboardInput>>()
+            .expect("The InputPlugin adds the keyboard events")
+            .send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+            });
+    }
+
+    /// Runs a console command at the end of the next frame
+    pub fn submit_command(&mut self, line: &str) {
+        self.app
+            .world
+            .get_resource_mut::<Console>()
+            .expect("The ConsolePlugin adds the console")
+            .submit(line);
+    }
+
+    pub fn count<C: Component>(&mut self) -> usize {
+        self.app
+            .world
+            .query_filtered::<(), With<C>>()
+            .iter(&self.app.world)
+            .count()
+    }
+}
+
+/// Replaces the measured time of the frame with [`FRAME_TIME`]
+fn advance_time(mut time: ResMut<Time>) {
+    let last_update = time.last_update().unwrap_or_else(Instant::now);
+    time.update_with_instant(last_update + FRAME_TIME);
+}
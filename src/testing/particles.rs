@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+
+use super::{TestApp, LOAD_TIMEOUT};
+use crate::{
+    particles::{Particle, ParticleEmitter, ParticleEvent},
+    world::Player,
+};
+
+/// The `Emitter` entities of `Level_0` and the pool size of their effect
+const LEVEL_0_EMITTERS: usize = 3;
+const LAVA_BUBBLES_POOL: usize = 8;
+const POOF_POOL: usize = 8;
+
+#[test]
+fn level_emitters_fill_their_pools() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .query_filtered::<(), With<Particle>>()
+        .iter(world)
+        .count()
+        == LEVEL_0_EMITTERS * LAVA_BUBBLES_POOL));
+    assert_eq!(test_app.count::<ParticleEmitter>(), LEVEL_0_EMITTERS);
+}
+
+fn level(test_app: &mut TestApp) -> Entity {
+    test_app
+        .app
+        .world
+        .query_filtered::<&Parent, With<Player>>()
+        .iter(&test_app.app.world)
+        .next()
+        .unwrap()
+        .0
+}
+
+fn burst(test_app: &mut TestApp, level: Entity, position: Vec2) {
+    test_app
+        .app
+        .world
+        .get_resource_mut::<Events<ParticleEvent>>()
+        .unwrap()
+        .send(ParticleEvent::in_level(
+            "particles/poof.emitter",
+            level,
+            position,
+        ));
+}
+
+#[test]
+fn bursts_of_an_effect_share_one_pool() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+    let level = level(&mut test_app);
+
+    // The player lands first, its dust has an emitter of its own
+    test_app.step(60);
+    let emitters = test_app.count::<ParticleEmitter>();
+    let particles = test_app.count::<Particle>();
+
+    burst(&mut test_app, level, Vec2::new(64., 64.));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .query_filtered::<(), With<Particle>>()
+        .iter(world)
+        .count()
+        == particles + POOF_POOL));
+
+    // Bursts after the first one died out reuse its sprites
+    test_app.step(60);
+    let entities = test_app.app.world.entities().len();
+    burst(&mut test_app, level, Vec2::new(96., 64.));
+    test_app.step(60);
+    burst(&mut test_app, level, Vec2::new(128., 64.));
+    test_app.step(60);
+
+    assert_eq!(test_app.count::<ParticleEmitter>(), emitters + 1);
+    assert_eq!(test_app.app.world.entities().len(), entities);
+}
+
+#[test]
+fn overlapping_bursts_grow_the_pool() {
+    let mut test_app = TestApp::new();
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+    let level = level(&mut test_app);
+
+    test_app.step(60);
+    let particles = test_app.count::<Particle>();
+
+    burst(&mut test_app, level, Vec2::new(64., 64.));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .query_filtered::<(), With<Particle>>()
+        .iter(world)
+        .count()
+        == particles + POOF_POOL));
+
+    // The first burst is still alive, the second one gets sprites of its own
+    test_app.step(5);
+    let second = Vec2::new(256., 64.);
+    burst(&mut test_app, level, second);
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .query_filtered::<(&Transform, &Visible), With<Particle>>()
+        .iter(world)
+        .filter(|(transform, visible)| {
+            visible.is_visible && transform.translation.truncate().distance(second) < 32.
+        })
+        .count()
+        == POOF_POOL));
+    assert_eq!(test_app.count::<Particle>(), particles + 2 * POOF_POOL);
+}
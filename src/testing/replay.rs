@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use super::{TestApp, LOAD_TIMEOUT};
+use crate::{
+    replay::{Ghost, Replay, ReplayMode, ReplayOutcome},
+    stages::GameState,
+};
+
+#[test]
+fn recorded_runs_replay_the_same_and_show_as_ghosts() {
+    let path = std::env::temp_dir().join(format!("replay-{}.ron", std::process::id()));
+
+    let mut test_app = TestApp::new();
+    test_app
+        .app
+        .world
+        .insert_resource(ReplayMode::Record(path.clone()));
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+
+    test_app.step(20);
+    test_app.press(KeyCode::D);
+    test_app.step(25);
+    test_app.press(KeyCode::Space);
+    test_app.step(10);
+    test_app.release(KeyCode::Space);
+    test_app.release(KeyCode::D);
+    test_app.step(20);
+
+    test_app.set_state(GameState::MainMenu);
+    test_app.step(2);
+
+    let replay = Replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(replay.level, "Level_0");
+    assert!(replay.final_player.is_some());
+
+    let mut test_app = TestApp::new();
+    test_app
+        .app
+        .world
+        .insert_resource(ReplayMode::Verify(replay.clone()));
+    assert!(test_app.wait_for_state(GameState::MainMenu));
+
+    test_app.start_level("Level_0");
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .get_resource::<ReplayOutcome>()
+        .is_some()));
+
+    let outcome = test_app.app.world.get_resource::<ReplayOutcome>().unwrap();
+    assert_eq!(outcome.0, Ok(()));
+
+    let mut test_app = TestApp::new();
+    test_app
+        .app
+        .world
+        .insert_resource(ReplayMode::Ghost(replay));
+    assert!(test_app.start_level_and_wait_for_player("Level_0"));
+    assert!(test_app.run_until(LOAD_TIMEOUT, |world| world
+        .query_filtered::<(), With<Ghost>>()
+        .iter(world)
+        .next()
+        .is_some()));
+}
+
+#[test]
+fn shipped_replays_verify() {
+    let folder = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/replays");
+    let mut paths: Vec<_> = std::fs::read_dir(folder)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |extension| extension == "ron")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let replay = Replay::load(&path).unwrap();
+        let level = replay.level.clone();
+
+        let mut test_app = TestApp::new();
+        test_app
+            .app
+            .world
+            .insert_resource(ReplayMode::Verify(replay));
+        assert!(test_app.wait_for_state(GameState::MainMenu));
+
+        test_app.start_level(&level);
+        assert!(
+            test_app.run_until(LOAD_TIMEOUT, |world| world
+                .get_resource::<ReplayOutcome>()
+                .is_some()),
+            "{} did not finish",
+            path.display()
+        );
+
+        let outcome = test_app.app.world.get_resource::<ReplayOutcome>().unwrap();
+        assert_eq!(outcome.0, Ok(()), "{} diverged", path.display());
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*, transform::TransformSystem};
+
+use crate::{
+    game_config::{changed_config, ConfigChanged},
+    GameAssets,
+};
+
+/// Ticks per second until `game.config` is loaded
+const DEFAULT_TICK_RATE: f64 = 60.;
+const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// Runs the gameplay at a fixed rate, independent of the frame rate
+///
+/// Systems in the [`FixedTickStage`] run zero or more times per frame, after
+/// [`FixedTickSystems::Input`], and should use [`FixedTick::delta_seconds`] instead of [`Time`].
+/// Entities with an [`Interpolated`] component are drawn between their last two ticks, so they
+/// move smoothly at any frame rate.
+#[derive(Debug, Default)]
+pub struct FixedTickPlugin;
+
+impl Plugin for FixedTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FixedTick>();
+        app.add_stage_after(
+            CoreStage::Update,
+            FixedTickStage,
+            SystemStage::parallel().with_run_criteria(run_fixed_ticks),
+        );
+        app.add_system_to_stage(
+            FixedTickStage,
+            store_previous_translations.label(FixedTickSystems::Prepare),
+        );
+        app.add_system_to_stage(CoreStage::PreUpdate, restore_simulated_translations);
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            interpolate_translations.before(TransformSystem::TransformPropagate),
+        );
+        app.add_system(load_fixed_tick_from_config);
+    }
+}
+
+#[derive(StageLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct FixedTickStage;
+
+#[derive(SystemLabel, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum FixedTickSystems {
+    /// Runs first in every tick
+    Prepare,
+    /// Decides the actions of the tick, e.g. by playing back a replay
+    Input,
+}
+
+/// The clock of the [`FixedTickStage`]
+#[derive(Debug, Clone)]
+pub struct FixedTick {
+    /// Seconds simulated by every tick
+    step: f64,
+    /// How many ticks a slow frame may run to catch up, the time left after that is dropped
+    pub max_ticks_per_frame: u32,
+    accumulator: f64,
+    ticks_this_frame: u32,
+    /// Whether the stage is between two ticks of the same frame
+    looping: bool,
+}
+
+impl Default for FixedTick {
+    fn default() -> Self {
+        FixedTick::new(DEFAULT_TICK_RATE, DEFAULT_MAX_TICKS_PER_FRAME)
+    }
+}
+
+impl FixedTick {
+    pub fn new(rate: f64, max_ticks_per_frame: u32) -> Self {
+        FixedTick {
+            step: 1. / rate.max(1.),
+            max_ticks_per_frame,
+            accumulator: 0.,
+            ticks_this_frame: 0,
+            looping: false,
+        }
+    }
+
+    /// Ticks per second
+    pub fn rate(&self) -> f64 {
+        1. / self.step
+    }
+
+    pub fn set_rate(&mut self, rate: f64) {
+        self.step = 1. / rate.max(1.);
+    }
+
+    /// The time simulated by one tick, use it instead of [`Time::delta_seconds`]
+    pub fn delta_seconds(&self) -> f32 {
+        self.step as f32
+    }
+
+    /// How far the frame is between the last tick and the next one, from `0.` to `1.`
+    pub fn overstep(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0., 1.) as f32
+    }
+}
+
+fn run_fixed_ticks(time: Res<Time>, mut fixed_tick: ResMut<FixedTick>) -> ShouldRun {
+    // The criteria is checked again after every tick, only the first check adds the frame time
+    if !fixed_tick.looping {
+        fixed_tick.accumulator += time.delta_seconds_f64();
+        fixed_tick.ticks_this_frame = 0;
+    }
+
+    if fixed_tick.accumulator >= fixed_tick.step {
+        if fixed_tick.ticks_this_frame < fixed_tick.max_ticks_per_frame {
+            fixed_tick.accumulator -= fixed_tick.step;
+            fixed_tick.ticks_this_frame += 1;
+            fixed_tick.looping = true;
+
+            return ShouldRun::YesAndCheckAgain;
+        }
+
+        let dropped = (fixed_tick.accumulator / fixed_tick.step) as u64;
+        debug!("Could not keep up, dropped {} ticks", dropped);
+        fixed_tick.accumulator %= fixed_tick.step;
+    }
+
+    fixed_tick.looping = false;
+    ShouldRun::No
+}
+
+/// Draws the entity between its translations of the last two ticks
+///
+/// Systems outside the [`FixedTickStage`] see the simulated translation, only the rendering
+/// (and everything after the transform propagation) sees the interpolated one.
+#[derive(Debug, Default, Clone)]
+pub struct Interpolated {
+    previous: Option<Vec3>,
+    simulated: Option<Vec3>,
+}
+
+fn store_previous_translations(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = Some(transform.translation);
+    }
+}
+
+fn restore_simulated_translations(mut query: Query<(&mut Transform, &Interpolated)>) {
+    for (mut transform, interpolated) in query.iter_mut() {
+        if let Some(simulated) = interpolated.simulated {
+            transform.translation = simulated;
+        }
+    }
+}
+
+fn interpolate_translations(
+    fixed_tick: Res<FixedTick>,
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+) {
+    let overstep = fixed_tick.overstep();
+
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        interpolated.simulated = Some(transform.translation);
+
+        if let Some(previous) = interpolated.previous {
+            transform.translation = previous.lerp(transform.translation, overstep);
+        }
+    }
+}
+
+fn load_fixed_tick_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut fixed_tick: ResMut<FixedTick>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    fixed_tick.set_rate(config.tick.rate as f64);
+    fixed_tick.max_ticks_per_frame = config.tick.max_ticks_per_frame as u32;
+}
@@ -6,22 +6,65 @@ use bevy::{
 };
 use bevy_spicy_aseprite::{AsepriteImage, AsepriteSlice, AsepriteSliceName};
 
-use crate::utils::GetSubHandle;
+use crate::{
+    audio::{SoundEffect, SoundEvent},
+    game_config::{changed_config, ConfigChanged},
+    utils::{GetSubHandle, SubHandles},
+    GameAssets,
+};
 
 #[derive(Debug, Default)]
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(update_nine_patch_info)
+        app.init_resource::<UiSettings>()
+            .add_system(load_ui_settings_from_config)
+            .add_system(update_nine_patch_info)
             .add_system(update_nine_patch_image)
             .add_system(update_nine_patch_button);
     }
 }
 
+/// Read from the `[ui]` section of `game.config`, applies to menus created afterwards
+#[derive(Debug, Clone)]
+pub struct UiSettings {
+    /// Multiplies the font size of the menus
+    pub scale: f32,
+    /// Seconds each half of a screen transition takes
+    pub transition_time: f32,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        UiSettings {
+            scale: 1.,
+            transition_time: 0.4,
+        }
+    }
+}
+
+fn load_ui_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<UiSettings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    settings.scale = config.ui.scale as f32;
+    settings.transition_time = config.ui.transition_time as f32;
+}
+
 fn update_nine_patch_image(
     aseprite_assets: Res<Assets<AsepriteImage>>,
-    mut texture_assets: ResMut<Assets<Texture>>,
+    mut sub_handles: ResMut<SubHandles>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut nine_patch_query: Query<
         (
@@ -48,12 +91,12 @@ fn update_nine_patch_image(
             return;
         };
 
-        *color_material = materials.add(ColorMaterial::texture(
-            aseprite_image_handle.get_sub_handle(
-                &slice.label_with_nine_slice(nine_patch.nine_patch),
-                &mut texture_assets,
-            ),
-        ));
+        *color_material = materials.add(ColorMaterial::texture(nine_slice_texture(
+            aseprite_image_handle,
+            slice,
+            nine_patch.nine_patch,
+            &mut sub_handles,
+        )));
     }
 }
 
@@ -218,12 +261,27 @@ fn update_nine_patch_info(
     }
 }
 
+fn nine_slice_texture(
+    aseprite_handle: &Handle<AsepriteImage>,
+    slice: &aseprite_reader::AsepriteSlice,
+    nine_slice: NineSlice,
+    sub_handles: &mut SubHandles,
+) -> Handle<Texture> {
+    match aseprite_handle.get_sub_handle(&slice.label_with_nine_slice(nine_slice), sub_handles) {
+        Ok(texture) => texture,
+        Err(err) => {
+            error!("Could not get the nine patch of {}: {}", slice.name, err);
+            Handle::default()
+        }
+    }
+}
+
 pub fn create_nine_patch<'w, 's, 'a, 'f>(
     commands: &'f mut ChildBuilder<'w, 's, 'a>,
     slice: &aseprite_reader::AsepriteSlice,
     aseprite_handle: Handle<AsepriteImage>,
     material_assets: &mut Assets<ColorMaterial>,
-    texture_assets: &mut Assets<Texture>,
+    sub_handles: &mut SubHandles,
     style: Option<Style>,
 ) -> EntityCommands<'w, 's, 'f> {
     let nine_patch_info = if let Some(nine_patch_info) = slice.nine_patch_info.as_ref() {
@@ -260,9 +318,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::TopLeft),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::TopLeft,
+                sub_handles,
             ))),
             // visible: visible.clone(),
             ..Default::default()
@@ -288,9 +348,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::TopRight),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::TopRight,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -317,9 +379,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::TopCenter),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::TopCenter,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -345,9 +409,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::BottomLeft),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::BottomLeft,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -373,9 +439,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::BottomRight),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::BottomRight,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -402,9 +470,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::BottomCenter),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::BottomCenter,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -431,9 +501,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::LeftCenter),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::LeftCenter,
+                sub_handles,
             ))),
             visible: visible.clone(),
             ..Default::default()
@@ -460,9 +532,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
                 },
                 ..Default::default()
             },
-            material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-                &slice.label_with_nine_slice(NineSlice::RightCenter),
-                texture_assets,
+            material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+                &aseprite_handle,
+                slice,
+                NineSlice::RightCenter,
+                sub_handles,
             ))),
             ..Default::default()
         })
@@ -484,9 +558,11 @@ pub fn create_nine_patch<'w, 's, 'a, 'f>(
             },
             ..style.unwrap_or_default()
         },
-        material: material_assets.add(ColorMaterial::texture(aseprite_handle.get_sub_handle(
-            &slice.label_with_nine_slice(NineSlice::Center),
-            texture_assets,
+        material: material_assets.add(ColorMaterial::texture(nine_slice_texture(
+            &aseprite_handle,
+            slice,
+            NineSlice::Center,
+            sub_handles,
         ))),
         visible,
         ..Default::default()
@@ -515,8 +591,15 @@ fn update_nine_patch_button(
         (Changed<Interaction>, With<Button>),
     >,
     mut nine_patch_query: Query<&mut NinePatch>,
+    mut sound_events: EventWriter<SoundEvent>,
 ) {
     for (interaction, nine_patch_button, children) in button_query.iter() {
+        match *interaction {
+            Interaction::Clicked => sound_events.send(SoundEvent::ui(SoundEffect::ButtonClick)),
+            Interaction::Hovered => sound_events.send(SoundEvent::ui(SoundEffect::ButtonHover)),
+            Interaction::None => {}
+        }
+
         match *interaction {
             Interaction::Clicked => children.iter().for_each(|entity| {
                 if let Some(pressed) = nine_patch_button.pressed.as_ref() {
@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
 use bevy::{
-    asset::{Asset, AssetPathId, HandleId, LabelId},
+    asset::{Asset, AssetPath, AssetServerSettings, FileAssetIo, HandleId},
     prelude::*,
+    utils::HashMap,
 };
 use bevy_spicy_aseprite::AsepriteImage;
 
@@ -9,36 +12,108 @@ pub struct UtilsPlugin;
 
 impl Plugin for UtilsPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<SubHandles>();
         app.add_system(AsepriteTileAtlasBundle::keep_in_sync);
     }
 }
 
-pub trait GetSubHandle {
-    fn get_sub_handle<T: Asset>(&self, label: &str, assets: &mut Assets<T>) -> Handle<T>;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubHandleError {
+    /// The handle was created by `Assets::add` and not loaded from a file, so it has no labels
+    NotLoadedFromPath(HandleId),
+    /// The asset server does not know the path of the handle (anymore)
+    UnknownPath(HandleId),
 }
 
-impl<A: Asset> GetSubHandle for Handle<A> {
-    fn get_sub_handle<T: Asset>(&self, label: &str, assets: &mut Assets<T>) -> Handle<T> {
-        let id = self.id;
-        match id {
-            HandleId::Id(_, _) => Handle::default(),
-            HandleId::AssetPathId(asset_path_id) => {
-                let source_path = asset_path_id.source_path_id();
-                let new_label: LabelId = Some(label).into();
+impl std::fmt::Display for SubHandleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubHandleError::NotLoadedFromPath(id) => {
+                write!(f, "the handle {:?} was not loaded from a path", id)
+            }
+            SubHandleError::UnknownPath(id) => {
+                write!(f, "the asset server has no path for the handle {:?}", id)
+            }
+        }
+    }
+}
 
-                let serialize = ron::ser::to_string(&(source_path, new_label)).unwrap();
+impl std::error::Error for SubHandleError {}
 
-                let new_asset_path_id: AssetPathId = ron::de::from_str(&serialize).unwrap();
+/// Builds the handles of labeled sub assets, e.g. the slices of an aseprite file
+///
+/// The ids are cached per handle and label, so asking for the same sub asset every frame is cheap.
+pub struct SubHandles {
+    asset_server: AssetServer,
+    handles: HashMap<HandleId, HashMap<String, HandleId>>,
+}
 
-                let mut handle = Handle::weak(new_asset_path_id.into());
-                handle.make_strong(assets);
+impl FromWorld for SubHandles {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world
+            .get_resource::<AssetServer>()
+            .expect("The AssetServer has to be added before the SubHandles")
+            .clone();
 
-                handle
-            }
+        SubHandles {
+            asset_server,
+            handles: HashMap::default(),
         }
     }
 }
 
+impl SubHandles {
+    /// Returns a strong handle to the asset with the given label inside the file of `handle`
+    pub fn get<T: Asset>(
+        &mut self,
+        handle: HandleId,
+        label: &str,
+    ) -> Result<Handle<T>, SubHandleError> {
+        if let Some(id) = self
+            .handles
+            .get(&handle)
+            .and_then(|labels| labels.get(label))
+        {
+            return Ok(self.asset_server.get_handle(*id));
+        }
+
+        if let HandleId::Id(..) = handle {
+            return Err(SubHandleError::NotLoadedFromPath(handle));
+        }
+
+        let path = self
+            .asset_server
+            .get_handle_path(handle)
+            .ok_or(SubHandleError::UnknownPath(handle))?;
+        let id = HandleId::from(AssetPath::new_ref(path.path(), Some(label)));
+
+        self.handles
+            .entry(handle)
+            .or_default()
+            .insert(label.to_owned(), id);
+
+        Ok(self.asset_server.get_handle(id))
+    }
+}
+
+pub trait GetSubHandle {
+    fn get_sub_handle<T: Asset>(
+        &self,
+        label: &str,
+        sub_handles: &mut SubHandles,
+    ) -> Result<Handle<T>, SubHandleError>;
+}
+
+impl<A: Asset> GetSubHandle for Handle<A> {
+    fn get_sub_handle<T: Asset>(
+        &self,
+        label: &str,
+        sub_handles: &mut SubHandles,
+    ) -> Result<Handle<T>, SubHandleError> {
+        sub_handles.get(self.id, label)
+    }
+}
+
 #[derive(Debug)]
 pub struct AsepriteTextureAtlasConfiguration {
     pub tile_size: Vec2,
@@ -70,7 +145,7 @@ impl AsepriteTileAtlasBundle {
     fn keep_in_sync(
         mut aseprite_events: EventReader<AssetEvent<AsepriteImage>>,
         mut texture_atlas_assets: ResMut<Assets<TextureAtlas>>,
-        mut texture_assets: ResMut<Assets<Texture>>,
+        mut sub_handles: ResMut<SubHandles>,
         mut ase_query: Query<(
             &Handle<AsepriteImage>,
             &mut Handle<TextureAtlas>,
@@ -90,8 +165,19 @@ impl AsepriteTileAtlasBundle {
                     continue;
                 }
 
+                let texture = match aseprite_handle.get_sub_handle("Frame0", &mut sub_handles) {
+                    Ok(texture) => texture,
+                    Err(err) => {
+                        error!(
+                            "Could not get the texture of {:?}: {}",
+                            aseprite_handle, err
+                        );
+                        continue;
+                    }
+                };
+
                 let texture_atlas = TextureAtlas::from_grid_with_padding(
-                    aseprite_handle.get_sub_handle("Frame0", &mut texture_assets),
+                    texture,
                     aseprite_atlas_configuration.tile_size,
                     aseprite_atlas_configuration.columns,
                     aseprite_atlas_configuration.rows,
@@ -109,3 +195,60 @@ impl AsepriteTileAtlasBundle {
         }
     }
 }
+
+/// Critically damped spring towards `target`, as popularized by Game Programming Gems 4
+///
+/// `velocity` carries the state between calls and `smooth_time` is roughly the time it
+/// takes to reach the target. The result never overshoots.
+pub fn smooth_damp(
+    current: Vec2,
+    target: Vec2,
+    velocity: &mut Vec2,
+    smooth_time: f32,
+    delta: f32,
+) -> Vec2 {
+    let omega = 2. / smooth_time.max(0.0001);
+    let x = omega * delta;
+    let exp = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+
+    let change = current - target;
+    let temp = (*velocity + omega * change) * delta;
+    *velocity = (*velocity - omega * temp) * exp;
+
+    let result = target + (change + temp) * exp;
+
+    // Large steps would carry it past the target, stop right there instead
+    if (target - current).dot(result - target) > 0. {
+        *velocity = Vec2::ZERO;
+        return target;
+    }
+
+    result
+}
+
+/// Where the asset server loads `path` from on disk, for reading assets without it
+pub fn asset_file_path(asset_settings: Option<&AssetServerSettings>, path: &str) -> PathBuf {
+    let asset_folder = asset_settings.map_or("assets", |settings| &settings.asset_folder);
+
+    FileAssetIo::get_root_path().join(asset_folder).join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::smooth_damp;
+
+    #[test]
+    fn smooth_damp_never_overshoots() {
+        let target = Vec2::new(10., 0.);
+        let mut current = Vec2::ZERO;
+        let mut velocity = Vec2::new(500., 0.);
+
+        for _ in 0..20 {
+            current = smooth_damp(current, target, &mut velocity, 0.1, 0.25);
+            assert!(current.x <= target.x, "{} went past {}", current, target);
+        }
+        assert_eq!(current, target);
+    }
+}
@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use bevy_simple_tilemap::{prelude::TileMapBundle, TileMap};
+
+use crate::{
+    camera::ParallaxLayer,
+    game_config::{changed_config, ConfigChanged},
+    GameAssets,
+};
+
+use super::{add_layer, LevelLayer, LevelName};
+
+/// How the background layers of a level scroll, read from the `[parallax]` section of `game.config`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackgroundSettings {
+    pub factor: Vec2,
+    pub repeat: bool,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        BackgroundSettings {
+            factor: Vec2::new(0.5, 0.5),
+            repeat: true,
+        }
+    }
+}
+
+impl BackgroundSettings {
+    fn parallax_layer(&self, level_width: f32) -> ParallaxLayer {
+        let parallax_layer = ParallaxLayer::new(self.factor);
+
+        if self.repeat {
+            parallax_layer.with_repeat_width(level_width)
+        } else {
+            parallax_layer
+        }
+    }
+}
+
+/// Spawns the `Background` layer of the level as a parallax scrolling tilemap
+pub fn spawn_background(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    level_width: f32,
+    texture_atlas: Handle<TextureAtlas>,
+    settings: BackgroundSettings,
+) {
+    let mut tilemap = TileMap::default();
+    fill_background(layer, level_width, settings, &mut tilemap);
+
+    parent
+        .spawn_bundle(TileMapBundle {
+            tilemap,
+            texture_atlas,
+            ..Default::default()
+        })
+        .insert(LevelLayer::Background)
+        .insert(settings.parallax_layer(level_width));
+}
+
+/// Re-creates the tiles and parallax settings, for example after a hot-reload
+pub fn update_background(
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    level_width: f32,
+    settings: BackgroundSettings,
+    tilemap: &mut TileMap,
+    parallax_layer: &mut ParallaxLayer,
+) {
+    tilemap.clear();
+    fill_background(layer, level_width, settings, tilemap);
+
+    *parallax_layer = settings.parallax_layer(level_width);
+}
+
+fn fill_background(
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    level_width: f32,
+    settings: BackgroundSettings,
+    tilemap: &mut TileMap,
+) {
+    if settings.repeat {
+        // One copy on each side is enough to cover the camera, the layer gets shifted by whole
+        // level widths to stay in view
+        let width_in_tiles = (level_width / layer.grid_size as f32) as i32;
+        for copy in -1..=1 {
+            add_layer(layer, 0, IVec2::new(copy * width_in_tiles, 0), tilemap);
+        }
+    } else {
+        add_layer(layer, 0, IVec2::ZERO, tilemap);
+    }
+}
+
+pub fn load_background_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<BackgroundSettings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    let new_settings = BackgroundSettings {
+        factor: Vec2::new(
+            config.parallax.background.factor_x as f32,
+            config.parallax.background.factor_y as f32,
+        ),
+        repeat: config.parallax.background.repeat,
+    };
+
+    // Only a real change refills the backgrounds of the spawned levels
+    if *settings != new_settings {
+        *settings = new_settings;
+    }
+}
+
+/// Applies changed [`BackgroundSettings`] to the backgrounds of all spawned levels
+pub fn apply_background_settings(
+    settings: Res<BackgroundSettings>,
+    ldtk_assets: Res<Assets<crate::levels::Project>>,
+    level_query: Query<(&Handle<crate::levels::Project>, &LevelName, &Children)>,
+    mut background_query: Query<(&LevelLayer, &mut TileMap, &mut ParallaxLayer)>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    for (level_handle, level_name, children) in level_query.iter() {
+        let ldtk_level = if let Some(level) = ldtk_assets.get(level_handle).and_then(|ldtk| {
+            ldtk.levels
+                .iter()
+                .find(|level| level.identifier == level_name.0)
+        }) {
+            level
+        } else {
+            continue;
+        };
+
+        for child in children.iter() {
+            if let Ok((LevelLayer::Background, mut tilemap, mut parallax_layer)) =
+                background_query.get_mut(*child)
+            {
+                update_background(
+                    &ldtk_level.layers.background,
+                    ldtk_level.dimensions_px.x as f32,
+                    *settings,
+                    &mut tilemap,
+                    &mut parallax_layer,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::{ecs::system::CommandQueue, prelude::*};
+
+use super::{
+    enemy::{self, EnemyKind, EnemySettings},
+    LevelBundle, LevelName, Player, Velocity, WorldLevels,
+};
+use crate::{
+    console::{AddConsoleCommand, ConsoleCommand},
+    utils::{GetSubHandle, SubHandles},
+    GameAssets,
+};
+
+const ENEMY_KINDS: [(&str, EnemyKind); 2] = [
+    ("Mushroom", EnemyKind::Mushroom),
+    ("Tortoise", EnemyKind::Tortoise),
+];
+
+/// Spawned enemies appear this far above the player, so they don't hurt it right away
+const ENEMY_SPAWN_OFFSET: Vec2 = Vec2::new(0., 32.);
+
+pub fn add_world_commands(app: &mut App) {
+    app.add_console_command(
+        ConsoleCommand::new("level", "level load|unload <name>", level)
+            .with_completion(complete_level),
+    );
+    app.add_console_command(ConsoleCommand::new("tp", "tp <x> <y>", teleport));
+    app.add_console_command(
+        ConsoleCommand::new("spawn", "spawn enemy <kind>", spawn).with_completion(|args, _| {
+            match args {
+                [] => vec![String::from("enemy")],
+                ["enemy"] => ENEMY_KINDS
+                    .iter()
+                    .map(|(name, _)| (*name).to_owned())
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }),
+    );
+}
+
+fn level_identifiers(world: &World) -> Vec<String> {
+    let project = world.get_resource::<GameAssets>().and_then(|game_assets| {
+        world
+            .get_resource::<Assets<crate::levels::Project>>()?
+            .get(&game_assets.levels)
+    });
+
+    project
+        .map(|project| {
+            project
+                .levels
+                .iter()
+                .map(|level| level.identifier.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn level(args: &[&str], world: &mut World) -> Result<String, String> {
+    let world_levels = world
+        .get_resource::<WorldLevels>()
+        .ok_or("levels can only be changed while the game is running")?;
+
+    match args {
+        ["load", name] => {
+            if world_levels.level_map.levels.contains_key(*name) {
+                return Err(format!("{} is loaded already", name));
+            }
+
+            if !level_identifiers(world).iter().any(|level| level == *name) {
+                return Err(format!("there is no level {}", name));
+            }
+
+            let level_handle = world_levels.level_handle.clone();
+            world.spawn().insert_bundle(LevelBundle {
+                level_name: LevelName((*name).to_owned()),
+                level_handle,
+                ..Default::default()
+            });
+
+            Ok(format!("Loading {}", name))
+        }
+        ["unload", name] => {
+            let entity = world
+                .get_resource_mut::<WorldLevels>()
+                .and_then(|mut world_levels| world_levels.level_map.levels.remove(*name))
+                .ok_or_else(|| format!("{} is not loaded", name))?;
+
+            let mut queue = CommandQueue::default();
+            Commands::new(&mut queue, world)
+                .entity(entity)
+                .despawn_recursive();
+            queue.apply(world);
+
+            Ok(format!("Unloaded {}", name))
+        }
+        _ => Err(String::from("expected load or unload and a level")),
+    }
+}
+
+fn complete_level(args: &[&str], world: &World) -> Vec<String> {
+    match args {
+        [] => vec![String::from("load"), String::from("unload")],
+        ["load"] => level_identifiers(world),
+        ["unload"] => world
+            .get_resource::<WorldLevels>()
+            .map(|world_levels| {
+                world_levels
+                    .level_map
+                    .iter()
+                    .map(|(name, _)| name.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Moves the player to a position in pixels, relative to the level it is in
+fn teleport(args: &[&str], world: &mut World) -> Result<String, String> {
+    let position = match args {
+        [x, y] => Vec2::new(
+            x.parse().map_err(|_| format!("invalid x '{}'", x))?,
+            y.parse().map_err(|_| format!("invalid y '{}'", y))?,
+        ),
+        _ => return Err(String::from("expected a position")),
+    };
+
+    let (mut transform, mut velocity) = world
+        .query_filtered::<(&mut Transform, &mut Velocity), With<Player>>()
+        .iter_mut(world)
+        .next()
+        .ok_or("there is no player")?;
+
+    transform.translation = position.extend(transform.translation.z);
+    velocity.0 = Vec2::ZERO;
+
+    Ok(format!("Teleported to {}", position))
+}
+
+fn spawn(args: &[&str], world: &mut World) -> Result<String, String> {
+    let name = match args {
+        ["enemy", name] => *name,
+        _ => return Err(String::from("expected an enemy kind")),
+    };
+
+    let kind = ENEMY_KINDS
+        .iter()
+        .find(|(kind_name, _)| kind_name.eq_ignore_ascii_case(name))
+        .map(|(_, kind)| *kind)
+        .ok_or_else(|| format!("unknown enemy kind '{}'", name))?;
+
+    let (level, position) = world
+        .query_filtered::<(&Parent, &Transform), With<Player>>()
+        .iter(world)
+        .next()
+        .map(|(parent, transform)| (parent.0, transform.translation.truncate()))
+        .ok_or("there is no player to spawn the enemy at")?;
+
+    let texture = world.resource_scope(|world, mut sub_handles: Mut<SubHandles>| {
+        world
+            .get_resource::<GameAssets>()
+            .ok_or_else(|| String::from("the assets are not loaded"))?
+            .entity_sprites
+            .get_sub_handle("Frame0", &mut sub_handles)
+            .map_err(|err| err.to_string())
+    })?;
+
+    world.resource_scope(|world, mut materials: Mut<Assets<ColorMaterial>>| {
+        let settings = world
+            .get_resource::<EnemySettings>()
+            .ok_or("the enemies have no settings")?
+            .clone();
+
+        let mut queue = CommandQueue::default();
+        Commands::new(&mut queue, world)
+            .entity(level)
+            .with_children(|parent| {
+                enemy::spawn_enemy(
+                    parent,
+                    kind,
+                    position + ENEMY_SPAWN_OFFSET,
+                    texture,
+                    &mut materials,
+                    &settings,
+                );
+            });
+        queue.apply(world);
+
+        Ok(format!("Spawned a {:?}", kind))
+    })
+}
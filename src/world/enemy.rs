@@ -0,0 +1,425 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use crate::{
+    audio::{SoundEffect, SoundEvent},
+    game_config::{changed_config, ConfigChanged},
+    particles::ParticleEvent,
+    tick::{FixedTick, Interpolated},
+    GameAssets,
+};
+
+use super::{
+    hazard::Dying,
+    physics::{overlaps, Collider, CollisionMap, KinematicBody, Velocity},
+    LevelObject,
+};
+
+/// The puff of smoke where an enemy was stomped
+const STOMP_POOF: &str = "particles/poof.emitter";
+
+/// Kills an enemy (or gets bounced by it) when landing on it from above
+#[derive(Debug, Default)]
+pub struct Stomper;
+
+/// Sent when a [`Stomper`] lands on an enemy
+#[derive(Debug, Clone, Copy)]
+pub struct StompEvent {
+    pub stomper: Entity,
+    pub enemy: Entity,
+}
+
+/// Sent when a [`Stomper`] runs into a dangerous enemy from any other side than the top
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyContactEvent {
+    pub entity: Entity,
+    pub enemy: Entity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyKind {
+    Mushroom,
+    Tortoise,
+}
+
+impl From<&crate::levels::EnemyKind> for EnemyKind {
+    fn from(kind: &crate::levels::EnemyKind) -> Self {
+        match kind {
+            crate::levels::EnemyKind::Mushroom => EnemyKind::Mushroom,
+            crate::levels::EnemyKind::Tortoise => EnemyKind::Tortoise,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyState {
+    Walking,
+    /// A stomped Tortoise, it gets up again after a while unless it is kicked
+    Shell {
+        sliding: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    pub state: EnemyState,
+    /// `-1.` for left and `1.` for right
+    pub direction: f32,
+    /// Time until the next hop of a Mushroom or until a Tortoise leaves its shell
+    timer: f32,
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyKind) -> Self {
+        Enemy {
+            kind,
+            state: EnemyState::Walking,
+            direction: -1.,
+            timer: 0.,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MushroomSettings {
+    pub walk_speed: f32,
+    /// Seconds between two hops, `0.` disables hopping
+    pub hop_interval: f32,
+    pub hop_speed: f32,
+    /// Upwards speed given to whatever stomps on a Mushroom
+    pub bounce_speed: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TortoiseSettings {
+    pub walk_speed: f32,
+    pub shell_speed: f32,
+    /// Upwards speed given to whatever stomps on a Tortoise
+    pub bounce_speed: f32,
+    /// Seconds a resting shell waits before walking again
+    pub shell_time: f32,
+}
+
+/// Tuning of the enemies, read from the `[enemies]` section of `game.config`
+#[derive(Debug, Clone)]
+pub struct EnemySettings {
+    pub gravity: f32,
+    pub mushroom: MushroomSettings,
+    pub tortoise: TortoiseSettings,
+}
+
+impl Default for EnemySettings {
+    fn default() -> Self {
+        EnemySettings {
+            gravity: 900.,
+            mushroom: MushroomSettings {
+                walk_speed: 24.,
+                hop_interval: 2.,
+                hop_speed: 160.,
+                bounce_speed: 380.,
+            },
+            tortoise: TortoiseSettings {
+                walk_speed: 20.,
+                shell_speed: 180.,
+                bounce_speed: 200.,
+                shell_time: 5.,
+            },
+        }
+    }
+}
+
+impl EnemySettings {
+    fn walk_speed(&self, kind: EnemyKind) -> f32 {
+        match kind {
+            EnemyKind::Mushroom => self.mushroom.walk_speed,
+            EnemyKind::Tortoise => self.tortoise.walk_speed,
+        }
+    }
+}
+
+/// Spawns all enemies placed in the `Entities` layer of a level as children of it
+///
+/// Entities are placed like tiles, centered on their LDtk position.
+pub fn spawn_enemies(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    texture: Handle<Texture>,
+    materials: &mut Assets<ColorMaterial>,
+    settings: &EnemySettings,
+) {
+    let entities = if let bevy_spicy_ldtk::SpecialValues::Entities(entities) = &layer.special {
+        entities
+    } else {
+        error!("The entities layer does not contain entities");
+        return;
+    };
+
+    for entity in entities {
+        let kind = if let crate::levels::ProjectEntities::Enemy(enemy) = &entity.fields {
+            EnemyKind::from(&enemy.enemy_kind)
+        } else {
+            continue;
+        };
+
+        spawn_enemy(
+            parent,
+            kind,
+            entity.position_px.as_f32(),
+            texture.clone(),
+            materials,
+            settings,
+        );
+    }
+}
+
+pub fn spawn_enemy(
+    parent: &mut ChildBuilder,
+    kind: EnemyKind,
+    position: Vec2,
+    texture: Handle<Texture>,
+    materials: &mut Assets<ColorMaterial>,
+    settings: &EnemySettings,
+) {
+    let color = match kind {
+        EnemyKind::Mushroom => Color::rgb(1., 0.6, 0.6),
+        EnemyKind::Tortoise => Color::rgb(0.6, 1., 0.6),
+    };
+
+    parent
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(ColorMaterial::modulated_texture(texture, color)),
+            transform: Transform::from_translation(position.extend(3.)),
+            ..Default::default()
+        })
+        .insert(Enemy::new(kind))
+        .insert(LevelObject)
+        .insert(Velocity::default())
+        .insert(Interpolated::default())
+        .insert(Collider {
+            half_size: Vec2::new(7., 7.),
+        })
+        .insert(KinematicBody::new(settings.gravity));
+}
+
+/// Walks the enemies back and forth, turning around at walls and ledges
+pub fn patrol_enemies(
+    fixed_tick: Res<FixedTick>,
+    settings: Res<EnemySettings>,
+    map_query: Query<&CollisionMap>,
+    mut enemy_query: Query<(
+        &mut Enemy,
+        &mut Velocity,
+        &KinematicBody,
+        &Collider,
+        &Transform,
+        &Parent,
+    )>,
+) {
+    let delta = fixed_tick.delta_seconds();
+
+    for (mut enemy, mut velocity, body, collider, transform, parent) in enemy_query.iter_mut() {
+        let map = if let Ok(map) = map_query.get(parent.0) {
+            map
+        } else {
+            continue;
+        };
+
+        if body.hit_wall == Some(enemy.direction) {
+            enemy.direction = -enemy.direction;
+        }
+
+        match enemy.state {
+            EnemyState::Walking => {
+                let position = transform.translation.xy();
+                let ahead = position
+                    + Vec2::new(
+                        enemy.direction * (collider.half_size.x + 1.),
+                        -(collider.half_size.y + 1.),
+                    );
+
+                if body.on_ground && !map.is_solid_at(ahead) {
+                    enemy.direction = -enemy.direction;
+                }
+
+                velocity.0.x = enemy.direction * settings.walk_speed(enemy.kind);
+
+                if enemy.kind == EnemyKind::Mushroom && settings.mushroom.hop_interval > 0. {
+                    enemy.timer -= delta;
+
+                    if enemy.timer <= 0. && body.on_ground {
+                        enemy.timer = settings.mushroom.hop_interval;
+                        velocity.0.y = settings.mushroom.hop_speed;
+                    }
+                }
+            }
+            EnemyState::Shell { sliding: true } => {
+                velocity.0.x = enemy.direction * settings.tortoise.shell_speed;
+            }
+            EnemyState::Shell { sliding: false } => {
+                velocity.0.x = 0.;
+                enemy.timer -= delta;
+
+                if enemy.timer <= 0. {
+                    enemy.state = EnemyState::Walking;
+                }
+            }
+        }
+    }
+}
+
+/// Lets [`Stomper`]s land on enemies and kick resting shells
+pub fn stomp_enemies(
+    mut stomp_events: EventWriter<StompEvent>,
+    mut contact_events: EventWriter<EnemyContactEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut stomper_query: Query<
+        (Entity, &Transform, &mut Velocity, &Collider, &Parent),
+        (With<Stomper>, Without<Enemy>, Without<Dying>),
+    >,
+    mut enemy_query: Query<(Entity, &Transform, &mut Enemy, &Collider, &Parent)>,
+) {
+    for (stomper, stomper_transform, mut stomper_velocity, stomper_collider, stomper_parent) in
+        stomper_query.iter_mut()
+    {
+        let stomper_position = stomper_transform.translation.xy();
+
+        for (enemy_entity, enemy_transform, mut enemy, enemy_collider, enemy_parent) in
+            enemy_query.iter_mut()
+        {
+            if stomper_parent.0 != enemy_parent.0 {
+                continue;
+            }
+
+            let enemy_position = enemy_transform.translation.xy();
+
+            if !overlaps(
+                stomper_position,
+                stomper_collider.half_size,
+                enemy_position,
+                enemy_collider.half_size,
+            ) {
+                continue;
+            }
+
+            let from_above = stomper_velocity.0.y < 0. && stomper_position.y > enemy_position.y;
+
+            if from_above {
+                stomp_events.send(StompEvent {
+                    stomper,
+                    enemy: enemy_entity,
+                });
+                sound_events.send(SoundEvent::in_level(
+                    SoundEffect::Stomp,
+                    enemy_parent.0,
+                    enemy_position,
+                ));
+                particle_events.send(ParticleEvent::in_level(
+                    STOMP_POOF,
+                    enemy_parent.0,
+                    enemy_position,
+                ));
+                // Resolved in `resolve_stomps`, bounce off right away so it is not stomped twice
+                stomper_velocity.0.y = 0.;
+            } else if enemy.state == (EnemyState::Shell { sliding: false }) {
+                enemy.direction = (enemy_position.x - stomper_position.x).signum();
+                enemy.state = EnemyState::Shell { sliding: true };
+            } else {
+                contact_events.send(EnemyContactEvent {
+                    entity: stomper,
+                    enemy: enemy_entity,
+                });
+            }
+        }
+    }
+}
+
+/// Bounces the stomper and turns stomped Tortoises into shells
+pub fn resolve_stomps(
+    settings: Res<EnemySettings>,
+    mut stomp_events: EventReader<StompEvent>,
+    mut stomper_query: Query<(&Transform, &mut Velocity), Without<Enemy>>,
+    mut enemy_query: Query<(&Transform, &mut Enemy, &mut Velocity)>,
+) {
+    for StompEvent { stomper, enemy } in stomp_events.iter() {
+        let (stomper_transform, mut stomper_velocity) =
+            if let Ok(stomper) = stomper_query.get_mut(*stomper) {
+                stomper
+            } else {
+                continue;
+            };
+
+        let (enemy_transform, mut enemy, mut enemy_velocity) =
+            if let Ok(enemy) = enemy_query.get_mut(*enemy) {
+                enemy
+            } else {
+                continue;
+            };
+
+        match enemy.kind {
+            EnemyKind::Mushroom => {
+                stomper_velocity.0.y = settings.mushroom.bounce_speed;
+            }
+            EnemyKind::Tortoise => {
+                stomper_velocity.0.y = settings.tortoise.bounce_speed;
+
+                enemy.state = match enemy.state {
+                    EnemyState::Walking | EnemyState::Shell { sliding: true } => {
+                        EnemyState::Shell { sliding: false }
+                    }
+                    EnemyState::Shell { sliding: false } => {
+                        enemy.direction = (enemy_transform.translation.x
+                            - stomper_transform.translation.x)
+                            .signum();
+                        EnemyState::Shell { sliding: true }
+                    }
+                };
+                enemy.timer = settings.tortoise.shell_time;
+                enemy_velocity.0.x = 0.;
+            }
+        }
+    }
+}
+
+pub fn face_enemies(mut enemy_query: Query<(&Enemy, &mut Sprite), Changed<Enemy>>) {
+    for (enemy, mut sprite) in enemy_query.iter_mut() {
+        sprite.flip_x = enemy.direction > 0.;
+    }
+}
+
+pub fn load_enemy_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<EnemySettings>,
+    mut body_query: Query<&mut KinematicBody, With<Enemy>>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    let enemies = &config.enemies;
+    *settings = EnemySettings {
+        gravity: enemies.gravity as f32,
+        mushroom: MushroomSettings {
+            walk_speed: enemies.mushroom.walk_speed as f32,
+            hop_interval: enemies.mushroom.hop_interval as f32,
+            hop_speed: enemies.mushroom.hop_speed as f32,
+            bounce_speed: enemies.mushroom.bounce_speed as f32,
+        },
+        tortoise: TortoiseSettings {
+            walk_speed: enemies.tortoise.walk_speed as f32,
+            shell_speed: enemies.tortoise.shell_speed as f32,
+            bounce_speed: enemies.tortoise.bounce_speed as f32,
+            shell_time: enemies.tortoise.shell_time as f32,
+        },
+    };
+
+    for mut body in body_query.iter_mut() {
+        body.gravity = settings.gravity;
+    }
+}
@@ -0,0 +1,364 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use crate::{
+    audio::{SoundEffect, SoundEvent},
+    camera::CameraShakeEvent,
+    tick::FixedTick,
+};
+
+use super::{
+    enemy::EnemyContactEvent,
+    physics::{overlaps, Collider, CollisionMap, KinematicBody, TileKind, Velocity},
+    player::{LevelEntries, Player, PlayerSettings},
+    seed::{Carrier, Seed},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Damage {
+    Amount(u32),
+    /// Kills no matter how much health is left
+    Kill,
+}
+
+/// What hurt an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HazardKind {
+    Lava,
+    Enemy,
+    /// Fell out of the bottom of the level
+    Fall,
+}
+
+impl HazardKind {
+    fn damage(self) -> Damage {
+        match self {
+            HazardKind::Lava | HazardKind::Fall => Damage::Kill,
+            HazardKind::Enemy => Damage::Amount(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HurtEvent {
+    pub entity: Entity,
+    pub hazard: HazardKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerDiedEvent {
+    pub player: Entity,
+    pub hazard: HazardKind,
+}
+
+/// Sent when the level entity should respawn all its [`LevelObject`]s from the LDtk data
+#[derive(Debug, Clone, Copy)]
+pub struct ResetLevelEvent(pub Entity);
+
+#[derive(Debug, Clone, Copy)]
+pub struct CheckpointReachedEvent {
+    pub player: Entity,
+    pub position: Vec2,
+}
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    /// Seconds until the entity can be hurt again
+    pub invulnerable: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Health {
+            current: max,
+            max,
+            invulnerable: 0.,
+        }
+    }
+}
+
+/// Where a player respawns, the last reached checkpoint or the start of the level
+#[derive(Debug, Default)]
+pub struct Respawn {
+    pub checkpoint: Option<Vec2>,
+}
+
+/// Plays the death sequence, the player respawns once it is over
+#[derive(Debug)]
+pub struct Dying {
+    timer: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    pub reached: bool,
+}
+
+/// Spawns the checkpoints placed in the `Entities` layer of a level as children of it
+///
+/// Checkpoints are not [`LevelObject`]s, they keep being reached when the level is reset.
+pub fn spawn_checkpoints(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    let entities = if let bevy_spicy_ldtk::SpecialValues::Entities(entities) = &layer.special {
+        entities
+    } else {
+        error!("The entities layer does not contain entities");
+        return;
+    };
+
+    for entity in entities {
+        if let crate::levels::ProjectEntities::Checkpoint(_) = &entity.fields {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    material: materials.add(Color::rgba(0.25, 0.72, 0.95, 0.5).into()),
+                    sprite: Sprite::new(Vec2::new(4., 16.)),
+                    transform: Transform::from_translation(entity.position_px.as_f32().extend(1.)),
+                    ..Default::default()
+                })
+                .insert(Checkpoint::default())
+                .insert(Collider {
+                    half_size: Vec2::new(8., 8.),
+                });
+        }
+    }
+}
+
+/// Hurts players touching lava or falling out of the level
+pub fn touch_hazards(
+    mut hurt_events: EventWriter<HurtEvent>,
+    map_query: Query<&CollisionMap>,
+    player_query: Query<(Entity, &Transform, &Collider, &Parent), (With<Player>, Without<Dying>)>,
+) {
+    for (player, transform, collider, parent) in player_query.iter() {
+        let map = if let Ok(map) = map_query.get(parent.0) {
+            map
+        } else {
+            continue;
+        };
+
+        let position = transform.translation.xy();
+
+        let hazard = if map.touches(position, collider.half_size, TileKind::Lava) {
+            HazardKind::Lava
+        } else if position.y < -map.grid_size {
+            HazardKind::Fall
+        } else {
+            continue;
+        };
+
+        hurt_events.send(HurtEvent {
+            entity: player,
+            hazard,
+        });
+    }
+}
+
+pub fn hurt_by_enemies(
+    mut contact_events: EventReader<EnemyContactEvent>,
+    mut hurt_events: EventWriter<HurtEvent>,
+) {
+    for contact in contact_events.iter() {
+        hurt_events.send(HurtEvent {
+            entity: contact.entity,
+            hazard: HazardKind::Enemy,
+        });
+    }
+}
+
+/// Takes the damage of the hazards and starts the death sequence once no health is left
+pub fn apply_damage(
+    mut commands: Commands,
+    settings: Res<PlayerSettings>,
+    mut hurt_events: EventReader<HurtEvent>,
+    mut died_events: EventWriter<PlayerDiedEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut seed_query: Query<&mut Seed>,
+    mut player_query: Query<
+        (
+            &mut Health,
+            &mut Velocity,
+            &mut Carrier,
+            &Transform,
+            &Parent,
+        ),
+        Without<Dying>,
+    >,
+) {
+    for HurtEvent { entity, hazard } in hurt_events.iter() {
+        let (mut health, mut velocity, mut carrier, transform, parent) =
+            if let Ok(player) = player_query.get_mut(*entity) {
+                player
+            } else {
+                continue;
+            };
+
+        if health.current == 0 {
+            // Already died this frame
+            continue;
+        }
+
+        match hazard.damage() {
+            Damage::Amount(_) if health.invulnerable > 0. => continue,
+            Damage::Amount(amount) => {
+                health.current = health.current.saturating_sub(amount);
+                health.invulnerable = settings.invulnerable_time;
+            }
+            Damage::Kill => health.current = 0,
+        }
+
+        let position = transform.translation.xy();
+
+        if health.current > 0 {
+            shake_events.send(CameraShakeEvent(0.3));
+            sound_events.send(SoundEvent::in_level(SoundEffect::Hurt, parent.0, position));
+            continue;
+        }
+
+        // The level is reset after the death sequence, the carried seed is respawned with it
+        if let Some(seed) = carrier.seed.take() {
+            if let Ok(mut seed) = seed_query.get_mut(seed) {
+                seed.carrier = None;
+            }
+        }
+
+        velocity.0 = Vec2::new(0., settings.death_hop_speed);
+
+        commands
+            .entity(*entity)
+            .remove::<KinematicBody>()
+            .insert(Dying {
+                timer: settings.death_time,
+            });
+
+        shake_events.send(CameraShakeEvent(0.6));
+        sound_events.send(SoundEvent::in_level(SoundEffect::Death, parent.0, position));
+        died_events.send(PlayerDiedEvent {
+            player: *entity,
+            hazard: *hazard,
+        });
+    }
+}
+
+pub fn tick_invulnerability(
+    fixed_tick: Res<FixedTick>,
+    mut health_query: Query<(&mut Health, &mut Visible)>,
+) {
+    for (mut health, mut visible) in health_query.iter_mut() {
+        if health.invulnerable <= 0. {
+            continue;
+        }
+
+        health.invulnerable = (health.invulnerable - fixed_tick.delta_seconds()).max(0.);
+
+        // Blink while invulnerable
+        visible.is_visible =
+            health.invulnerable <= 0. || (health.invulnerable * 10.) as u32 % 2 == 0;
+    }
+}
+
+/// Lets the player hop up and fall through the level, then respawns it and resets the level
+pub fn play_death_sequence(
+    mut commands: Commands,
+    fixed_tick: Res<FixedTick>,
+    settings: Res<PlayerSettings>,
+    mut reset_events: EventWriter<ResetLevelEvent>,
+    level_query: Query<&LevelEntries>,
+    mut player_query: Query<(
+        Entity,
+        &mut Dying,
+        &mut Transform,
+        &mut Velocity,
+        &mut Health,
+        &Respawn,
+        &Parent,
+    )>,
+) {
+    let delta = fixed_tick.delta_seconds();
+
+    for (entity, mut dying, mut transform, mut velocity, mut health, respawn, parent) in
+        player_query.iter_mut()
+    {
+        velocity.0.y -= settings.gravity * delta;
+        transform.translation += (velocity.0 * delta).extend(0.);
+
+        dying.timer -= delta;
+        if dying.timer > 0. {
+            continue;
+        }
+
+        let position = respawn
+            .checkpoint
+            .or_else(|| level_query.get(parent.0).ok().and_then(LevelEntries::start))
+            .unwrap_or_default();
+
+        transform.translation = position.extend(transform.translation.z);
+        velocity.0 = Vec2::ZERO;
+        *health = Health::new(settings.max_health);
+
+        commands
+            .entity(entity)
+            .remove::<Dying>()
+            .insert(KinematicBody::new(settings.gravity));
+
+        reset_events.send(ResetLevelEvent(parent.0));
+    }
+}
+
+pub fn reach_checkpoints(
+    mut reached_events: EventWriter<CheckpointReachedEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    mut player_query: Query<
+        (Entity, &Transform, &Collider, &Parent, &mut Respawn),
+        (With<Player>, Without<Dying>),
+    >,
+    mut checkpoint_query: Query<
+        (
+            &mut Checkpoint,
+            &Transform,
+            &Collider,
+            &Parent,
+            &Handle<ColorMaterial>,
+        ),
+        Without<Player>,
+    >,
+) {
+    for (player, player_transform, player_collider, player_parent, mut respawn) in
+        player_query.iter_mut()
+    {
+        for (mut checkpoint, transform, collider, parent, material) in checkpoint_query.iter_mut() {
+            let position = transform.translation.xy();
+
+            if checkpoint.reached
+                || parent.0 != player_parent.0
+                || !overlaps(
+                    player_transform.translation.xy(),
+                    player_collider.half_size,
+                    position,
+                    collider.half_size,
+                )
+            {
+                continue;
+            }
+
+            checkpoint.reached = true;
+            respawn.checkpoint = Some(position);
+
+            if let Some(material) = material_assets.get_mut(material) {
+                material.color = Color::rgb(0.25, 0.72, 0.95);
+            }
+
+            reached_events.send(CheckpointReachedEvent { player, position });
+            sound_events.send(SoundEvent::in_level(
+                SoundEffect::Checkpoint,
+                parent.0,
+                position,
+            ));
+        }
+    }
+}
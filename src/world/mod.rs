@@ -1,15 +1,57 @@
+mod background;
+mod commands;
+mod enemy;
+mod hazard;
+mod physics;
+mod player;
+mod seed;
 mod startup;
+mod stats;
 
-use crate::{stages::GameState, GameAssets};
+use crate::{
+    camera::{Free2DCamera, ParallaxLayer},
+    particles::ParticleEmitter,
+    stages::GameState,
+    tick::{FixedTickStage, FixedTickSystems},
+    utils::{GetSubHandle, SubHandles},
+    GameAssets,
+};
 use bevy::{prelude::*, utils::HashMap};
 use bevy_simple_tilemap::{prelude::TileMapBundle, Tile, TileFlags, TileMap};
 
-use self::startup::WorldLevels;
+use self::{
+    background::BackgroundSettings, enemy::EnemySettings, physics::PhysicsSettings,
+    player::PlayerSettings, seed::SeedSettings,
+};
+
+pub use self::{
+    enemy::{Enemy, EnemyContactEvent, EnemyKind, StompEvent, Stomper},
+    hazard::{
+        CheckpointReachedEvent, HazardKind, Health, HurtEvent, PlayerDiedEvent, ResetLevelEvent,
+    },
+    physics::{Collider, CollisionMap, KinematicBody, TileKind, Velocity},
+    player::{LevelEntries, Player},
+    seed::{
+        Carrier, Exit, LevelCompleteEvent, Seed, SeedBrokenEvent, SeedDeliveredEvent, SeedKind,
+    },
+    startup::WorldLevels,
+    stats::LevelStats,
+};
 
 #[derive(Debug, SystemLabel, Clone, Copy, Hash, PartialEq, Eq)]
 enum WorldSystems {
     WorldSetup,
     InitialLoad,
+    Behaviour,
+    MovePlayer,
+    Physics,
+    Contacts,
+    CarrySeeds,
+    Resolve,
+    Invulnerability,
+    Damage,
+    Death,
+    Respawn,
 }
 
 #[derive(Debug, Default)]
@@ -17,6 +59,27 @@ pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
+        app.add_event::<StompEvent>();
+        app.add_event::<EnemyContactEvent>();
+        app.add_event::<SeedDeliveredEvent>();
+        app.add_event::<SeedBrokenEvent>();
+        app.add_event::<LevelCompleteEvent>();
+        app.add_event::<HurtEvent>();
+        app.add_event::<PlayerDiedEvent>();
+        app.add_event::<ResetLevelEvent>();
+        app.add_event::<CheckpointReachedEvent>();
+        app.init_resource::<LevelStats>();
+        app.init_resource::<EnemySettings>();
+        app.init_resource::<PlayerSettings>();
+        app.init_resource::<SeedSettings>();
+        app.init_resource::<PhysicsSettings>();
+        app.init_resource::<BackgroundSettings>();
+        app.add_system(enemy::load_enemy_settings_from_config);
+        app.add_system(player::load_player_settings_from_config);
+        app.add_system(seed::load_seed_settings_from_config);
+        app.add_system(physics::load_physics_settings_from_config);
+        app.add_system(background::load_background_settings_from_config);
+        commands::add_world_commands(app);
         app.add_system_set(
             SystemSet::on_enter(GameState::Running)
                 .with_system(startup::setup_levels.label(WorldSystems::WorldSetup))
@@ -29,7 +92,107 @@ impl Plugin for WorldPlugin {
         app.add_system_set(
             SystemSet::on_update(GameState::Running)
                 .with_system(load_new_levels)
-                .with_system(update_levels),
+                .with_system(update_levels)
+                .with_system(background::apply_background_settings)
+                .with_system(limit_cameras_to_level)
+                .with_system(player::spawn_player),
+        );
+        // Systems touching the same components are ordered, the parallel executor would pick a
+        // different order from run to run and replays would diverge
+        app.add_system_set_to_stage(
+            FixedTickStage,
+            SystemSet::on_update(GameState::Running)
+                .after(FixedTickSystems::Input)
+                .with_system(enemy::patrol_enemies.label(WorldSystems::Behaviour))
+                .with_system(
+                    player::move_player
+                        .label(WorldSystems::Behaviour)
+                        .label(WorldSystems::MovePlayer),
+                )
+                // Reads the velocity of the carrier, to throw seeds along with it
+                .with_system(
+                    seed::pick_up_and_drop_seeds
+                        .label(WorldSystems::Behaviour)
+                        .after(WorldSystems::MovePlayer),
+                )
+                .with_system(
+                    physics::move_bodies
+                        .label(WorldSystems::Physics)
+                        .after(WorldSystems::Behaviour),
+                )
+                .with_system(
+                    enemy::stomp_enemies
+                        .label(WorldSystems::Contacts)
+                        .after(WorldSystems::Physics),
+                )
+                .with_system(
+                    seed::carry_seeds
+                        .label(WorldSystems::Contacts)
+                        .label(WorldSystems::CarrySeeds)
+                        .after(WorldSystems::Physics),
+                )
+                // Reads where the carried seeds were moved to
+                .with_system(
+                    seed::break_fragile_seeds
+                        .label(WorldSystems::Contacts)
+                        .after(WorldSystems::CarrySeeds),
+                )
+                .with_system(
+                    seed::deliver_seeds
+                        .label(WorldSystems::Resolve)
+                        .after(WorldSystems::Contacts),
+                )
+                .with_system(
+                    seed::reveal_hidden_tiles
+                        .label(WorldSystems::Resolve)
+                        .after(WorldSystems::Contacts),
+                )
+                .with_system(
+                    enemy::resolve_stomps
+                        .label(WorldSystems::Resolve)
+                        .after(WorldSystems::Contacts),
+                )
+                .with_system(
+                    hazard::touch_hazards
+                        .label(WorldSystems::Contacts)
+                        .after(WorldSystems::Physics),
+                )
+                .with_system(
+                    hazard::reach_checkpoints
+                        .label(WorldSystems::Contacts)
+                        .after(WorldSystems::Physics),
+                )
+                // Damage knocks the player back and drops the carried seed, after stomps bounced
+                // and seeds were delivered
+                .with_system(
+                    hazard::hurt_by_enemies
+                        .label(WorldSystems::Damage)
+                        .after(WorldSystems::Resolve),
+                )
+                .with_system(
+                    hazard::apply_damage
+                        .label(WorldSystems::Death)
+                        .after(WorldSystems::Damage),
+                )
+                .with_system(
+                    hazard::tick_invulnerability
+                        .label(WorldSystems::Invulnerability)
+                        .before(WorldSystems::Damage),
+                )
+                .with_system(
+                    hazard::play_death_sequence
+                        .label(WorldSystems::Respawn)
+                        .after(WorldSystems::Death),
+                )
+                // Events are only kept for two frames and a frame may run no tick at all, so
+                // everything reading events sent during the tick runs after their senders
+                .with_system(reset_levels.after(WorldSystems::Respawn))
+                .with_system(
+                    stats::track_level_stats
+                        .after(WorldSystems::Death)
+                        .after(WorldSystems::Resolve),
+                )
+                .with_system(enemy::face_enemies.after(WorldSystems::Resolve)),
         );
         app.add_system_set(
             SystemSet::on_exit(GameState::Running).with_system(startup::remove_level),
@@ -42,27 +205,63 @@ pub struct LevelMap {
     levels: HashMap<String, Entity>,
 }
 
+impl LevelMap {
+    /// The names and entities of all spawned levels, loaded or not
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.levels
+            .iter()
+            .map(|(name, entity)| (name.as_str(), *entity))
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct LevelName(pub String);
 
 #[derive(Debug, Default)]
 pub struct Level;
 
+/// Which LDtk layer a tilemap of a level was created from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelLayer {
+    Front,
+    Background,
+    /// The hidden tiles of the `Front` layer, shown while revealed by a glowing seed
+    Hidden,
+}
+
+/// Spawned from the `Entities` layer of a level and respawned when the level is reset
+#[derive(Debug, Default)]
+pub struct LevelObject;
+
+/// The rectangle (in world pixels) a level covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelBounds(pub Rect<f32>);
+
 #[derive(Debug, Default, Bundle)]
 pub struct LevelBundle {
     pub level: Level,
     pub level_name: LevelName,
     pub level_handle: Handle<crate::levels::Project>,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
 }
 
 pub struct DefaultLevels(pub Vec<String>);
 
+/// The level the player is currently in, the cameras are limited to its bounds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentLevel(pub String);
+
 fn insert_configured_levels(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
     default_levels: Option<Res<DefaultLevels>>,
 ) {
     if let Some(default_levels) = default_levels {
+        if let Some(first_level) = default_levels.0.first() {
+            commands.insert_resource(CurrentLevel(first_level.clone()));
+        }
+
         for level in &default_levels.0 {
             commands.spawn_bundle(LevelBundle {
                 level_name: LevelName(level.clone()),
@@ -79,6 +278,12 @@ fn load_new_levels(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
     ldtk_assets: Res<Assets<crate::levels::Project>>,
+    background_settings: Res<BackgroundSettings>,
+    asset_server: Res<AssetServer>,
+    enemy_settings: Res<EnemySettings>,
+    seed_settings: Res<SeedSettings>,
+    mut sub_handles: ResMut<SubHandles>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
     mut world_level: ResMut<WorldLevels>,
     level_query: Query<(Entity, &LevelName), Added<Level>>,
 ) {
@@ -109,15 +314,184 @@ fn load_new_levels(
         info!("Spawned the level: {}", level_name.0);
         info!("Tile atlas: {:?}", game_assets.world_tile_atlas);
 
+        let (origin, bounds) = level_placement(
+            ldtk_level.world_position_px,
+            ldtk_level.dimensions_px,
+            ldtk_level.layers.front.grid_size as f32,
+        );
+
+        let collision_map = CollisionMap::from_layer(&ldtk_level.layers.front);
+
+        let entity_texture = match game_assets
+            .entity_sprites
+            .get_sub_handle("Frame0", &mut sub_handles)
+        {
+            Ok(entity_texture) => entity_texture,
+            Err(err) => {
+                error!("Could not get the entity sprites: {}", err);
+                Handle::default()
+            }
+        };
+
         commands.entity(entity).with_children(|parent| {
             let mut tilemap = TileMap::default();
-            add_layer(&ldtk_level.layers.front, 2, &mut tilemap);
+            add_layer(&ldtk_level.layers.front, 2, IVec2::ZERO, &mut tilemap);
 
-            parent.spawn_bundle(TileMapBundle {
-                tilemap,
-                texture_atlas: game_assets.world_tile_atlas.clone(),
-                ..Default::default()
-            });
+            parent
+                .spawn_bundle(TileMapBundle {
+                    tilemap,
+                    texture_atlas: game_assets.world_tile_atlas.clone(),
+                    ..Default::default()
+                })
+                .insert(LevelLayer::Front);
+
+            background::spawn_background(
+                parent,
+                &ldtk_level.layers.background,
+                ldtk_level.dimensions_px.x as f32,
+                game_assets.world_tile_atlas.clone(),
+                *background_settings,
+            );
+
+            spawn_level_objects(
+                parent,
+                &ldtk_level.layers.entities,
+                entity_texture,
+                &mut material_assets,
+                &enemy_settings,
+                &seed_settings,
+            );
+
+            hazard::spawn_checkpoints(parent, &ldtk_level.layers.entities, &mut material_assets);
+
+            spawn_emitters(parent, &ldtk_level.layers.entities, &asset_server);
+
+            seed::spawn_hidden_tiles(parent, &collision_map, game_assets.world_tile_atlas.clone());
+        });
+
+        commands
+            .entity(entity)
+            .insert(Transform::from_translation(origin.extend(0.)))
+            .insert(bounds)
+            .insert(collision_map)
+            .insert(LevelEntries::from_layer(&ldtk_level.layers.entities));
+    }
+}
+
+fn spawn_level_objects(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    entity_texture: Handle<Texture>,
+    material_assets: &mut Assets<ColorMaterial>,
+    enemy_settings: &EnemySettings,
+    seed_settings: &SeedSettings,
+) {
+    enemy::spawn_enemies(
+        parent,
+        layer,
+        entity_texture,
+        material_assets,
+        enemy_settings,
+    );
+
+    seed::spawn_seeds_and_exits(parent, layer, material_assets, seed_settings);
+}
+
+/// Spawns the particle emitters placed in LDtk, they keep running when the level is reset
+fn spawn_emitters(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    asset_server: &AssetServer,
+) {
+    let entities = if let bevy_spicy_ldtk::SpecialValues::Entities(entities) = &layer.special {
+        entities
+    } else {
+        error!("The entities layer does not contain entities");
+        return;
+    };
+
+    for entity in entities {
+        if let crate::levels::ProjectEntities::Emitter(emitter) = &entity.fields {
+            parent
+                .spawn()
+                .insert(ParticleEmitter::new(
+                    asset_server.load(emitter.effect.as_str()),
+                ))
+                .insert(Transform::from_translation(
+                    entity.position_px.as_f32().extend(0.),
+                ))
+                .insert(GlobalTransform::default());
+        }
+    }
+}
+
+/// Respawns the enemies, seeds and exits of a level as they are placed in LDtk
+fn reset_levels(
+    mut commands: Commands,
+    mut reset_events: EventReader<ResetLevelEvent>,
+    game_assets: Res<GameAssets>,
+    ldtk_assets: Res<Assets<crate::levels::Project>>,
+    enemy_settings: Res<EnemySettings>,
+    seed_settings: Res<SeedSettings>,
+    mut sub_handles: ResMut<SubHandles>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    level_query: Query<(&Handle<crate::levels::Project>, &LevelName, &Children)>,
+    object_query: Query<(), With<LevelObject>>,
+) {
+    for ResetLevelEvent(level_entity) in reset_events.iter() {
+        let (level_handle, level_name, children) = if let Ok(level) = level_query.get(*level_entity)
+        {
+            level
+        } else {
+            continue;
+        };
+
+        let ldtk_project = if let Some(ldtk) = ldtk_assets.get(level_handle) {
+            ldtk
+        } else {
+            error!("Could not get ldtk level for the level reset");
+            continue;
+        };
+
+        let ldtk_level = if let Some(level) = ldtk_project
+            .levels
+            .iter()
+            .find(|level| level.identifier == level_name.0)
+        {
+            level
+        } else {
+            error!("Could not find level with name {} in project", level_name.0);
+            continue;
+        };
+
+        info!("Resetting the level: {}", level_name.0);
+
+        for child in children.iter() {
+            if object_query.get(*child).is_ok() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+
+        let entity_texture = match game_assets
+            .entity_sprites
+            .get_sub_handle("Frame0", &mut sub_handles)
+        {
+            Ok(entity_texture) => entity_texture,
+            Err(err) => {
+                error!("Could not get the entity sprites: {}", err);
+                Handle::default()
+            }
+        };
+
+        commands.entity(*level_entity).with_children(|parent| {
+            spawn_level_objects(
+                parent,
+                &ldtk_level.layers.entities,
+                entity_texture,
+                &mut material_assets,
+                &enemy_settings,
+                &seed_settings,
+            );
         });
     }
 }
@@ -125,8 +499,17 @@ fn load_new_levels(
 fn update_levels(
     mut level_asset_events: EventReader<AssetEvent<crate::levels::Project>>,
     ldtk_assets: Res<Assets<crate::levels::Project>>,
-    level_query: Query<(&Handle<crate::levels::Project>, &LevelName, &Children)>,
-    mut tile_map_query: Query<&mut TileMap>,
+    background_settings: Res<BackgroundSettings>,
+    mut level_query: Query<(
+        &Handle<crate::levels::Project>,
+        &LevelName,
+        &Children,
+        &mut Transform,
+        &mut LevelBounds,
+        &mut CollisionMap,
+        &mut LevelEntries,
+    )>,
+    mut tile_map_query: Query<(&mut TileMap, &LevelLayer, Option<&mut ParallaxLayer>)>,
 ) {
     for asset_event in level_asset_events.iter() {
         let asset_handle = match asset_event {
@@ -134,7 +517,16 @@ fn update_levels(
             AssetEvent::Removed { .. } => continue,
         };
 
-        for (level_handle, level_name, children) in level_query.iter() {
+        for (
+            level_handle,
+            level_name,
+            children,
+            mut transform,
+            mut bounds,
+            mut collision_map,
+            mut entries,
+        ) in level_query.iter_mut()
+        {
             if asset_handle != level_handle {
                 continue;
             }
@@ -157,20 +549,111 @@ fn update_levels(
                 continue;
             };
 
+            let (origin, new_bounds) = level_placement(
+                ldtk_level.world_position_px,
+                ldtk_level.dimensions_px,
+                ldtk_level.layers.front.grid_size as f32,
+            );
+            transform.translation = origin.extend(transform.translation.z);
+            if *bounds != new_bounds {
+                *bounds = new_bounds;
+            }
+            *collision_map = CollisionMap::from_layer(&ldtk_level.layers.front);
+            *entries = LevelEntries::from_layer(&ldtk_level.layers.entities);
+
             for child in children.iter() {
-                if let Ok(mut tile_map) = tile_map_query.get_mut(*child) {
-                    tile_map.clear();
+                if let Ok((mut tile_map, level_layer, parallax_layer)) =
+                    tile_map_query.get_mut(*child)
+                {
+                    match (level_layer, parallax_layer) {
+                        (LevelLayer::Front, _) => {
+                            tile_map.clear();
 
-                    add_layer(&ldtk_level.layers.front, 2, &mut tile_map);
+                            add_layer(&ldtk_level.layers.front, 2, IVec2::ZERO, &mut tile_map);
+                        }
+                        (LevelLayer::Background, Some(mut parallax_layer)) => {
+                            background::update_background(
+                                &ldtk_level.layers.background,
+                                ldtk_level.dimensions_px.x as f32,
+                                *background_settings,
+                                &mut tile_map,
+                                &mut parallax_layer,
+                            );
+                        }
+                        (LevelLayer::Background, None) => {}
+                        (LevelLayer::Hidden, _) => {
+                            seed::fill_hidden_tiles(&collision_map, &mut tile_map);
+                        }
+                    }
                 }
             }
         }
     }
 }
 
+/// Returns where a level is placed in the world and the rectangle it covers
+///
+/// Tiles are centered on their grid position, so a level extends half a tile to the left of and
+/// below its origin.
+fn level_placement(
+    world_position_px: IVec2,
+    dimensions_px: IVec2,
+    grid_size: f32,
+) -> (Vec2, LevelBounds) {
+    let origin = world_position_px.as_f32();
+    let size = dimensions_px.as_f32();
+    let half_tile = grid_size / 2.;
+
+    let bounds = Rect {
+        left: origin.x - half_tile,
+        right: origin.x + size.x - half_tile,
+        bottom: origin.y - half_tile,
+        top: origin.y + size.y - half_tile,
+    };
+
+    (origin, LevelBounds(bounds))
+}
+
+/// Keeps the limits of all cameras in sync with the bounds of the [`CurrentLevel`]
+fn limit_cameras_to_level(
+    current_level: Option<Res<CurrentLevel>>,
+    world_levels: Res<WorldLevels>,
+    bounds_query: Query<&LevelBounds>,
+    changed_bounds_query: Query<(), Changed<LevelBounds>>,
+    mut camera_query: Query<&mut Free2DCamera>,
+) {
+    let current_level = if let Some(current_level) = current_level {
+        current_level
+    } else {
+        return;
+    };
+
+    let level_entity = if let Some(entity) = world_levels.level_map.levels.get(&current_level.0) {
+        *entity
+    } else {
+        return;
+    };
+
+    if !current_level.is_changed() && changed_bounds_query.get(level_entity).is_err() {
+        return;
+    }
+
+    let bounds = if let Ok(bounds) = bounds_query.get(level_entity) {
+        bounds
+    } else {
+        // The level has not been loaded yet
+        return;
+    };
+
+    for mut free_2d_camera in camera_query.iter_mut() {
+        free_2d_camera.set_limits(Some(bounds.0));
+    }
+}
+
 fn add_layer(
     layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
     height: i32,
+    offset: IVec2,
     map: &mut TileMap,
 ) {
     match &layer.special {
@@ -180,7 +663,7 @@ fn add_layer(
         | bevy_spicy_ldtk::SpecialValues::Tiles { tiles, .. }
         | bevy_spicy_ldtk::SpecialValues::AutoLayer { auto_layer: tiles } => {
             for tile in tiles {
-                let pos = tile.position_px / layer.grid_size as i32;
+                let pos = tile.position_px / layer.grid_size as i32 + offset;
                 // info!("Spawning at {}", pos);
                 let sprite_index = tile.id as _;
                 let mut flags = TileFlags::empty();
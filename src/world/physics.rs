@@ -0,0 +1,291 @@
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+
+use crate::{
+    camera::world_to_tile,
+    game_config::{changed_config, ConfigChanged},
+    tick::FixedTick,
+    GameAssets,
+};
+
+/// Keeps bodies from getting stuck exactly on the edge of a tile
+const SKIN: f32 = 0.01;
+
+/// What a cell of the `Front` IntGrid layer is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileKind {
+    Empty,
+    Solid,
+    Lava,
+    Ladder,
+    /// Only solid (and visible) while revealed by a glowing seed
+    Hidden,
+}
+
+impl TileKind {
+    fn from_int_grid(value: i64) -> TileKind {
+        match value {
+            1 => TileKind::Solid,
+            2 => TileKind::Lava,
+            3 => TileKind::Ladder,
+            4 => TileKind::Hidden,
+            _ => TileKind::Empty,
+        }
+    }
+}
+
+/// The IntGrid of a level, in the local coordinates of the level entity
+#[derive(Debug, Default)]
+pub struct CollisionMap {
+    pub grid_size: f32,
+    pub size: IVec2,
+    /// Whether [`TileKind::Hidden`] tiles are solid
+    pub revealed: bool,
+    cells: HashMap<IVec2, TileKind>,
+}
+
+impl CollisionMap {
+    pub fn from_layer(layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>) -> Self {
+        let mut cells = HashMap::default();
+
+        if let bevy_spicy_ldtk::SpecialValues::IntGrid { values, .. } = &layer.special {
+            for value in values {
+                let kind = TileKind::from_int_grid(value.value);
+
+                if kind != TileKind::Empty {
+                    cells.insert(value.grid_position, kind);
+                }
+            }
+        }
+
+        CollisionMap {
+            grid_size: layer.grid_size as f32,
+            size: layer.dimensions_cell,
+            revealed: false,
+            cells,
+        }
+    }
+
+    pub fn cell_at(&self, position: Vec2) -> IVec2 {
+        world_to_tile(position, Vec2::ZERO, Vec2::splat(self.grid_size))
+    }
+
+    /// Cells outside of the level to the left and right are solid, so nothing walks out of it
+    pub fn tile(&self, cell: IVec2) -> TileKind {
+        if cell.x < 0 || cell.x >= self.size.x {
+            return TileKind::Solid;
+        }
+
+        self.cells.get(&cell).copied().unwrap_or(TileKind::Empty)
+    }
+
+    pub fn tile_at(&self, position: Vec2) -> TileKind {
+        self.tile(self.cell_at(position))
+    }
+
+    pub fn is_solid(&self, cell: IVec2) -> bool {
+        match self.tile(cell) {
+            TileKind::Solid => true,
+            TileKind::Hidden => self.revealed,
+            _ => false,
+        }
+    }
+
+    pub fn is_solid_at(&self, position: Vec2) -> bool {
+        self.is_solid(self.cell_at(position))
+    }
+
+    pub fn cells_of_kind(&self, kind: TileKind) -> impl Iterator<Item = IVec2> + '_ {
+        self.cells
+            .iter()
+            .filter(move |(_, cell_kind)| **cell_kind == kind)
+            .map(|(cell, _)| *cell)
+    }
+
+    /// All cells overlapped by the box around `center`
+    pub fn overlapping_cells(&self, center: Vec2, half_size: Vec2) -> impl Iterator<Item = IVec2> {
+        let min = self.cell_at(center - half_size + Vec2::splat(SKIN));
+        let max = self.cell_at(center + half_size - Vec2::splat(SKIN));
+
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Whether the box around `center` touches a tile of the given kind
+    pub fn touches(&self, center: Vec2, half_size: Vec2, kind: TileKind) -> bool {
+        self.overlapping_cells(center, half_size)
+            .any(|cell| self.tile(cell) == kind)
+    }
+}
+
+/// Velocity in pixels per second
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Velocity(pub Vec2);
+
+/// An axis aligned box around the position of an entity
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    pub half_size: Vec2,
+}
+
+/// Gets moved by its [`Velocity`] and collides with the solid tiles of the level it is in
+///
+/// Bodies have to be children of a level entity, their position is local to it.
+#[derive(Debug, Clone)]
+pub struct KinematicBody {
+    /// Downwards acceleration in pixels per second squared
+    pub gravity: f32,
+    pub on_ground: bool,
+    /// The direction (`-1.` or `1.`) of the wall the body ran into this frame
+    pub hit_wall: Option<f32>,
+    pub hit_ceiling: bool,
+    /// The downwards speed the body landed with this frame, `0.` if it did not land
+    pub impact_speed: f32,
+}
+
+impl KinematicBody {
+    pub fn new(gravity: f32) -> Self {
+        KinematicBody {
+            gravity,
+            on_ground: false,
+            hit_wall: None,
+            hit_ceiling: false,
+            impact_speed: 0.,
+        }
+    }
+}
+
+/// Limits shared by all bodies, read from the `[physics]` section of `game.config`
+#[derive(Debug, Clone)]
+pub struct PhysicsSettings {
+    /// Bodies never fall faster than this, in pixels per second
+    pub max_fall_speed: f32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        PhysicsSettings {
+            max_fall_speed: 600.,
+        }
+    }
+}
+
+pub fn load_physics_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<PhysicsSettings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    *settings = PhysicsSettings {
+        max_fall_speed: config.physics.max_fall_speed as f32,
+    };
+}
+
+pub fn move_bodies(
+    fixed_tick: Res<FixedTick>,
+    settings: Res<PhysicsSettings>,
+    map_query: Query<&CollisionMap>,
+    mut body_query: Query<(
+        &mut Transform,
+        &mut Velocity,
+        &mut KinematicBody,
+        &Collider,
+        &Parent,
+    )>,
+) {
+    let delta = fixed_tick.delta_seconds();
+
+    for (mut transform, mut velocity, mut body, collider, parent) in body_query.iter_mut() {
+        let map = if let Ok(map) = map_query.get(parent.0) {
+            map
+        } else {
+            continue;
+        };
+
+        velocity.0.y = (velocity.0.y - body.gravity * delta).max(-settings.max_fall_speed);
+
+        let mut position = transform.translation.xy();
+        let movement = velocity.0 * delta;
+
+        body.hit_wall = None;
+        if move_axis(map, &mut position, collider.half_size, movement.x, 0) {
+            body.hit_wall = Some(movement.x.signum());
+            velocity.0.x = 0.;
+        }
+
+        let was_on_ground = body.on_ground;
+        body.on_ground = false;
+        body.hit_ceiling = false;
+        body.impact_speed = 0.;
+        if move_axis(map, &mut position, collider.half_size, movement.y, 1) {
+            if movement.y < 0. {
+                body.on_ground = true;
+                if !was_on_ground {
+                    body.impact_speed = -velocity.0.y;
+                }
+            } else {
+                body.hit_ceiling = true;
+            }
+            velocity.0.y = 0.;
+        }
+
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+/// Moves `position` along one axis, stopping at the first solid tile. Returns whether it hit one.
+fn move_axis(
+    map: &CollisionMap,
+    position: &mut Vec2,
+    half_size: Vec2,
+    movement: f32,
+    axis: usize,
+) -> bool {
+    if movement == 0. {
+        return false;
+    }
+
+    position[axis] += movement;
+
+    let half_grid = map.grid_size / 2.;
+    let mut hit = false;
+
+    for cell in map.overlapping_cells(*position, half_size) {
+        if !map.is_solid(cell) {
+            continue;
+        }
+
+        let cell_center = cell.as_f32()[axis] * map.grid_size;
+
+        // Push the body back out on the side it came from
+        let resolved = if movement > 0. {
+            cell_center - half_grid - half_size[axis] - SKIN
+        } else {
+            cell_center + half_grid + half_size[axis] + SKIN
+        };
+
+        if (movement > 0. && resolved < position[axis])
+            || (movement < 0. && resolved > position[axis])
+        {
+            position[axis] = resolved;
+        }
+
+        hit = true;
+    }
+
+    hit
+}
+
+/// Whether two boxes overlap
+pub fn overlaps(a: Vec2, a_half_size: Vec2, b: Vec2, b_half_size: Vec2) -> bool {
+    let distance = (a - b).abs();
+    let reach = a_half_size + b_half_size;
+
+    distance.x < reach.x && distance.y < reach.y
+}
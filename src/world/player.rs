@@ -0,0 +1,245 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    actions::{Action, TickActions},
+    audio::{SoundEffect, SoundEvent},
+    camera::CameraTarget,
+    game_config::{changed_config, ConfigChanged},
+    particles::ParticleEvent,
+    tick::Interpolated,
+    utils::{GetSubHandle, SubHandles},
+    GameAssets,
+};
+
+use super::{
+    enemy::Stomper,
+    hazard::{Dying, Health, Respawn},
+    physics::{Collider, KinematicBody, Velocity},
+    seed::{Carrier, Seed, SeedSettings},
+    startup::WorldLevels,
+    CurrentLevel,
+};
+
+/// The name of the `LevelEntry` the player starts a level at
+pub const START_ENTRY: &str = "Start";
+
+/// The dust kicked up when the player lands hard enough
+const LANDING_DUST: &str = "particles/dust.emitter";
+/// Falling speed in pixels per second above which landing kicks up dust
+const LANDING_DUST_SPEED: f32 = 150.;
+
+#[derive(Debug, Default)]
+pub struct Player;
+
+/// Movement of the player, read from the `[player]` section of `game.config`
+#[derive(Debug, Clone)]
+pub struct PlayerSettings {
+    pub walk_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    pub max_health: u32,
+    /// Seconds the player can not be hurt after taking damage
+    pub invulnerable_time: f32,
+    /// Length of the death sequence in seconds
+    pub death_time: f32,
+    pub death_hop_speed: f32,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            walk_speed: 90.,
+            jump_speed: 300.,
+            gravity: 900.,
+            max_health: 3,
+            invulnerable_time: 1.5,
+            death_time: 1.5,
+            death_hop_speed: 250.,
+        }
+    }
+}
+
+/// The `LevelEntry` entities of a level by name, in local coordinates of the level
+#[derive(Debug, Default)]
+pub struct LevelEntries(pub HashMap<String, Vec2>);
+
+impl LevelEntries {
+    pub fn from_layer(layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>) -> Self {
+        let mut entries = HashMap::default();
+
+        if let bevy_spicy_ldtk::SpecialValues::Entities(entities) = &layer.special {
+            for entity in entities {
+                if let crate::levels::ProjectEntities::LevelEntry(entry) = &entity.fields {
+                    entries.insert(entry.name.clone(), entity.position_px.as_f32());
+                }
+            }
+        }
+
+        LevelEntries(entries)
+    }
+
+    /// The start of the level, or any entry if it has none called [`START_ENTRY`]
+    pub fn start(&self) -> Option<Vec2> {
+        self.0
+            .get(START_ENTRY)
+            .or_else(|| self.0.values().next())
+            .copied()
+    }
+}
+
+/// Spawns the player in the [`CurrentLevel`] once it is loaded
+pub fn spawn_player(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    settings: Res<PlayerSettings>,
+    current_level: Option<Res<CurrentLevel>>,
+    world_levels: Res<WorldLevels>,
+    mut sub_handles: ResMut<SubHandles>,
+    mut material_assets: ResMut<Assets<ColorMaterial>>,
+    player_query: Query<(), With<Player>>,
+    level_query: Query<&LevelEntries>,
+) {
+    if !player_query.is_empty() {
+        return;
+    }
+
+    let current_level = if let Some(current_level) = current_level {
+        current_level
+    } else {
+        return;
+    };
+
+    let level_entity = if let Some(entity) = world_levels.level_map.levels.get(&current_level.0) {
+        *entity
+    } else {
+        return;
+    };
+
+    let entries = if let Ok(entries) = level_query.get(level_entity) {
+        entries
+    } else {
+        // The level has not been loaded yet
+        return;
+    };
+
+    let start = if let Some(start) = entries.start() {
+        start
+    } else {
+        error!("Level {} has no LevelEntry to spawn at", current_level.0);
+        Vec2::ZERO
+    };
+
+    let texture = match game_assets
+        .entity_sprites
+        .get_sub_handle("Frame0", &mut sub_handles)
+    {
+        Ok(texture) => texture,
+        Err(err) => {
+            error!("Could not get the player sprite: {}", err);
+            return;
+        }
+    };
+
+    commands.entity(level_entity).with_children(|parent| {
+        parent
+            .spawn_bundle(SpriteBundle {
+                material: material_assets.add(texture.into()),
+                transform: Transform::from_translation(start.extend(4.)),
+                ..Default::default()
+            })
+            .insert(Player)
+            .insert(CameraTarget)
+            .insert(Stomper)
+            .insert(Carrier::default())
+            .insert(Health::new(settings.max_health))
+            .insert(Respawn::default())
+            .insert(Velocity::default())
+            .insert(Interpolated::default())
+            .insert(Collider {
+                half_size: Vec2::new(6., 7.),
+            })
+            .insert(KinematicBody::new(settings.gravity));
+    });
+}
+
+pub fn move_player(
+    actions: Res<TickActions>,
+    settings: Res<PlayerSettings>,
+    seed_settings: Res<SeedSettings>,
+    seed_query: Query<&Seed>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut player_query: Query<
+        (
+            &mut Velocity,
+            &mut Sprite,
+            &KinematicBody,
+            &Carrier,
+            &Transform,
+            &Collider,
+            &Parent,
+        ),
+        With<Player>,
+    >,
+) {
+    for (mut velocity, mut sprite, body, carrier, transform, collider, parent) in
+        player_query.iter_mut()
+    {
+        if body.impact_speed > LANDING_DUST_SPEED {
+            let feet = transform.translation.truncate() - Vec2::new(0., collider.half_size.y);
+            particle_events.send(ParticleEvent::in_level(LANDING_DUST, parent.0, feet));
+        }
+
+        let effects = carrier
+            .seed
+            .and_then(|seed| seed_query.get(seed).ok())
+            .map(|seed| seed_settings.effects(seed.kind))
+            .unwrap_or_default();
+
+        let direction = actions.axis(Action::MoveLeft, Action::MoveRight);
+        velocity.0.x = direction * settings.walk_speed * effects.speed_factor;
+
+        if direction != 0. {
+            sprite.flip_x = direction < 0.;
+        }
+
+        if body.on_ground && actions.just_pressed(Action::Jump) {
+            velocity.0.y = settings.jump_speed * effects.jump_factor;
+            sound_events.send(SoundEvent::in_level(
+                SoundEffect::Jump,
+                parent.0,
+                transform.translation.truncate(),
+            ));
+        }
+    }
+}
+
+pub fn load_player_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<PlayerSettings>,
+    mut body_query: Query<&mut KinematicBody, With<Player>>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    *settings = PlayerSettings {
+        walk_speed: config.player.walk_speed as f32,
+        jump_speed: config.player.jump_speed as f32,
+        gravity: config.player.gravity as f32,
+        max_health: config.player.max_health as u32,
+        invulnerable_time: config.player.invulnerable_time as f32,
+        death_time: config.player.death_time as f32,
+        death_hop_speed: config.player.death_hop_speed as f32,
+    };
+
+    for mut body in body_query.iter_mut() {
+        body.gravity = settings.gravity;
+    }
+}
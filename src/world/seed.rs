@@ -0,0 +1,499 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_simple_tilemap::{prelude::TileMapBundle, Tile, TileMap};
+
+use crate::{
+    actions::{Action, TickActions},
+    audio::{SoundEffect, SoundEvent},
+    game_config::{changed_config, ConfigChanged},
+    particles::ParticleEvent,
+    tick::Interpolated,
+    GameAssets,
+};
+
+use super::{
+    physics::{overlaps, Collider, CollisionMap, KinematicBody, TileKind, Velocity},
+    LevelLayer, LevelName, LevelObject,
+};
+
+/// The sprite of the world tileset drawn for revealed hidden tiles
+const HIDDEN_TILE_SPRITE: u32 = 0;
+
+/// How far above its carrier a carried seed is held
+const CARRY_HEIGHT: f32 = 12.;
+
+/// The sparkles where a seed is delivered
+const DELIVERED_SPARKLES: &str = "particles/sparkles.emitter";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedKind {
+    /// Slows down and weighs down whoever carries it
+    Heavy,
+    /// Breaks when it hits the ground too hard
+    Fragile,
+    /// Reveals the hidden tiles of the level while it is carried
+    Glowing,
+}
+
+impl From<&crate::levels::SeedKind> for SeedKind {
+    fn from(kind: &crate::levels::SeedKind) -> Self {
+        match kind {
+            crate::levels::SeedKind::Heavy => SeedKind::Heavy,
+            crate::levels::SeedKind::Fragile => SeedKind::Fragile,
+            crate::levels::SeedKind::Glowing => SeedKind::Glowing,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Seed {
+    pub kind: SeedKind,
+    /// The entity carrying the seed
+    pub carrier: Option<Entity>,
+}
+
+/// Can pick up, carry and drop a single seed
+#[derive(Debug, Default)]
+pub struct Carrier {
+    pub seed: Option<Entity>,
+}
+
+/// Where the seeds of a level have to be brought to
+#[derive(Debug, Clone)]
+pub struct Exit {
+    /// At least one, the level is completed by delivering seeds and not by walking into the exit
+    pub required_seeds: u32,
+    pub delivered_seeds: u32,
+}
+
+impl Exit {
+    pub fn is_complete(&self) -> bool {
+        self.delivered_seeds >= self.required_seeds
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeedDeliveredEvent {
+    pub seed: SeedKind,
+    pub exit: Entity,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SeedBrokenEvent {
+    pub seed: SeedKind,
+}
+
+/// Sent once enough seeds have reached the exit of a level
+#[derive(Debug, Clone)]
+pub struct LevelCompleteEvent {
+    pub level: String,
+}
+
+/// How a seed changes the movement of its carrier
+#[derive(Debug, Clone, Copy)]
+pub struct SeedEffects {
+    pub speed_factor: f32,
+    pub jump_factor: f32,
+}
+
+impl Default for SeedEffects {
+    fn default() -> Self {
+        SeedEffects {
+            speed_factor: 1.,
+            jump_factor: 1.,
+        }
+    }
+}
+
+/// Tuning of the seeds, read from the `[seeds]` section of `game.config`
+#[derive(Debug, Clone)]
+pub struct SeedSettings {
+    pub heavy: SeedEffects,
+    /// Landing faster than this (in pixels per second) breaks a fragile seed
+    pub fragile_break_speed: f32,
+    pub gravity: f32,
+}
+
+impl Default for SeedSettings {
+    fn default() -> Self {
+        SeedSettings {
+            heavy: SeedEffects {
+                speed_factor: 0.7,
+                jump_factor: 0.6,
+            },
+            fragile_break_speed: 400.,
+            gravity: 900.,
+        }
+    }
+}
+
+impl SeedSettings {
+    pub fn effects(&self, kind: SeedKind) -> SeedEffects {
+        match kind {
+            SeedKind::Heavy => self.heavy,
+            SeedKind::Fragile | SeedKind::Glowing => SeedEffects::default(),
+        }
+    }
+}
+
+/// Spawns the seeds and exits placed in the `Entities` layer of a level as children of it
+pub fn spawn_seeds_and_exits(
+    parent: &mut ChildBuilder,
+    layer: &bevy_spicy_ldtk::Layer<crate::levels::ProjectEntities>,
+    materials: &mut Assets<ColorMaterial>,
+    settings: &SeedSettings,
+) {
+    let entities = if let bevy_spicy_ldtk::SpecialValues::Entities(entities) = &layer.special {
+        entities
+    } else {
+        error!("The entities layer does not contain entities");
+        return;
+    };
+
+    for entity in entities {
+        let position = entity.position_px.as_f32();
+
+        match &entity.fields {
+            crate::levels::ProjectEntities::Seed(seed) => {
+                let kind = SeedKind::from(&seed.seed_kind);
+                let color = match kind {
+                    SeedKind::Heavy => Color::rgb_u8(139, 69, 19),
+                    SeedKind::Fragile => Color::rgb_u8(237, 228, 252),
+                    SeedKind::Glowing => Color::rgb_u8(227, 195, 91),
+                };
+
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        material: materials.add(color.into()),
+                        sprite: Sprite::new(Vec2::new(8., 8.)),
+                        transform: Transform::from_translation(position.extend(5.)),
+                        ..Default::default()
+                    })
+                    .insert(Seed {
+                        kind,
+                        carrier: None,
+                    })
+                    .insert(LevelObject)
+                    .insert(Velocity::default())
+                    .insert(Interpolated::default())
+                    .insert(Collider {
+                        half_size: Vec2::new(4., 4.),
+                    })
+                    .insert(KinematicBody::new(settings.gravity));
+            }
+            crate::levels::ProjectEntities::Exit(exit) => {
+                if exit.required_seeds < 1 {
+                    warn!(
+                        "The exit at {} requires {} seeds, it needs at least one",
+                        position, exit.required_seeds
+                    );
+                }
+
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        material: materials.add(Color::rgba(1., 1., 1., 0.5).into()),
+                        sprite: Sprite::new(Vec2::new(16., 16.)),
+                        transform: Transform::from_translation(position.extend(1.)),
+                        ..Default::default()
+                    })
+                    .insert(Exit {
+                        required_seeds: exit.required_seeds.max(1) as u32,
+                        delivered_seeds: 0,
+                    })
+                    .insert(LevelObject)
+                    .insert(Collider {
+                        half_size: Vec2::new(8., 8.),
+                    });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Spawns the tilemap showing the hidden tiles of a level while they are revealed
+pub fn spawn_hidden_tiles(
+    parent: &mut ChildBuilder,
+    collision_map: &CollisionMap,
+    texture_atlas: Handle<TextureAtlas>,
+) {
+    let mut tilemap = TileMap::default();
+    fill_hidden_tiles(collision_map, &mut tilemap);
+
+    parent
+        .spawn_bundle(TileMapBundle {
+            tilemap,
+            texture_atlas,
+            ..Default::default()
+        })
+        .insert(Visible {
+            is_visible: false,
+            is_transparent: true,
+        })
+        .insert(LevelLayer::Hidden);
+}
+
+pub fn fill_hidden_tiles(collision_map: &CollisionMap, tilemap: &mut TileMap) {
+    tilemap.clear();
+
+    for cell in collision_map.cells_of_kind(TileKind::Hidden) {
+        tilemap.set_tile(
+            cell.extend(2),
+            Some(Tile {
+                sprite_index: HIDDEN_TILE_SPRITE,
+                ..Default::default()
+            }),
+        );
+    }
+}
+
+/// Picks up a seed touching the carrier or drops the carried one
+pub fn pick_up_and_drop_seeds(
+    mut commands: Commands,
+    actions: Res<TickActions>,
+    settings: Res<SeedSettings>,
+    mut carrier_query: Query<(
+        Entity,
+        &mut Carrier,
+        &Transform,
+        &Collider,
+        &Velocity,
+        &Parent,
+    )>,
+    mut seed_query: Query<
+        (
+            Entity,
+            &mut Seed,
+            &Transform,
+            &Collider,
+            &mut Velocity,
+            &Parent,
+        ),
+        Without<Carrier>,
+    >,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+
+    for (
+        carrier_entity,
+        mut carrier,
+        carrier_transform,
+        carrier_collider,
+        carrier_velocity,
+        carrier_parent,
+    ) in carrier_query.iter_mut()
+    {
+        if let Some(seed_entity) = carrier.seed.take() {
+            if let Ok((_, mut seed, _, _, mut velocity, _)) = seed_query.get_mut(seed_entity) {
+                seed.carrier = None;
+                velocity.0 = carrier_velocity.0;
+                commands
+                    .entity(seed_entity)
+                    .insert(KinematicBody::new(settings.gravity));
+            }
+            continue;
+        }
+
+        let carrier_position = carrier_transform.translation.xy();
+
+        let seed = seed_query
+            .iter_mut()
+            .find(|(_, seed, transform, collider, _, parent)| {
+                seed.carrier.is_none()
+                    && parent.0 == carrier_parent.0
+                    && overlaps(
+                        carrier_position,
+                        carrier_collider.half_size,
+                        transform.translation.xy(),
+                        collider.half_size,
+                    )
+            });
+
+        if let Some((seed_entity, mut seed, _, _, mut velocity, _)) = seed {
+            seed.carrier = Some(carrier_entity);
+            velocity.0 = Vec2::ZERO;
+            carrier.seed = Some(seed_entity);
+            commands.entity(seed_entity).remove::<KinematicBody>();
+        }
+    }
+}
+
+/// Keeps carried seeds above their carrier
+pub fn carry_seeds(
+    carrier_query: Query<&Transform, With<Carrier>>,
+    mut seed_query: Query<(&Seed, &mut Transform), Without<Carrier>>,
+) {
+    for (seed, mut transform) in seed_query.iter_mut() {
+        let carrier = if let Some(carrier) = seed.carrier {
+            carrier
+        } else {
+            continue;
+        };
+
+        if let Ok(carrier_transform) = carrier_query.get(carrier) {
+            let position = carrier_transform.translation.xy() + Vec2::new(0., CARRY_HEIGHT);
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Breaks fragile seeds that hit the ground too hard, either on their own or with their carrier
+pub fn break_fragile_seeds(
+    mut commands: Commands,
+    settings: Res<SeedSettings>,
+    mut broken_events: EventWriter<SeedBrokenEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut carrier_query: Query<(&mut Carrier, &KinematicBody)>,
+    seed_query: Query<
+        (Entity, &Seed, Option<&KinematicBody>, &Transform, &Parent),
+        Without<Carrier>,
+    >,
+) {
+    for (seed_entity, seed, body, transform, parent) in seed_query.iter() {
+        if seed.kind != SeedKind::Fragile {
+            continue;
+        }
+
+        let impact_speed = match (seed.carrier, body) {
+            (Some(carrier), _) => carrier_query
+                .get_mut(carrier)
+                .map_or(0., |(_, body)| body.impact_speed),
+            (None, Some(body)) => body.impact_speed,
+            (None, None) => 0.,
+        };
+
+        if impact_speed <= settings.fragile_break_speed {
+            continue;
+        }
+
+        if let Some(carrier) = seed.carrier {
+            if let Ok((mut carrier, _)) = carrier_query.get_mut(carrier) {
+                carrier.seed = None;
+            }
+        }
+
+        broken_events.send(SeedBrokenEvent { seed: seed.kind });
+        sound_events.send(SoundEvent::in_level(
+            SoundEffect::SeedBroken,
+            parent.0,
+            transform.translation.truncate(),
+        ));
+        commands.entity(seed_entity).despawn_recursive();
+    }
+}
+
+/// Hidden tiles of a level are solid and visible while a glowing seed is carried in it
+pub fn reveal_hidden_tiles(
+    seed_query: Query<(&Seed, &Parent)>,
+    mut level_query: Query<(Entity, &mut CollisionMap, &Children)>,
+    mut layer_query: Query<(&LevelLayer, &mut Visible)>,
+) {
+    for (level_entity, mut collision_map, children) in level_query.iter_mut() {
+        let revealed = seed_query.iter().any(|(seed, parent)| {
+            seed.kind == SeedKind::Glowing && seed.carrier.is_some() && parent.0 == level_entity
+        });
+
+        if collision_map.revealed == revealed {
+            continue;
+        }
+
+        collision_map.revealed = revealed;
+
+        for child in children.iter() {
+            if let Ok((LevelLayer::Hidden, mut visible)) = layer_query.get_mut(*child) {
+                visible.is_visible = revealed;
+            }
+        }
+    }
+}
+
+/// Takes seeds that touch an exit and completes the level once enough arrived
+pub fn deliver_seeds(
+    mut commands: Commands,
+    mut delivered_events: EventWriter<SeedDeliveredEvent>,
+    mut complete_events: EventWriter<LevelCompleteEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut carrier_query: Query<&mut Carrier>,
+    seed_query: Query<(Entity, &Seed, &Transform, &Collider, &Parent)>,
+    mut exit_query: Query<(Entity, &mut Exit, &Transform, &Collider, &Parent)>,
+    level_query: Query<&LevelName>,
+) {
+    for (seed_entity, seed, seed_transform, seed_collider, seed_parent) in seed_query.iter() {
+        let exit =
+            exit_query
+                .iter_mut()
+                .find(|(_, _, exit_transform, exit_collider, exit_parent)| {
+                    exit_parent.0 == seed_parent.0
+                        && overlaps(
+                            seed_transform.translation.xy(),
+                            seed_collider.half_size,
+                            exit_transform.translation.xy(),
+                            exit_collider.half_size,
+                        )
+                });
+
+        let (exit_entity, mut exit, _, _, exit_parent) = if let Some(exit) = exit {
+            exit
+        } else {
+            continue;
+        };
+
+        if let Some(carrier) = seed.carrier {
+            if let Ok(mut carrier) = carrier_query.get_mut(carrier) {
+                carrier.seed = None;
+            }
+        }
+        commands.entity(seed_entity).despawn_recursive();
+
+        let was_complete = exit.is_complete();
+        exit.delivered_seeds += 1;
+        delivered_events.send(SeedDeliveredEvent {
+            seed: seed.kind,
+            exit: exit_entity,
+        });
+        sound_events.send(SoundEvent::in_level(
+            SoundEffect::SeedDelivered,
+            seed_parent.0,
+            seed_transform.translation.truncate(),
+        ));
+        particle_events.send(ParticleEvent::in_level(
+            DELIVERED_SPARKLES,
+            seed_parent.0,
+            seed_transform.translation.truncate(),
+        ));
+
+        if exit.is_complete() && !was_complete {
+            if let Ok(level_name) = level_query.get(exit_parent.0) {
+                info!("Completed the level: {}", level_name.0);
+                complete_events.send(LevelCompleteEvent {
+                    level: level_name.0.clone(),
+                });
+            }
+        }
+    }
+}
+
+pub fn load_seed_settings_from_config(
+    mut config_events: EventReader<ConfigChanged>,
+    config_assets: Res<Assets<crate::config::Root>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut settings: ResMut<SeedSettings>,
+) {
+    let config = if let Some(config) =
+        changed_config(&mut config_events, &config_assets, game_assets.as_deref())
+    {
+        config
+    } else {
+        return;
+    };
+
+    *settings = SeedSettings {
+        heavy: SeedEffects {
+            speed_factor: config.seeds.heavy.speed_factor as f32,
+            jump_factor: config.seeds.heavy.jump_factor as f32,
+        },
+        fragile_break_speed: config.seeds.fragile.break_speed as f32,
+        gravity: config.seeds.gravity as f32,
+    };
+}
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 
-use crate::GameAssets;
+use crate::{camera::Free2DCamera, GameAssets};
 
-use super::LevelMap;
+use super::{CurrentLevel, Level, LevelMap};
 
 /// The world management entity
 #[derive(Debug)]
@@ -22,7 +22,20 @@ pub fn setup_levels(mut commands: Commands, game_assets: Res<GameAssets>) {
     });
 }
 
-pub fn remove_level(mut commands: Commands, world: Res<WorldLevels>) {
+pub fn remove_level(
+    mut commands: Commands,
+    world: Res<WorldLevels>,
+    level_query: Query<Entity, With<Level>>,
+    mut camera_query: Query<&mut Free2DCamera>,
+) {
     commands.entity(world.main_entity).despawn_recursive();
+    for level in level_query.iter() {
+        commands.entity(level).despawn_recursive();
+    }
     commands.remove_resource::<WorldLevels>();
+    commands.remove_resource::<CurrentLevel>();
+
+    for mut free_2d_camera in camera_query.iter_mut() {
+        free_2d_camera.set_limits(None);
+    }
 }
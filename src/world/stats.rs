@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+
+use crate::tick::FixedTick;
+
+use super::{
+    hazard::{CheckpointReachedEvent, PlayerDiedEvent},
+    seed::{SeedBrokenEvent, SeedDeliveredEvent},
+    CurrentLevel,
+};
+
+/// How the player is doing in the [`CurrentLevel`], reset whenever a new level is entered
+#[derive(Debug, Default, Clone)]
+pub struct LevelStats {
+    /// Seconds spent in the level
+    pub time: f32,
+    pub deaths: u32,
+    /// Seconds of progress thrown away by dying, from the last checkpoint up to the death
+    pub time_lost: f32,
+    /// Seeds at the exit, dying resets the level and with it this count
+    pub seeds_delivered: u32,
+    pub seeds_broken: u32,
+    since_checkpoint: f32,
+}
+
+pub fn track_level_stats(
+    fixed_tick: Res<FixedTick>,
+    current_level: Option<Res<CurrentLevel>>,
+    mut stats: ResMut<LevelStats>,
+    mut died_events: EventReader<PlayerDiedEvent>,
+    mut checkpoint_events: EventReader<CheckpointReachedEvent>,
+    mut delivered_events: EventReader<SeedDeliveredEvent>,
+    mut broken_events: EventReader<SeedBrokenEvent>,
+) {
+    let current_level = if let Some(current_level) = current_level {
+        current_level
+    } else {
+        return;
+    };
+
+    if current_level.is_changed() {
+        *stats = LevelStats::default();
+    }
+
+    let delta = fixed_tick.delta_seconds();
+    stats.time += delta;
+    stats.since_checkpoint += delta;
+
+    if checkpoint_events.iter().count() > 0 {
+        stats.since_checkpoint = 0.;
+    }
+
+    for _ in died_events.iter() {
+        stats.deaths += 1;
+        stats.time_lost += stats.since_checkpoint;
+        stats.since_checkpoint = 0.;
+        stats.seeds_delivered = 0;
+    }
+
+    stats.seeds_delivered += delivered_events.iter().count() as u32;
+    stats.seeds_broken += broken_events.iter().count() as u32;
+}